@@ -17,6 +17,8 @@ fn main() {
 	let has = ctx.has(ContentFormat::Files);
 	println!("has_files={}", has);
 
-	let files = ctx.get_files().unwrap_or_default();
-	println!("{:?}", files);
+	match ctx.get_files() {
+		Ok(files) => println!("{:?}", files),
+		Err(e) => println!("failed to read files: {}", e),
+	}
 }
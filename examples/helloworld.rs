@@ -8,18 +8,21 @@ fn main() {
 	let has_rtf = ctx.has(ContentFormat::Rtf);
 	println!("has_rtf={}", has_rtf);
 
-	let rtf = ctx.get_rich_text().unwrap_or("".to_string());
-
-	println!("rtf={}", rtf);
+	match ctx.get_rich_text() {
+		Ok(rtf) => println!("rtf={}", rtf),
+		Err(e) => println!("failed to read rtf: {}", e),
+	}
 
 	let has_html = ctx.has(ContentFormat::Html);
 	println!("has_html={}", has_html);
 
-	let html = ctx.get_html().unwrap_or("".to_string());
-
-	println!("html={}", html);
-
-	let content = ctx.get_text().unwrap_or("".to_string());
+	match ctx.get_html() {
+		Ok(html) => println!("html={}", html),
+		Err(e) => println!("failed to read html: {}", e),
+	}
 
-	println!("txt={}", content);
+	match ctx.get_text() {
+		Ok(content) => println!("txt={}", content),
+		Err(e) => println!("failed to read text: {}", e),
+	}
 }
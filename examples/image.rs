@@ -1,6 +1,5 @@
-#[cfg(target_os = "linux")]
-use clipboard_rs::ClipboardContextX11Options;
-use clipboard_rs::{common::RustImage, Clipboard, ClipboardContext};
+use clipboard_rs::{common::RustImage, Clipboard, ClipboardContext, ClipboardContextBuilder};
+use std::time::Duration;
 
 #[cfg(target_os = "macos")]
 const TMP_PATH: &str = "/tmp/";
@@ -17,14 +16,14 @@ const TMP_PATH: &str = "C:\\Windows\\Temp\\";
 ))]
 const TMP_PATH: &str = "/tmp/";
 
-#[cfg(target_os = "linux")]
 fn setup_clipboard() -> ClipboardContext {
-	ClipboardContext::new_with_options(ClipboardContextX11Options { read_timeout: None }).unwrap()
-}
-
-#[cfg(not(target_os = "linux"))]
-fn setup_clipboard() -> ClipboardContext {
-	ClipboardContext::new().unwrap()
+	// large images may take a while to be transferred on X11
+	ClipboardContextBuilder::default()
+		.with_read_timeout(Duration::from_secs(10))
+		.with_open_retries(20)
+		.with_retry_delay(Duration::from_millis(10))
+		.build()
+		.unwrap()
 }
 
 fn main() {
@@ -1,30 +1,21 @@
 use clipboard_rs::{
-	Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
+	Clipboard, ClipboardEvent, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
 };
 use std::{thread, time::Duration};
 
-struct Manager {
-	ctx: ClipboardContext,
-}
-
-impl Manager {
-	pub fn new() -> Self {
-		let ctx = ClipboardContext::new().unwrap();
-		Manager { ctx }
-	}
-}
+struct Manager;
 
 impl ClipboardHandler for Manager {
-	fn on_clipboard_change(&mut self) {
-		println!(
-			"on_clipboard_change, txt = {}",
-			self.ctx.get_text().unwrap_or("".to_string())
-		);
+	fn on_clipboard_change_ctx(&mut self, clipboard: &dyn Clipboard, _event: &ClipboardEvent) {
+		match clipboard.get_text() {
+			Ok(txt) => println!("on_clipboard_change, txt = {}", txt),
+			Err(e) => println!("on_clipboard_change, no text: {}", e),
+		}
 	}
 }
 
 fn main() {
-	let manager = Manager::new();
+	let manager = Manager;
 
 	let mut watcher = ClipboardWatcherContext::new().unwrap();
 
@@ -0,0 +1,100 @@
+use crate::common::{LinuxBackend, Result, XSelection};
+use crate::ClipboardContext;
+use std::time::Duration;
+
+/// zh: 默认的剪贴板读取超时（毫秒）
+/// en: Default timeout of clipboard read operations in milliseconds
+pub const DEFAULT_READ_TIMEOUT: u64 = 500;
+
+/// zh: 默认的打开剪贴板重试次数
+/// en: Default number of retries when opening the clipboard
+pub const DEFAULT_OPEN_RETRIES: u32 = 10;
+
+/// zh: 用于在所有平台上以统一方式创建 [`ClipboardContext`]，不适用于当前平台的选项会被忽略
+/// en: Creates a [`ClipboardContext`] the same way on every platform, options that do not apply to the current platform are ignored
+#[derive(Clone, Debug)]
+// not every option is read on every platform
+#[allow(dead_code)]
+pub struct ClipboardContextBuilder {
+	pub(crate) read_timeout: Option<Duration>,
+	pub(crate) open_retries: u32,
+	pub(crate) retry_delay: Duration,
+	pub(crate) selection: XSelection,
+	pub(crate) pasteboard_name: Option<String>,
+	pub(crate) backend: Option<LinuxBackend>,
+}
+
+impl Default for ClipboardContextBuilder {
+	fn default() -> Self {
+		ClipboardContextBuilder {
+			read_timeout: Some(Duration::from_millis(DEFAULT_READ_TIMEOUT)),
+			open_retries: DEFAULT_OPEN_RETRIES,
+			retry_delay: Duration::ZERO,
+			selection: XSelection::default(),
+			pasteboard_name: None,
+			backend: None,
+		}
+	}
+}
+
+impl ClipboardContextBuilder {
+	/// zh: 设置剪贴板读取操作的超时，等同于 `read_timeout(Some(timeout))`，仅在 X11 上生效
+	/// en: Set the timeout of clipboard read operations, the same as `read_timeout(Some(timeout))`, only takes effect on X11
+	pub fn with_read_timeout(self, timeout: Duration) -> Self {
+		self.read_timeout(Some(timeout))
+	}
+
+	/// zh: 设置剪贴板读取操作的超时，None 表示一直等待，仅在 X11 上生效
+	/// en: Set the timeout of clipboard read operations, None means waiting forever, only takes effect on X11
+	pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+		self.read_timeout = timeout;
+		self
+	}
+
+	/// zh: 设置剪贴板被其他程序占用时打开剪贴板的重试次数，仅在 Windows 上生效
+	/// en: Set the number of retries when the clipboard is held open by another program, only takes effect on Windows
+	pub fn with_open_retries(mut self, retries: u32) -> Self {
+		self.open_retries = retries;
+		self
+	}
+
+	/// zh: 设置打开剪贴板的总尝试次数（包括第一次），等同于 `with_open_retries(attempts - 1)`，仅在 Windows 上生效
+	/// en: Set the total number of attempts (including the first one) to open the clipboard, the same as `with_open_retries(attempts - 1)`, only takes effect on Windows
+	pub fn clipboard_open_attempts(self, attempts: u32) -> Self {
+		self.with_open_retries(attempts.saturating_sub(1))
+	}
+
+	/// zh: 设置打开剪贴板时每次重试之间的间隔，仅在 Windows 上生效
+	/// en: Set the delay between retries when opening the clipboard, only takes effect on Windows
+	pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+		self.retry_delay = delay;
+		self
+	}
+
+	/// zh: 设置读写的选区，仅在 X11 上生效
+	/// en: Set the selection to read from and write to, only takes effect on X11
+	pub fn with_selection(mut self, selection: XSelection) -> Self {
+		self.selection = selection;
+		self
+	}
+
+	/// zh: 设置使用的粘贴板名称（例如 NSPasteboardNameFind 对应的 "Apple CFPasteboard find"），默认使用通用粘贴板，仅在 macOS 上生效
+	/// en: Set the name of the pasteboard to use (e.g. "Apple CFPasteboard find" for NSPasteboardNameFind), the general pasteboard is used by default, only takes effect on macOS
+	pub fn pasteboard_name(mut self, name: &str) -> Self {
+		self.pasteboard_name = Some(name.to_owned());
+		self
+	}
+
+	/// zh: 强制使用指定的后端，而不是根据 `WAYLAND_DISPLAY`、`XDG_SESSION_TYPE` 和 `DISPLAY` 自动选择，例如在 Wayland 会话中通过 Xwayland 使用 X11，仅在 Linux 上生效
+	/// en: Force the given backend instead of choosing one from `WAYLAND_DISPLAY`, `XDG_SESSION_TYPE` and `DISPLAY`, e.g. to use X11 through Xwayland in a Wayland session, only takes effect on Linux
+	pub fn force_backend(mut self, backend: LinuxBackend) -> Self {
+		self.backend = Some(backend);
+		self
+	}
+
+	/// zh: 创建剪贴板上下文
+	/// en: Create the clipboard context
+	pub fn build(self) -> Result<ClipboardContext> {
+		ClipboardContext::from_builder(&self)
+	}
+}
@@ -1,30 +1,544 @@
+use crate::Clipboard;
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
 use image::imageops::FilterType;
-use image::{DynamicImage, GenericImageView, ImageFormat, RgbaImage};
+use image::metadata::Orientation;
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader, RgbaImage};
 use std::error::Error;
-use std::io::Cursor;
+use std::fmt;
+use std::io::{BufRead, Cursor, Seek};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 pub type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync + 'static>>;
 
+/// zh: 可以区分的剪贴板错误，通过 `error.downcast_ref::<ClipboardError>()` 从 [`Result`] 的错误中取得
+/// en: Clipboard errors that callers can tell apart, obtained from the error of a [`Result`] with `error.downcast_ref::<ClipboardError>()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClipboardError {
+	/// zh: 平台相关的错误，`code` 为平台的错误码，没有则为 0
+	/// en: A platform specific error, `code` is the error code of the platform, 0 if there is none
+	PlatformError { code: i32, message: String },
+	/// zh: 等待剪贴板数据超时，例如 X11 上选区所有者在读取超时内没有回应
+	/// en: Timed out while waiting for clipboard data, e.g. the selection owner on X11 did not answer within the read timeout
+	Timeout,
+	/// zh: 没能取得剪贴板的所有权，例如 X11 上剪贴板管理器立即抢回了选区，调用方可以稍后重试
+	/// en: Failed to take ownership of the clipboard, e.g. a clipboard manager on X11 grabbed the selection back right away, callers may retry later
+	OwnershipFailed,
+	/// zh: 与显示服务器的连接已断开，并且重新连接失败，例如 X server 重启后在重试次数内没能连上
+	/// en: The connection to the display server was lost and could not be re-established, e.g. the X server did not come back within the reconnection retries after a restart
+	ConnectionLost,
+	/// zh: 其他错误
+	/// en: Any other error
+	Other(String),
+}
+
+impl fmt::Display for ClipboardError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ClipboardError::PlatformError { code, message } => {
+				write!(f, "{} (code = {})", message, code)
+			}
+			ClipboardError::Timeout => write!(f, "Timeout while waiting for clipboard data"),
+			ClipboardError::OwnershipFailed => {
+				write!(f, "Failed to take ownership of the clipboard")
+			}
+			ClipboardError::ConnectionLost => {
+				write!(f, "The connection to the display server was lost")
+			}
+			ClipboardError::Other(message) => write!(f, "{}", message),
+		}
+	}
+}
+
+impl Error for ClipboardError {}
+
 pub trait ContentData {
 	fn get_format(&self) -> ContentFormat;
 
 	fn as_bytes(&self) -> &[u8];
 
 	fn as_str(&self) -> Result<&str>;
+
+	/// zh: 内容的大小（字节），不需要编码：文本为 UTF-8 字节数，图片为未压缩的 RGBA 大小（宽 × 高 × 4），文件为所有路径的长度之和，其他格式为数据长度
+	/// en: The size of the content in bytes, without encoding it: the UTF-8 length for text, the uncompressed RGBA size (width × height × 4) for images, the total length of the paths for files, the length of the data for other formats
+	fn size_in_bytes(&self) -> usize {
+		self.as_bytes().len()
+	}
 }
 
 pub trait ClipboardHandler {
-	fn on_clipboard_change(&mut self);
+	/// zh: 剪切板变化时调用，如果实现了其他回调方法则可以不实现该方法
+	/// en: Called when the clipboard changes, may be left unimplemented when one of the other callbacks is implemented
+	fn on_clipboard_change(&mut self) {}
+
+	/// zh: 剪切板变化时调用，携带变化的详细信息，默认实现会调用 [`ClipboardHandler::on_clipboard_change_with_source`]
+	/// en: Called when the clipboard changes with the details of the change, the default implementation calls [`ClipboardHandler::on_clipboard_change_with_source`]
+	fn on_clipboard_event(&mut self, event: &ClipboardEvent) {
+		self.on_clipboard_change_with_source(event.source);
+	}
+
+	/// zh: 剪切板变化时调用，携带变化的来源，可以用来忽略本进程自己的写入，默认实现会调用 [`ClipboardHandler::on_clipboard_change`]
+	/// en: Called when the clipboard changes with the origin of the change, which can be used to ignore the writes of this process, the default implementation calls [`ClipboardHandler::on_clipboard_change`]
+	fn on_clipboard_change_with_source(&mut self, _source: ChangeSource) {
+		self.on_clipboard_change();
+	}
+
+	/// zh: 剪切板变化时调用，携带监听器持有的剪切板，可以直接用来读取变化后的内容，默认实现会调用 [`ClipboardHandler::on_clipboard_event`]
+	/// en: Called when the clipboard changes with the clipboard owned by the watcher, which can be used to read the new content directly, the default implementation calls [`ClipboardHandler::on_clipboard_event`]
+	fn on_clipboard_change_ctx(&mut self, _clipboard: &dyn Clipboard, event: &ClipboardEvent) {
+		self.on_clipboard_event(event);
+	}
+
+	/// zh: 监听出现无法恢复的错误、即将停止时调用，默认不做任何处理
+	/// en: Called when the watcher hits an unrecoverable error and is about to stop, does nothing by default
+	fn on_watch_error(&mut self, _error: &(dyn Error + Send + Sync)) {}
+}
+
+// lets a closure be used as a handler, e.g. `watcher.add_handler(|| println!("changed"))`
+impl<F: FnMut() + Send> ClipboardHandler for F {
+	fn on_clipboard_change(&mut self) {
+		self();
+	}
+}
+
+// lets one watcher hold handlers of different types, e.g. `ClipboardWatcherContext<Box<dyn ClipboardHandler + Send>>`
+impl ClipboardHandler for Box<dyn ClipboardHandler + Send> {
+	fn on_clipboard_change(&mut self) {
+		(**self).on_clipboard_change();
+	}
+
+	fn on_clipboard_event(&mut self, event: &ClipboardEvent) {
+		(**self).on_clipboard_event(event);
+	}
+
+	fn on_clipboard_change_with_source(&mut self, source: ChangeSource) {
+		(**self).on_clipboard_change_with_source(source);
+	}
+
+	fn on_clipboard_change_ctx(&mut self, clipboard: &dyn Clipboard, event: &ClipboardEvent) {
+		(**self).on_clipboard_change_ctx(clipboard, event);
+	}
+
+	fn on_watch_error(&mut self, error: &(dyn Error + Send + Sync)) {
+		(**self).on_watch_error(error);
+	}
+}
+
+/// zh: 剪切板变化的来源
+/// en: The origin of a clipboard change
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChangeSource {
+	/// zh: 本进程中的剪切板上下文写入的
+	/// en: Written by a clipboard context of this process
+	OwnWrite,
+	/// zh: 其他程序修改的
+	/// en: Changed by another program
+	#[default]
+	ExternalChange,
+}
+
+/// zh: 剪切板变化的详细信息
+/// en: Details of a clipboard change
+#[derive(Clone, Debug)]
+pub struct ClipboardEvent {
+	/// zh: 监听循环观察到变化的时刻
+	/// en: The moment the watcher loop observed the change
+	pub instant: Instant,
+	/// zh: 观察到变化时的系统时间
+	/// en: The wall-clock time at which the change was observed
+	pub time: SystemTime,
+	/// zh: 平台提供的时间戳（X11 上为 X server 的毫秒时间），没有则为 None
+	/// en: The timestamp provided by the platform (the X server time in milliseconds on X11), if any
+	pub platform_timestamp: Option<u64>,
+	/// zh: 发生变化的选区，只有 X11 上会是 CLIPBOARD 以外的值
+	/// en: The selection that changed, only X11 reports selections other than CLIPBOARD
+	pub selection: XSelection,
+	/// zh: 变化的来源。X11 上根据选区所有者是否为本进程的窗口判断；Windows 和 macOS 上根据本进程最近一次写入后的序列号判断，是一种近似
+	/// en: The origin of the change. On X11 it tells whether the selection owner is a window of this process; on Windows and macOS it compares with the sequence number after the last write of this process, which is an approximation
+	pub source: ChangeSource,
+}
+
+impl ClipboardEvent {
+	pub(crate) fn new(platform_timestamp: Option<u64>) -> Self {
+		ClipboardEvent {
+			instant: Instant::now(),
+			time: SystemTime::now(),
+			platform_timestamp,
+			selection: XSelection::Clipboard,
+			source: ChangeSource::default(),
+		}
+	}
+}
+
+/// zh: 剪切板格式的详细信息
+/// en: Detailed information of a clipboard format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatInfo {
+	/// zh: 格式名称，查询失败或格式没有名称时为 None
+	/// en: The name of the format, None if the lookup failed or the format has no name
+	pub name: Option<String>,
+	/// zh: 平台的格式标识（X11 上为 Atom，Windows 上为格式编号），没有数字标识的平台上为 0
+	/// en: The identifier of the format on the platform (the Atom on X11, the format number on Windows), 0 on platforms without numeric identifiers
+	pub id: u32,
+}
+
+/// zh: 写入剪切板内容的程序，由 macOS 和 Windows 上的 `ClipboardContext::get_owner` 返回
+/// en: The program that wrote the clipboard content, returned by `ClipboardContext::get_owner` on macOS and Windows
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClipboardOwner {
+	/// zh: 程序名称，macOS 上为本地化的应用名称，Windows 上为可执行文件名（不含扩展名）
+	/// en: The name of the program, the localized application name on macOS, the executable file name without the extension on Windows
+	pub app_name: Option<String>,
+	/// zh: 程序的进程 ID
+	/// en: The process id of the program
+	pub pid: Option<u32>,
+}
+
+/// zh: 剪切板全部内容的快照，由 [`crate::Clipboard::snapshot`] 创建，可以通过 [`crate::Clipboard::restore`] 写回剪切板。
+/// 每种格式都保存为原始字节，图片不会被重新解码
+/// en: A snapshot of the whole clipboard content, created by [`crate::Clipboard::snapshot`] and written back with [`crate::Clipboard::restore`].
+/// Every format is kept as its raw bytes, images are not re-decoded
+#[derive(Clone, Debug, Default)]
+pub struct ClipboardSnapshot {
+	// 每一项对应一个剪切板条目（macOS 上的 NSPasteboardItem），其他平台只有一项
+	pub(crate) items: Vec<Vec<(FormatInfo, Vec<u8>)>>,
+}
+
+impl ClipboardSnapshot {
+	/// zh: 快照中保存的所有格式名称
+	/// en: The names of all formats kept in the snapshot
+	pub fn formats(&self) -> Vec<&str> {
+		self.items
+			.iter()
+			.flatten()
+			.filter_map(|(info, _)| info.name.as_deref())
+			.collect()
+	}
+
+	/// zh: 获得快照中指定格式的数据，有多个条目时返回第一个
+	/// en: Get the data of the given format in the snapshot, the first one is returned when several items have it
+	pub fn get(&self, format: &str) -> Option<&[u8]> {
+		self.items
+			.iter()
+			.flatten()
+			.find(|(info, _)| info.name.as_deref() == Some(format))
+			.map(|(_, data)| data.as_slice())
+	}
+
+	/// zh: 快照是否为空
+	/// en: Check if the snapshot is empty
+	pub fn is_empty(&self) -> bool {
+		self.items.iter().all(|item| item.is_empty())
+	}
+}
+
+/// zh: 可以从剪切板读取的类型，通过 [`crate::Clipboard::read`] 使用，例如 `let image: RustImageData = ctx.read()?;`
+/// en: A type that can be read from the clipboard, used through [`crate::Clipboard::read`], e.g. `let image: RustImageData = ctx.read()?;`
+pub trait FromClipboard: Sized {
+	fn from_clipboard(clipboard: &dyn Clipboard) -> Result<Self>;
+}
+
+/// zh: 可以写入剪切板的类型，通过 [`crate::Clipboard::write`] 使用，例如 `ctx.write("hello")?;`
+/// en: A type that can be written to the clipboard, used through [`crate::Clipboard::write`], e.g. `ctx.write("hello")?;`
+pub trait ToClipboard {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()>;
+}
+
+impl FromClipboard for String {
+	fn from_clipboard(clipboard: &dyn Clipboard) -> Result<Self> {
+		clipboard.get_text()
+	}
+}
+
+impl FromClipboard for RustImageData {
+	fn from_clipboard(clipboard: &dyn Clipboard) -> Result<Self> {
+		clipboard.get_image()
+	}
+}
+
+impl FromClipboard for Vec<PathBuf> {
+	fn from_clipboard(clipboard: &dyn Clipboard) -> Result<Self> {
+		clipboard.get_files_as_paths()
+	}
+}
+
+impl FromClipboard for ClipboardSnapshot {
+	fn from_clipboard(clipboard: &dyn Clipboard) -> Result<Self> {
+		clipboard.snapshot()
+	}
+}
+
+impl ToClipboard for String {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()> {
+		clipboard.set_text(self)
+	}
+}
+
+impl ToClipboard for &str {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()> {
+		clipboard.set_text(self.to_owned())
+	}
 }
 
+impl ToClipboard for RustImageData {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()> {
+		clipboard.set_image(self)
+	}
+}
+
+impl ToClipboard for Vec<PathBuf> {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()> {
+		let files = self
+			.iter()
+			.map(|path| path.to_string_lossy().into_owned())
+			.collect();
+		clipboard.set_files(files)
+	}
+}
+
+impl ToClipboard for ClipboardContent {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()> {
+		clipboard.set(vec![self])
+	}
+}
+
+impl ToClipboard for Vec<ClipboardContent> {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()> {
+		clipboard.set(self)
+	}
+}
+
+impl ToClipboard for ClipboardSnapshot {
+	fn to_clipboard(self, clipboard: &dyn Clipboard) -> Result<()> {
+		clipboard.restore(self)
+	}
+}
+
+/// zh: 停止监听的通道，可以克隆后交给多个持有者，只有调用 [`WatcherShutdown::stop`] 才会停止监听
+/// en: The channel to stop watching, can be cloned and handed to several owners, the watcher only stops when [`WatcherShutdown::stop`] is called
+#[derive(Clone)]
+pub struct WatcherShutdown {
+	stop_signal: Sender<()>,
+	// 监听器退出 start_watch 后会发送一次
+	done_receiver: Arc<Mutex<Receiver<()>>>,
+	stop_on_drop: bool,
+}
+
+// 等待监听器退出的默认超时
+const DEFAULT_STOP_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl WatcherShutdown {
+	pub(crate) fn new(
+		stop_signal: Sender<()>,
+		done_receiver: Arc<Mutex<Receiver<()>>>,
+		stop_on_drop: bool,
+	) -> Self {
+		WatcherShutdown {
+			stop_signal,
+			done_receiver,
+			stop_on_drop,
+		}
+	}
+
+	/// zh: 停止监视
+	/// en: stop watching
+	pub fn stop(self) {
+		// with stop_on_drop the signal is sent by drop
+		if !self.stop_on_drop {
+			let _ = self.stop_signal.send(());
+		}
+	}
+
+	/// zh: 停止监视，并阻塞直到 `start_watch` 退出，最多等待 5 秒，超时返回 Err
+	/// en: Stop watching and block until `start_watch` has returned, waits at most 5 seconds, returns Err on timeout
+	#[allow(clippy::result_unit_err)]
+	pub fn wait_for_completion(self) -> std::result::Result<(), ()> {
+		self.wait_for_completion_timeout(DEFAULT_STOP_WAIT_TIMEOUT)
+	}
+
+	/// zh: 停止监视，并阻塞直到 `start_watch` 退出，最多等待 `timeout`，超时返回 Err
+	/// en: Stop watching and block until `start_watch` has returned, waits at most `timeout`, returns Err on timeout
+	#[allow(clippy::result_unit_err)]
+	pub fn wait_for_completion_timeout(mut self, timeout: Duration) -> std::result::Result<(), ()> {
+		let done_receiver = self.done_receiver.lock().map_err(|_| ())?;
+		// drop the completion left over from a previous session of the watcher
+		while done_receiver.try_recv().is_ok() {}
+		let _ = self.stop_signal.send(());
+		// the signal has been sent, do not send it again on drop
+		self.stop_on_drop = false;
+		done_receiver.recv_timeout(timeout).map_err(|_| ())
+	}
+}
+
+impl Drop for WatcherShutdown {
+	fn drop(&mut self) {
+		if self.stop_on_drop {
+			let _ = self.stop_signal.send(());
+		}
+	}
+}
+
+// 将变化事件发送给所有订阅者，接收端已被丢弃的订阅者会被移除
+pub(crate) fn notify_subscribers(
+	subscribers: &mut Vec<Sender<ClipboardEvent>>,
+	event: &ClipboardEvent,
+) {
+	subscribers.retain(|sender| sender.send(event.clone()).is_ok());
+}
+
+// 监听器中的处理器，formats 为 None 时处理所有变化
+pub(crate) struct HandlerEntry<T> {
+	pub(crate) handler: T,
+	pub(crate) formats: Option<Vec<ContentFormat>>,
+	// 通过 add_named_handler 添加时的名称
+	pub(crate) name: Option<String>,
+}
+
+impl<T> HandlerEntry<T> {
+	pub(crate) fn new(handler: T, formats: Option<Vec<ContentFormat>>) -> Self {
+		HandlerEntry {
+			handler,
+			formats,
+			name: None,
+		}
+	}
+}
+
+// 添加命名处理器，已有同名处理器时原地替换，保持其顺序
+pub(crate) fn insert_named_handler<T>(handlers: &mut Vec<HandlerEntry<T>>, name: &str, handler: T) {
+	let entry = HandlerEntry {
+		handler,
+		formats: None,
+		name: Some(name.to_owned()),
+	};
+	match handlers
+		.iter_mut()
+		.find(|existing| existing.name.as_deref() == Some(name))
+	{
+		Some(existing) => *existing = entry,
+		None => handlers.push(entry),
+	}
+}
+
+// 按添加顺序的下标移除处理器，下标越界时返回 None
+pub(crate) fn take_handler_at<T>(handlers: &mut Vec<HandlerEntry<T>>, index: usize) -> Option<T> {
+	if index < handlers.len() {
+		Some(handlers.remove(index).handler)
+	} else {
+		None
+	}
+}
+
+// 按名称移除处理器
+pub(crate) fn take_named_handler<T>(handlers: &mut Vec<HandlerEntry<T>>, name: &str) -> Option<T> {
+	let index = handlers
+		.iter()
+		.position(|entry| entry.name.as_deref() == Some(name))?;
+	Some(handlers.remove(index).handler)
+}
+
+// 通知关心当前剪切板内容的处理器，每种格式在一次变化中最多通过 `has` 检查一次
+pub(crate) fn dispatch_to_handlers<T: ClipboardHandler>(
+	handlers: &mut [HandlerEntry<T>],
+	clipboard: &dyn Clipboard,
+	event: &ClipboardEvent,
+	mut has: impl FnMut(&ContentFormat) -> bool,
+) {
+	let mut checked: Vec<(ContentFormat, bool)> = Vec::new();
+	for entry in handlers.iter_mut() {
+		if let Some(formats) = &entry.formats {
+			let matched = formats.iter().any(|format| {
+				match checked
+					.iter()
+					.find(|(checked_format, _)| checked_format == format)
+				{
+					Some((_, present)) => *present,
+					None => {
+						let present = has(format);
+						checked.push((format.clone(), present));
+						present
+					}
+				}
+			});
+			if !matched {
+				continue;
+			}
+		}
+		entry.handler.on_clipboard_change_ctx(clipboard, event);
+	}
+}
+
+/// zh: 限制剪切板变化事件分发给处理器的频率。
+/// 一连串变化中的第一个事件会立即分发，之后的事件会被合并，最后一次变化总会在间隔结束后分发
+/// en: Limit the rate at which clipboard change events are dispatched to handlers.
+/// The first event of a burst is delivered immediately, the following ones are
+/// coalesced, and the last change of the burst is always delivered once the
+/// interval has elapsed (trailing-edge delivery)
+#[derive(Default)]
+pub(crate) struct EventThrottle {
+	min_interval: Option<Duration>,
+	last_dispatch: Option<Instant>,
+	pending: bool,
+}
+
+impl EventThrottle {
+	/// zh: `max_events_per_second <= 0`（或不是有限值）时不限制频率
+	/// en: `max_events_per_second <= 0` (or not finite) disables rate limiting
+	pub(crate) fn set_max_event_rate(&mut self, max_events_per_second: f32) {
+		self.min_interval = if max_events_per_second.is_finite() && max_events_per_second > 0.0 {
+			Some(Duration::from_secs_f32(1.0 / max_events_per_second))
+		} else {
+			None
+		};
+	}
+
+	/// zh: 每次轮询时调用，`changed` 表示此次轮询是否观察到了剪切板变化，返回是否需要通知处理器
+	/// en: Called on every poll, `changed` indicates whether a clipboard change was observed, returns whether the handlers should be notified
+	pub(crate) fn should_dispatch(&mut self, changed: bool, now: Instant) -> bool {
+		self.pending |= changed;
+		if !self.pending {
+			return false;
+		}
+		let ready = match (self.min_interval, self.last_dispatch) {
+			(Some(interval), Some(last)) => now.saturating_duration_since(last) >= interval,
+			_ => true,
+		};
+		if ready {
+			self.pending = false;
+			self.last_dispatch = Some(now);
+		}
+		ready
+	}
+}
+
+#[derive(Clone, Debug)]
 pub enum ClipboardContent {
 	Text(String),
 	Rtf(String),
 	Html(String),
 	Image(RustImageData),
 	Files(Vec<String>),
+	/// zh: 带有操作类型的文件列表，剪切时文件管理器粘贴后会移动文件，macOS 上会忽略操作类型
+	/// en: Files with their operation, on cut the file manager moves the files when pasting, the operation is ignored on macOS
+	FilesWithOperation(Vec<String>, FileOperation),
+	Markdown(String),
+	Url(String),
 	Other(String, Vec<u8>),
 }
 
+impl ClipboardContent {
+	/// zh: 文件列表的操作类型，不是文件列表时为 None
+	/// en: The operation of the files, None if the content is not a file list
+	pub fn operation(&self) -> Option<FileOperation> {
+		match self {
+			ClipboardContent::Files(_) => Some(FileOperation::Copy),
+			ClipboardContent::FilesWithOperation(_, operation) => Some(*operation),
+			_ => None,
+		}
+	}
+}
+
 impl ContentData for ClipboardContent {
 	fn get_format(&self) -> ContentFormat {
 		match self {
@@ -32,7 +546,11 @@ impl ContentData for ClipboardContent {
 			ClipboardContent::Rtf(_) => ContentFormat::Rtf,
 			ClipboardContent::Html(_) => ContentFormat::Html,
 			ClipboardContent::Image(_) => ContentFormat::Image,
-			ClipboardContent::Files(_) => ContentFormat::Files,
+			ClipboardContent::Files(_) | ClipboardContent::FilesWithOperation(_, _) => {
+				ContentFormat::Files
+			}
+			ClipboardContent::Markdown(_) => ContentFormat::Markdown,
+			ClipboardContent::Url(_) => ContentFormat::Url,
 			ClipboardContent::Other(format, _) => ContentFormat::Other(format.clone()),
 		}
 	}
@@ -42,9 +560,11 @@ impl ContentData for ClipboardContent {
 			ClipboardContent::Text(data) => data.as_bytes(),
 			ClipboardContent::Rtf(data) => data.as_bytes(),
 			ClipboardContent::Html(data) => data.as_bytes(),
+			ClipboardContent::Markdown(data) => data.as_bytes(),
+			ClipboardContent::Url(data) => data.as_bytes(),
 			// dynamic image is not supported to as bytes
 			ClipboardContent::Image(_) => &[],
-			ClipboardContent::Files(data) => {
+			ClipboardContent::Files(data) | ClipboardContent::FilesWithOperation(data, _) => {
 				// use first file path as data
 				if let Some(path) = data.first() {
 					path.as_bytes()
@@ -61,8 +581,10 @@ impl ContentData for ClipboardContent {
 			ClipboardContent::Text(data) => Ok(data),
 			ClipboardContent::Rtf(data) => Ok(data),
 			ClipboardContent::Html(data) => Ok(data),
+			ClipboardContent::Markdown(data) => Ok(data),
+			ClipboardContent::Url(data) => Ok(data),
 			ClipboardContent::Image(_) => Err("can't convert image to string".into()),
-			ClipboardContent::Files(data) => {
+			ClipboardContent::Files(data) | ClipboardContent::FilesWithOperation(data, _) => {
 				// use first file path as data
 				if let Some(path) = data.first() {
 					Ok(path)
@@ -73,26 +595,264 @@ impl ContentData for ClipboardContent {
 			ClipboardContent::Other(_, data) => std::str::from_utf8(data).map_err(|e| e.into()),
 		}
 	}
+
+	fn size_in_bytes(&self) -> usize {
+		match self {
+			ClipboardContent::Image(image) => {
+				let (width, height) = image.get_size();
+				(width as usize)
+					.saturating_mul(height as usize)
+					.saturating_mul(4)
+			}
+			ClipboardContent::Files(files) | ClipboardContent::FilesWithOperation(files, _) => {
+				files.iter().map(String::len).sum()
+			}
+			_ => self.as_bytes().len(),
+		}
+	}
 }
 
-#[derive(Clone)]
+/// zh: 将纯文本包装为 RTF 文档，会转义 RTF 的控制字符，非 ASCII 字符使用 `\u` 表示
+/// en: Wrap plain text into an RTF document, RTF control characters are escaped and non-ASCII characters are written with `\u`
+pub fn wrap_plain_as_rtf(text: &str) -> String {
+	let mut rtf = String::from("{\\rtf1\\ansi\\deff0 ");
+	for c in text.chars() {
+		match c {
+			'\\' | '{' | '}' => {
+				rtf.push('\\');
+				rtf.push(c);
+			}
+			'\n' => rtf.push_str("\\par\n"),
+			'\r' => {}
+			c if c.is_ascii() => rtf.push(c),
+			c => {
+				// \u takes a signed 16-bit value, characters outside the BMP are written as surrogate pairs
+				let mut units = [0u16; 2];
+				for unit in c.encode_utf16(&mut units) {
+					rtf.push_str(&format!("\\u{}?", *unit as i16));
+				}
+			}
+		}
+	}
+	rtf.push('}');
+	rtf
+}
+
+// 把 text 追加到 current 之后，current 为空时不加分隔符
+pub(crate) fn append_text_to(current: String, text: &str, separator: Option<&str>) -> String {
+	if current.is_empty() {
+		return text.to_owned();
+	}
+	let mut joined = current;
+	joined.push_str(separator.unwrap_or(""));
+	joined.push_str(text);
+	joined
+}
+
+// 64 位 FNV-1a 哈希，算法固定，结果不随 Rust 版本或进程变化
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+	pub(crate) fn new() -> Self {
+		Fnv1a(0xcbf2_9ce4_8422_2325)
+	}
+
+	pub(crate) fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.0 ^= u64::from(*byte);
+			self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+
+	pub(crate) fn finish(&self) -> u64 {
+		self.0
+	}
+}
+
+// 检查 RTF 以 `{\rtf` 开头且括号配对（忽略转义的括号）
+pub(crate) fn validate_rtf(rtf: &str) -> Result<()> {
+	if !rtf.starts_with("{\\rtf") {
+		return Err("invalid RTF: it must start with {\\rtf".into());
+	}
+	let mut depth = 0usize;
+	let mut chars = rtf.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => {
+				// skip the escaped character, e.g. \{ \} \\
+				chars.next();
+			}
+			'{' => depth += 1,
+			'}' => {
+				depth = depth
+					.checked_sub(1)
+					.ok_or("invalid RTF: unbalanced braces")?;
+				if depth == 0 && !chars.as_str().trim().is_empty() {
+					return Err("invalid RTF: content after the end of the document".into());
+				}
+			}
+			_ => {}
+		}
+	}
+	if depth != 0 {
+		return Err("invalid RTF: unbalanced braces".into());
+	}
+	Ok(())
+}
+
+// 将 Markdown 渲染为 HTML，供不支持 Markdown 的程序粘贴
+#[cfg(feature = "markdown")]
+pub(crate) fn markdown_to_html(markdown: &str) -> String {
+	let parser = pulldown_cmark::Parser::new(markdown);
+	let mut html = String::new();
+	pulldown_cmark::html::push_html(&mut html, parser);
+	html
+}
+
+// 将 provider 生成的字节转换为对应格式的内容，不支持的格式返回 None
+pub(crate) fn content_from_bytes(
+	format: ContentFormat,
+	bytes: Vec<u8>,
+) -> Result<Option<ClipboardContent>> {
+	let to_string = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned();
+	Ok(match format {
+		ContentFormat::Text => Some(ClipboardContent::Text(to_string(bytes))),
+		ContentFormat::Rtf => Some(ClipboardContent::Rtf(to_string(bytes))),
+		ContentFormat::Html => Some(ClipboardContent::Html(to_string(bytes))),
+		ContentFormat::Image => Some(ClipboardContent::Image(RustImageData::from_bytes(&bytes)?)),
+		ContentFormat::Files => Some(ClipboardContent::Files(
+			to_string(bytes).lines().map(str::to_owned).collect(),
+		)),
+		ContentFormat::Markdown => Some(ClipboardContent::Markdown(to_string(bytes))),
+		ContentFormat::Url => Some(ClipboardContent::Url(to_string(bytes))),
+		ContentFormat::Metafile => None,
+		ContentFormat::Other(name) => Some(ClipboardContent::Other(name, bytes)),
+	})
+}
+
+// 将各平台的格式名称映射为对应的 ContentFormat，无法识别的名称作为自定义格式
+pub(crate) fn format_from_name(name: &str) -> ContentFormat {
+	match name {
+		// X11, macOS, Windows
+		"UTF8_STRING"
+		| "STRING"
+		| "TEXT"
+		| "text/plain"
+		| "text/plain;charset=utf-8"
+		| "public.utf8-plain-text"
+		| "CF_UNICODETEXT"
+		| "CF_TEXT"
+		| "CF_OEMTEXT" => ContentFormat::Text,
+		"text/rtf" | "text/richtext" | "public.rtf" | "Rich Text Format" => ContentFormat::Rtf,
+		"text/html" | "public.html" | "HTML Format" => ContentFormat::Html,
+		"image/png" | "public.png" | "public.tiff" | "PNG" | "CF_DIB" | "CF_DIBV5" => {
+			ContentFormat::Image
+		}
+		"text/uri-list" | "public.file-url" | "NSFilenamesPboardType" | "CF_HDROP" => {
+			ContentFormat::Files
+		}
+		"text/markdown" | "text/x-markdown" | "net.daringfireball.markdown" => {
+			ContentFormat::Markdown
+		}
+		"text/x-moz-url" | "public.url" | "UniformResourceLocator" => ContentFormat::Url,
+		other => ContentFormat::Other(other.to_owned()),
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ContentFormat {
 	Text,
 	Rtf,
 	Html,
 	Image,
 	Files,
+	/// zh: Markdown 文本，X11 和 Windows 上为 "text/markdown"，macOS 上为 "net.daringfireball.markdown"
+	/// en: Markdown text, "text/markdown" on X11 and Windows, "net.daringfireball.markdown" on macOS
+	Markdown,
+	/// zh: URL，X11 上为 "text/x-moz-url"，macOS 上为 "public.url"，Windows 上为 "UniformResourceLocator"
+	/// en: URL, "text/x-moz-url" on X11, "public.url" on macOS, "UniformResourceLocator" on Windows
+	Url,
+	/// zh: Windows 图元文件（CF_METAFILEPICT），其他平台不支持
+	/// en: Windows Metafile (CF_METAFILEPICT), unsupported on other platforms
+	Metafile,
 	Other(String),
 }
 
+/// zh: 剪切板中文件的操作类型，粘贴时由文件管理器决定是复制还是移动
+/// en: The operation of the files on the clipboard, the file manager decides whether to copy or move them when pasting
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileOperation {
+	#[default]
+	Copy,
+	Cut,
+}
+
+/// zh: X11 的选区，在其他平台上没有意义
+/// en: The X11 selection, meaningless on other platforms
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum XSelection {
+	/// zh: 通过复制/粘贴使用的 CLIPBOARD 选区
+	/// en: The CLIPBOARD selection used by copy/paste
+	#[default]
+	Clipboard,
+	/// zh: 选中文本、中键粘贴使用的 PRIMARY 选区
+	/// en: The PRIMARY selection used by select/middle-click paste
+	Primary,
+	/// zh: 很少使用的 SECONDARY 选区
+	/// en: The rarely used SECONDARY selection
+	Secondary,
+}
+
+/// zh: Linux 上的剪切板后端，在其他平台上没有意义
+/// en: The clipboard backend on Linux, meaningless on other platforms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinuxBackend {
+	/// zh: X11，也可以通过 Xwayland 使用
+	/// en: X11, also usable through Xwayland
+	X11,
+	/// zh: 通过 wlr-data-control 协议访问的 Wayland，需要启用 `wayland` 特性
+	/// en: Wayland through the wlr-data-control protocol, needs the `wayland` feature
+	Wayland,
+	/// zh: 通过 OSC 52 转义序列访问终端的剪切板，只支持纯文本，需要启用 `osc52` 特性，没有显示服务时（例如通过 SSH）自动使用
+	/// en: The clipboard of the terminal through the OSC 52 escape sequence, plain text only, needs the `osc52` feature, used automatically when there is no display server (e.g. over SSH)
+	Osc52,
+	/// zh: 进程内存中的剪切板（[`crate::MemoryClipboardContext`]），用于没有显示服务的测试环境，只有指定时才会使用
+	/// en: The clipboard in the process memory ([`crate::MemoryClipboardContext`]), for test environments without a display server, only used when asked for
+	Memory,
+}
+
+impl fmt::Display for LinuxBackend {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LinuxBackend::X11 => write!(f, "X11"),
+			LinuxBackend::Wayland => write!(f, "Wayland"),
+			LinuxBackend::Osc52 => write!(f, "OSC 52"),
+			LinuxBackend::Memory => write!(f, "Memory"),
+		}
+	}
+}
+
+#[derive(Clone)]
 pub struct RustImageData {
 	width: u32,
 	height: u32,
 	data: Option<DynamicImage>,
+	// 解码时读取到的原始 EXIF 数据
+	exif: Option<Vec<u8>>,
+}
+
+// 只打印尺寸，不打印像素数据
+impl fmt::Debug for RustImageData {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Image")
+			.field("width", &self.width)
+			.field("height", &self.height)
+			.finish()
+	}
 }
 
 /// 此处的 `RustImageBuffer` 已经是带有图片格式的字节流，例如 png,jpeg;
-pub struct RustImageBuffer(Vec<u8>);
+pub struct RustImageBuffer(Vec<u8>, ImageFormat);
 
 pub trait RustImage: Sized {
 	/// create an empty image
@@ -125,6 +885,18 @@ pub trait RustImage: Sized {
 	/// zh: 调整图片大小，不保留长宽比
 	fn resize(&self, width: u32, height: u32, filter: FilterType) -> Result<Self>;
 
+	/// en: Rotate the image clockwise, `degrees` must be 90, 180 or 270, negative values rotate counterclockwise (-90 is the same as 270)
+	/// zh: 顺时针旋转图片，`degrees` 只能是 90、180 或 270，负数表示逆时针旋转（-90 等同于 270）
+	fn rotate(&self, degrees: i32) -> Result<Self>;
+
+	/// en: Flip the image horizontally
+	/// zh: 水平翻转图片
+	fn flip_horizontal(&self) -> Result<Self>;
+
+	/// en: Flip the image vertically
+	/// zh: 垂直翻转图片
+	fn flip_vertical(&self) -> Result<Self>;
+
 	fn to_jpeg(&self) -> Result<RustImageBuffer>;
 
 	/// en: Convert to png format, the returned image is a new image, and the data itself will not be modified
@@ -133,6 +905,15 @@ pub trait RustImage: Sized {
 
 	fn to_bitmap(&self) -> Result<RustImageBuffer>;
 
+	/// en: Convert to tiff format
+	/// zh: 转为 tiff 格式
+	fn to_tiff(&self) -> Result<RustImageBuffer>;
+
+	/// en: Convert to webp format, the output is always lossless since the `image` crate only ships a lossless webp encoder
+	/// zh: 转为 webp 格式，目前 `image` 库只提供无损的 webp 编码器，所以输出总是无损的
+	#[cfg(feature = "webp")]
+	fn to_webp(&self) -> Result<RustImageBuffer>;
+
 	fn save_to_path(&self, path: &str) -> Result<()>;
 
 	fn get_dynamic_image(&self) -> Result<DynamicImage>;
@@ -147,7 +928,7 @@ macro_rules! image_to_format {
 				Some(image) => {
 					let mut bytes: Vec<u8> = Vec::new();
 					image.write_to(&mut Cursor::new(&mut bytes), $format)?;
-					Ok(RustImageBuffer(bytes))
+					Ok(RustImageBuffer(bytes, $format))
 				}
 				None => Err("image is empty".into()),
 			}
@@ -155,12 +936,36 @@ macro_rules! image_to_format {
 	};
 }
 
+// the signature every PNG file starts with
+// https://www.w3.org/TR/png/#5PNG-file-signature
+pub(crate) fn is_png(bytes: &[u8]) -> bool {
+	bytes.starts_with(b"\x89PNG\r\n\x1a\n")
+}
+
+// read the size and the format from the header of the encoded image without decoding the pixels
+pub(crate) fn image_dimensions_from_bytes(bytes: &[u8]) -> Result<(u32, u32, ImageFormat)> {
+	let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+	let format = reader.format().ok_or("Unknown image format")?;
+	let (width, height) = reader.into_dimensions()?;
+	Ok((width, height, format))
+}
+
+// decode the image and keep its EXIF data, not every decoder supports metadata so a failure to read it is ignored
+fn decode_image<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<RustImageData> {
+	let mut decoder = reader.into_decoder()?;
+	let exif = decoder.exif_metadata().unwrap_or(None);
+	let mut image = RustImageData::from_dynamic_image(DynamicImage::from_decoder(decoder)?);
+	image.exif = exif;
+	Ok(image)
+}
+
 impl RustImage for RustImageData {
 	fn empty() -> Self {
 		RustImageData {
 			width: 0,
 			height: 0,
 			data: None,
+			exif: None,
 		}
 	}
 
@@ -169,23 +974,11 @@ impl RustImage for RustImageData {
 	}
 
 	fn from_path(path: &str) -> Result<Self> {
-		let image = image::open(path)?;
-		let (width, height) = image.dimensions();
-		Ok(RustImageData {
-			width,
-			height,
-			data: Some(image),
-		})
+		decode_image(ImageReader::open(path)?)
 	}
 
 	fn from_bytes(bytes: &[u8]) -> Result<Self> {
-		let image = image::load_from_memory(bytes)?;
-		let (width, height) = image.dimensions();
-		Ok(RustImageData {
-			width,
-			height,
-			data: Some(image),
-		})
+		decode_image(ImageReader::new(Cursor::new(bytes)).with_guessed_format()?)
 	}
 
 	fn from_dynamic_image(image: DynamicImage) -> Self {
@@ -194,6 +987,7 @@ impl RustImage for RustImageData {
 			width,
 			height,
 			data: Some(image),
+			exif: None,
 		}
 	}
 
@@ -209,6 +1003,7 @@ impl RustImage for RustImageData {
 					width: resized.width(),
 					height: resized.height(),
 					data: Some(resized),
+					exif: self.exif.clone(),
 				})
 			}
 			None => Err("image is empty".into()),
@@ -223,18 +1018,53 @@ impl RustImage for RustImageData {
 					width: resized.width(),
 					height: resized.height(),
 					data: Some(resized),
+					exif: self.exif.clone(),
 				})
 			}
 			None => Err("image is empty".into()),
 		}
 	}
 
+	fn rotate(&self, degrees: i32) -> Result<Self> {
+		match &self.data {
+			Some(image) => {
+				let rotated = match degrees {
+					90 | -270 => image.rotate90(),
+					180 | -180 => image.rotate180(),
+					270 | -90 => image.rotate270(),
+					_ => return Err(format!("unsupported rotation: {} degrees", degrees).into()),
+				};
+				Ok(RustImageData::from_dynamic_image(rotated))
+			}
+			None => Err("image is empty".into()),
+		}
+	}
+
+	fn flip_horizontal(&self) -> Result<Self> {
+		match &self.data {
+			Some(image) => Ok(RustImageData::from_dynamic_image(image.fliph())),
+			None => Err("image is empty".into()),
+		}
+	}
+
+	fn flip_vertical(&self) -> Result<Self> {
+		match &self.data {
+			Some(image) => Ok(RustImageData::from_dynamic_image(image.flipv())),
+			None => Err("image is empty".into()),
+		}
+	}
+
 	image_to_format!(to_jpeg, ImageFormat::Jpeg);
 
 	image_to_format!(to_png, ImageFormat::Png);
 
 	image_to_format!(to_bitmap, ImageFormat::Bmp);
 
+	image_to_format!(to_tiff, ImageFormat::Tiff);
+
+	#[cfg(feature = "webp")]
+	image_to_format!(to_webp, ImageFormat::WebP);
+
 	fn save_to_path(&self, path: &str) -> Result<()> {
 		match &self.data {
 			Some(image) => {
@@ -260,13 +1090,170 @@ impl RustImage for RustImageData {
 	}
 }
 
+const PNG_DATA_URI_PREFIX: &str = "data:image/png;base64,";
+
+impl RustImageData {
+	/// zh: 根据数据开头的魔数猜测图片格式，无法识别时返回 None
+	/// en: Guess the image format from the magic bytes at the start of the data, returns None if the format is not recognized
+	pub fn guess_format(bytes: &[u8]) -> Option<ImageFormat> {
+		image::guess_format(bytes).ok()
+	}
+
+	/// zh: 按指定的格式解码图片，不再根据魔数猜测格式
+	/// en: Decode the image with the given format instead of sniffing the magic bytes
+	pub fn from_bytes_with_format(bytes: &[u8], format: ImageFormat) -> Result<Self> {
+		decode_image(ImageReader::with_format(Cursor::new(bytes), format))
+	}
+
+	/// zh: 获得图片解码时读取到的原始 EXIF 数据（以 TIFF 头开始），图片不是从带 EXIF 的数据解码而来时返回 None，旋转和翻转后的图片不再带有 EXIF
+	/// en: Get the raw EXIF data (starting with the TIFF header) read when the image was decoded, returns None if the image was not decoded from data carrying EXIF, rotated and flipped images no longer carry it
+	pub fn get_exif_data(&self) -> Option<Vec<u8>> {
+		self.exif.clone()
+	}
+
+	/// zh: 获得 EXIF 中的方向标签（1 到 8），没有 EXIF 或没有方向标签时返回 None，可以配合 [`RustImage::rotate`] 和翻转方法把图片摆正
+	/// en: Get the orientation tag (1 to 8) of the EXIF data, returns None if there is no EXIF or no orientation tag, use it with [`RustImage::rotate`] and the flip methods to display the image upright
+	pub fn get_image_orientation(&self) -> Option<u16> {
+		self.exif
+			.as_deref()
+			.and_then(Orientation::from_exif_chunk)
+			.map(|orientation| u16::from(orientation.to_exif()))
+	}
+
+	/// zh: 以 RGBA 像素借用图片并调用 f，不会把图片复制一份返回给调用方。RGBA8 图片直接借用，其他像素格式会先临时转换，f 返回后即释放
+	/// en: Call `f` with the image borrowed as RGBA pixels, no copy of the image is handed back to the caller. RGBA8 images are borrowed directly, other pixel formats are converted into a temporary buffer that is freed once `f` returns
+	pub fn with_pixels<R>(&self, f: impl FnOnce(&RgbaImage) -> R) -> Result<R> {
+		match &self.data {
+			Some(DynamicImage::ImageRgba8(image)) => Ok(f(image)),
+			Some(image) => Ok(f(&image.to_rgba8())),
+			None => Err("image is empty".into()),
+		}
+	}
+
+	/// zh: 逐像素比较两张图片，尺寸相同且每个像素的每个通道相差不超过 tolerance 时返回 true
+	/// en: Compare two images pixel by pixel, returns true if they have the same size and every channel of every pixel differs by at most `tolerance`
+	pub fn compare_pixels(&self, other: &RustImageData, tolerance: u8) -> bool {
+		if self.width != other.width || self.height != other.height {
+			return false;
+		}
+		match (&self.data, &other.data) {
+			(Some(a), Some(b)) => {
+				let (a, b) = (a.to_rgba8(), b.to_rgba8());
+				a.dimensions() == b.dimensions()
+					&& a.as_raw()
+						.iter()
+						.zip(b.as_raw())
+						.all(|(x, y)| x.abs_diff(*y) <= tolerance)
+			}
+			(None, None) => true,
+			_ => false,
+		}
+	}
+
+	/// zh: 转为 png 格式的 base64 data URI，例如 `data:image/png;base64,...`
+	/// en: Convert to a png base64 data URI, e.g. `data:image/png;base64,...`
+	pub fn to_base64_png(&self) -> Result<String> {
+		let png = self.to_png()?;
+		let encoded = BASE64_STANDARD.encode(png.get_bytes());
+		Ok(format!("{}{}", PNG_DATA_URI_PREFIX, encoded))
+	}
+
+	/// zh: 从 base64 字符串创建图片，支持纯 base64 和 `data:image/png;base64,` 前缀的 data URI
+	/// en: Create an image from a base64 string, both bare base64 and a `data:image/png;base64,` data URI are accepted
+	pub fn from_base64_png(data_uri: &str) -> Result<Self> {
+		let data_uri = data_uri.trim();
+		let encoded = data_uri
+			.strip_prefix(PNG_DATA_URI_PREFIX)
+			.unwrap_or(data_uri);
+		let bytes = BASE64_STANDARD.decode(encoded)?;
+		RustImageData::from_bytes(&bytes)
+	}
+}
+
 impl RustImageBuffer {
 	pub fn get_bytes(&self) -> &[u8] {
 		&self.0
 	}
 
+	/// zh: 字节流的图片格式
+	/// en: The image format of the bytes
+	pub fn format(&self) -> ImageFormat {
+		self.1
+	}
+
+	/// zh: 图片格式对应的 MIME 类型，例如 "image/png"
+	/// en: The MIME type of the image format, e.g. "image/png"
+	pub fn mime_type(&self) -> &'static str {
+		self.1.to_mime_type()
+	}
+
+	/// zh: 根据字节流开头的魔数判断图片格式，不会解码图片，无法识别时返回 None
+	/// en: Detect the image format from the magic bytes at the start of the bytes without decoding the image, None if it is not recognized
+	pub fn detect_format(&self) -> Option<ImageFormat> {
+		image::guess_format(&self.0).ok()
+	}
+
+	/// zh: 字节流是否为 PNG 图片
+	/// en: Whether the bytes are a PNG image
+	pub fn is_png(&self) -> bool {
+		self.detect_format() == Some(ImageFormat::Png)
+	}
+
+	/// zh: 字节流是否为 JPEG 图片
+	/// en: Whether the bytes are a JPEG image
+	pub fn is_jpeg(&self) -> bool {
+		self.detect_format() == Some(ImageFormat::Jpeg)
+	}
+
 	pub fn save_to_path(&self, path: &str) -> Result<()> {
 		std::fs::write(path, &self.0)?;
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::EventThrottle;
+	use std::time::{Duration, Instant};
+
+	#[test]
+	fn test_throttle_unlimited() {
+		let mut throttle = EventThrottle::default();
+		let now = Instant::now();
+		assert!(throttle.should_dispatch(true, now));
+		assert!(throttle.should_dispatch(true, now));
+		assert!(!throttle.should_dispatch(false, now));
+	}
+
+	#[test]
+	fn test_throttle_trailing_edge() {
+		let mut throttle = EventThrottle::default();
+		throttle.set_max_event_rate(2.0);
+		let start = Instant::now();
+		let at = |millis: u64| start + Duration::from_millis(millis);
+
+		// the first event of a burst is delivered immediately
+		assert!(throttle.should_dispatch(true, at(0)));
+		// the following ones are coalesced
+		assert!(!throttle.should_dispatch(true, at(100)));
+		assert!(!throttle.should_dispatch(true, at(200)));
+		assert!(!throttle.should_dispatch(false, at(400)));
+		// the last change is delivered once the interval has elapsed
+		assert!(throttle.should_dispatch(false, at(500)));
+		// and only once
+		assert!(!throttle.should_dispatch(false, at(1200)));
+		// a new change after a quiet period is delivered immediately
+		assert!(throttle.should_dispatch(true, at(1300)));
+	}
+
+	#[test]
+	fn test_throttle_disable() {
+		let mut throttle = EventThrottle::default();
+		throttle.set_max_event_rate(1.0);
+		let now = Instant::now();
+		assert!(throttle.should_dispatch(true, now));
+		assert!(!throttle.should_dispatch(true, now));
+		throttle.set_max_event_rate(0.0);
+		assert!(throttle.should_dispatch(true, now));
+	}
+}
@@ -0,0 +1,541 @@
+use crate::common::{ContentData, RustImage};
+use crate::{
+	Clipboard, ClipboardContent, ClipboardSnapshot, ContentFormat, FileOperation, FormatInfo,
+	ImageFormat, Result, RustImageData,
+};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// zh: [`ClipboardRecorder`] 记录的一次剪切板操作，读取操作记录结果，错误只保留错误信息
+/// en: A clipboard operation recorded by [`ClipboardRecorder`], reads record their result, errors are kept as their message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClipboardOperation {
+	AvailableFormats(std::result::Result<Vec<String>, String>),
+	AvailableFormatsDetailed(std::result::Result<Vec<FormatInfo>, String>),
+	AvailableFormatNames(std::result::Result<Vec<String>, String>),
+	Has {
+		format: ContentFormat,
+		result: bool,
+	},
+	HasAny {
+		formats: Vec<ContentFormat>,
+		result: bool,
+	},
+	HasAll {
+		formats: Vec<ContentFormat>,
+		result: bool,
+	},
+	IsEmpty(bool),
+	Clear,
+	GetBuffer {
+		format: String,
+		result: std::result::Result<Vec<u8>, String>,
+	},
+	ReadStreaming {
+		format: String,
+		result: std::result::Result<(), String>,
+	},
+	GetText(std::result::Result<String, String>),
+	GetRichText(std::result::Result<String, String>),
+	GetHtml(std::result::Result<String, String>),
+	GetHtmlFullDocument(std::result::Result<String, String>),
+	GetTextBytes(std::result::Result<Vec<u8>, String>),
+	GetTextLossless(std::result::Result<String, String>),
+	TryGetText(std::result::Result<Option<String>, String>),
+	GetRichTextBytes(std::result::Result<Vec<u8>, String>),
+	GetRichTextLossless(std::result::Result<String, String>),
+	GetHtmlBytes(std::result::Result<Vec<u8>, String>),
+	GetHtmlLossless(std::result::Result<String, String>),
+	GetMarkdown(std::result::Result<String, String>),
+	GetUrl(std::result::Result<String, String>),
+	/// zh: 读取到的图片的尺寸
+	/// en: The size of the image read
+	GetImage(std::result::Result<(u32, u32), String>),
+	/// zh: 读取到的图片的尺寸，没有图片时为 None
+	/// en: The size of the image read, None when there is no image
+	TryGetImage(std::result::Result<Option<(u32, u32)>, String>),
+	ImageDimensions(std::result::Result<(u32, u32, ImageFormat), String>),
+	GetFiles(std::result::Result<Vec<String>, String>),
+	TryGetFiles(std::result::Result<Option<Vec<String>>, String>),
+	GetFilesWithOperation(std::result::Result<(FileOperation, Vec<String>), String>),
+	GetFilesAsPaths(std::result::Result<Vec<PathBuf>, String>),
+	/// zh: 请求的格式和读取到的内容的格式
+	/// en: The formats asked for and the formats of the contents read
+	Get {
+		formats: Vec<ContentFormat>,
+		result: std::result::Result<Vec<ContentFormat>, String>,
+	},
+	SetBuffer {
+		format: String,
+		buffer: Vec<u8>,
+	},
+	SetBuffers(Vec<(String, Vec<u8>)>),
+	SetText(String),
+	SetRichText(String),
+	SetRichTextChecked(String),
+	AppendText(String),
+	AppendTextWithSeparator {
+		text: String,
+		separator: Option<String>,
+	},
+	SetHtml(String),
+	SetHtmlRaw(String),
+	SetUrl(String),
+	SetMarkdown(String),
+	SetImage {
+		size: (u32, u32),
+	},
+	SetImageFromPath(String),
+	SetFiles(Vec<String>),
+	SetFilesWithOperation {
+		files: Vec<String>,
+		operation: FileOperation,
+	},
+	/// zh: 写入的内容的格式
+	/// en: The formats of the contents written
+	Set(Vec<ContentFormat>),
+	/// zh: 要写入的内容的格式和大小限制
+	/// en: The formats of the contents to write and the size limit
+	SetWithSizeLimit {
+		formats: Vec<ContentFormat>,
+		max_bytes: usize,
+	},
+	/// zh: 声明的格式
+	/// en: The formats announced
+	SetWithLazy(Vec<ContentFormat>),
+	CloneTo,
+	/// zh: 快照中保存的格式名称
+	/// en: The names of the formats kept in the snapshot
+	Snapshot(std::result::Result<Vec<String>, String>),
+	/// zh: 写回的快照中的格式名称
+	/// en: The names of the formats in the snapshot written back
+	Restore(Vec<String>),
+	ContentFingerprint(std::result::Result<u64, String>),
+	GetSequenceNumber(std::result::Result<u64, String>),
+	HasChangedSince {
+		last: u64,
+		result: std::result::Result<bool, String>,
+	},
+}
+
+/// zh: 把所有操作转发给内部的剪切板并按顺序记录下来，用于排查与其他程序的互操作问题
+/// en: Forwards every operation to the inner clipboard and records them in order, for debugging interoperability issues with other programs
+pub struct ClipboardRecorder<C: Clipboard> {
+	inner: C,
+	log: Mutex<Vec<ClipboardOperation>>,
+}
+
+impl<C: Clipboard> ClipboardRecorder<C> {
+	pub fn new(inner: C) -> Self {
+		ClipboardRecorder {
+			inner,
+			log: Mutex::new(Vec::new()),
+		}
+	}
+
+	/// zh: 取出目前记录的所有操作，记录随之清空
+	/// en: Take all the operations recorded so far, the log is emptied
+	pub fn drain_log(&self) -> Vec<ClipboardOperation> {
+		match self.log.lock() {
+			Ok(mut log) => log.drain(..).collect(),
+			Err(poisoned) => poisoned.into_inner().drain(..).collect(),
+		}
+	}
+
+	/// zh: 取回内部的剪切板
+	/// en: Get the inner clipboard back
+	pub fn into_inner(self) -> C {
+		self.inner
+	}
+
+	fn record(&self, operation: ClipboardOperation) {
+		match self.log.lock() {
+			Ok(mut log) => log.push(operation),
+			Err(poisoned) => poisoned.into_inner().push(operation),
+		}
+	}
+}
+
+// the result of a read, with the error replaced by its message
+fn recorded<T: Clone>(result: &Result<T>) -> std::result::Result<T, String> {
+	match result {
+		Ok(value) => Ok(value.clone()),
+		Err(e) => Err(e.to_string()),
+	}
+}
+
+// the names of the formats kept in a snapshot
+fn snapshot_formats(snapshot: &ClipboardSnapshot) -> Vec<String> {
+	snapshot.formats().into_iter().map(str::to_owned).collect()
+}
+
+// the formats of the contents, to record writes without copying their data
+fn content_formats(contents: &[ClipboardContent]) -> Vec<ContentFormat> {
+	contents
+		.iter()
+		.map(|content| content.get_format())
+		.collect()
+}
+
+impl<C: Clipboard> Clipboard for ClipboardRecorder<C> {
+	fn available_formats(&self) -> Result<Vec<String>> {
+		let res = self.inner.available_formats();
+		self.record(ClipboardOperation::AvailableFormats(recorded(&res)));
+		res
+	}
+
+	fn available_formats_detailed(&self) -> Result<Vec<FormatInfo>> {
+		let res = self.inner.available_formats_detailed();
+		self.record(ClipboardOperation::AvailableFormatsDetailed(recorded(&res)));
+		res
+	}
+
+	fn available_format_names(&self) -> Result<Vec<String>> {
+		let res = self.inner.available_format_names();
+		self.record(ClipboardOperation::AvailableFormatNames(recorded(&res)));
+		res
+	}
+
+	fn has(&self, format: ContentFormat) -> bool {
+		let result = self.inner.has(format.clone());
+		self.record(ClipboardOperation::Has { format, result });
+		result
+	}
+
+	fn has_any(&self, formats: &[ContentFormat]) -> bool {
+		let result = self.inner.has_any(formats);
+		self.record(ClipboardOperation::HasAny {
+			formats: formats.to_vec(),
+			result,
+		});
+		result
+	}
+
+	fn has_all(&self, formats: &[ContentFormat]) -> bool {
+		let result = self.inner.has_all(formats);
+		self.record(ClipboardOperation::HasAll {
+			formats: formats.to_vec(),
+			result,
+		});
+		result
+	}
+
+	fn is_empty(&self) -> bool {
+		let result = self.inner.is_empty();
+		self.record(ClipboardOperation::IsEmpty(result));
+		result
+	}
+
+	fn clear(&self) -> Result<()> {
+		self.record(ClipboardOperation::Clear);
+		self.inner.clear()
+	}
+
+	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		let res = self.inner.get_buffer(format);
+		self.record(ClipboardOperation::GetBuffer {
+			format: format.to_owned(),
+			result: recorded(&res),
+		});
+		res
+	}
+
+	fn read_streaming(&self, format: &str, sink: &mut dyn Write) -> Result<()> {
+		let res = self.inner.read_streaming(format, sink);
+		self.record(ClipboardOperation::ReadStreaming {
+			format: format.to_owned(),
+			result: recorded(&res),
+		});
+		res
+	}
+
+	fn get_text(&self) -> Result<String> {
+		let res = self.inner.get_text();
+		self.record(ClipboardOperation::GetText(recorded(&res)));
+		res
+	}
+
+	fn get_rich_text(&self) -> Result<String> {
+		let res = self.inner.get_rich_text();
+		self.record(ClipboardOperation::GetRichText(recorded(&res)));
+		res
+	}
+
+	fn get_html(&self) -> Result<String> {
+		let res = self.inner.get_html();
+		self.record(ClipboardOperation::GetHtml(recorded(&res)));
+		res
+	}
+
+	fn get_html_full_document(&self) -> Result<String> {
+		let res = self.inner.get_html_full_document();
+		self.record(ClipboardOperation::GetHtmlFullDocument(recorded(&res)));
+		res
+	}
+
+	fn get_text_bytes(&self) -> Result<Vec<u8>> {
+		let res = self.inner.get_text_bytes();
+		self.record(ClipboardOperation::GetTextBytes(recorded(&res)));
+		res
+	}
+
+	fn get_text_lossless(&self) -> Result<String> {
+		let res = self.inner.get_text_lossless();
+		self.record(ClipboardOperation::GetTextLossless(recorded(&res)));
+		res
+	}
+
+	fn try_get_text(&self) -> Result<Option<String>> {
+		let res = self.inner.try_get_text();
+		self.record(ClipboardOperation::TryGetText(recorded(&res)));
+		res
+	}
+
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		let res = self.inner.get_rich_text_bytes();
+		self.record(ClipboardOperation::GetRichTextBytes(recorded(&res)));
+		res
+	}
+
+	fn get_rich_text_lossless(&self) -> Result<String> {
+		let res = self.inner.get_rich_text_lossless();
+		self.record(ClipboardOperation::GetRichTextLossless(recorded(&res)));
+		res
+	}
+
+	fn get_html_bytes(&self) -> Result<Vec<u8>> {
+		let res = self.inner.get_html_bytes();
+		self.record(ClipboardOperation::GetHtmlBytes(recorded(&res)));
+		res
+	}
+
+	fn get_html_lossless(&self) -> Result<String> {
+		let res = self.inner.get_html_lossless();
+		self.record(ClipboardOperation::GetHtmlLossless(recorded(&res)));
+		res
+	}
+
+	fn get_markdown(&self) -> Result<String> {
+		let res = self.inner.get_markdown();
+		self.record(ClipboardOperation::GetMarkdown(recorded(&res)));
+		res
+	}
+
+	fn get_url(&self) -> Result<String> {
+		let res = self.inner.get_url();
+		self.record(ClipboardOperation::GetUrl(recorded(&res)));
+		res
+	}
+
+	fn get_image(&self) -> Result<RustImageData> {
+		let res = self.inner.get_image();
+		let size = match &res {
+			Ok(image) => Ok(image.get_size()),
+			Err(e) => Err(e.to_string()),
+		};
+		self.record(ClipboardOperation::GetImage(size));
+		res
+	}
+
+	fn try_get_image(&self) -> Result<Option<RustImageData>> {
+		let res = self.inner.try_get_image();
+		let size = match &res {
+			Ok(image) => Ok(image.as_ref().map(|image| image.get_size())),
+			Err(e) => Err(e.to_string()),
+		};
+		self.record(ClipboardOperation::TryGetImage(size));
+		res
+	}
+
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		let res = self.inner.image_dimensions();
+		self.record(ClipboardOperation::ImageDimensions(recorded(&res)));
+		res
+	}
+
+	fn get_files(&self) -> Result<Vec<String>> {
+		let res = self.inner.get_files();
+		self.record(ClipboardOperation::GetFiles(recorded(&res)));
+		res
+	}
+
+	fn try_get_files(&self) -> Result<Option<Vec<String>>> {
+		let res = self.inner.try_get_files();
+		self.record(ClipboardOperation::TryGetFiles(recorded(&res)));
+		res
+	}
+
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		let res = self.inner.get_files_with_operation();
+		self.record(ClipboardOperation::GetFilesWithOperation(recorded(&res)));
+		res
+	}
+
+	fn get_files_as_paths(&self) -> Result<Vec<PathBuf>> {
+		let res = self.inner.get_files_as_paths();
+		self.record(ClipboardOperation::GetFilesAsPaths(recorded(&res)));
+		res
+	}
+
+	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
+		let res = self.inner.get(formats);
+		let result = match &res {
+			Ok(contents) => Ok(content_formats(contents)),
+			Err(e) => Err(e.to_string()),
+		};
+		self.record(ClipboardOperation::Get {
+			formats: formats.to_vec(),
+			result,
+		});
+		res
+	}
+
+	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
+		self.record(ClipboardOperation::SetBuffer {
+			format: format.to_owned(),
+			buffer: buffer.clone(),
+		});
+		self.inner.set_buffer(format, buffer)
+	}
+
+	fn set_buffers(&self, entries: Vec<(String, Vec<u8>)>) -> Result<()> {
+		self.record(ClipboardOperation::SetBuffers(entries.clone()));
+		self.inner.set_buffers(entries)
+	}
+
+	fn set_text(&self, text: String) -> Result<()> {
+		self.record(ClipboardOperation::SetText(text.clone()));
+		self.inner.set_text(text)
+	}
+
+	fn set_rich_text(&self, text: String) -> Result<()> {
+		self.record(ClipboardOperation::SetRichText(text.clone()));
+		self.inner.set_rich_text(text)
+	}
+
+	fn set_rich_text_checked(&self, rtf: String) -> Result<()> {
+		self.record(ClipboardOperation::SetRichTextChecked(rtf.clone()));
+		self.inner.set_rich_text_checked(rtf)
+	}
+
+	fn append_text(&self, text: &str) -> Result<()> {
+		self.record(ClipboardOperation::AppendText(text.to_owned()));
+		self.inner.append_text(text)
+	}
+
+	fn append_text_with_separator(&self, text: &str, separator: Option<&str>) -> Result<()> {
+		self.record(ClipboardOperation::AppendTextWithSeparator {
+			text: text.to_owned(),
+			separator: separator.map(str::to_owned),
+		});
+		self.inner.append_text_with_separator(text, separator)
+	}
+
+	fn set_html(&self, html: String) -> Result<()> {
+		self.record(ClipboardOperation::SetHtml(html.clone()));
+		self.inner.set_html(html)
+	}
+
+	fn set_html_raw(&self, html: String) -> Result<()> {
+		self.record(ClipboardOperation::SetHtmlRaw(html.clone()));
+		self.inner.set_html_raw(html)
+	}
+
+	fn set_url(&self, url: String) -> Result<()> {
+		self.record(ClipboardOperation::SetUrl(url.clone()));
+		self.inner.set_url(url)
+	}
+
+	fn set_markdown(&self, markdown: String) -> Result<()> {
+		self.record(ClipboardOperation::SetMarkdown(markdown.clone()));
+		self.inner.set_markdown(markdown)
+	}
+
+	fn set_image(&self, image: RustImageData) -> Result<()> {
+		self.record(ClipboardOperation::SetImage {
+			size: image.get_size(),
+		});
+		self.inner.set_image(image)
+	}
+
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		self.record(ClipboardOperation::SetImageFromPath(path.to_owned()));
+		self.inner.set_image_from_path(path)
+	}
+
+	fn set_files(&self, files: Vec<String>) -> Result<()> {
+		self.record(ClipboardOperation::SetFiles(files.clone()));
+		self.inner.set_files(files)
+	}
+
+	fn set_files_with_operation(&self, files: Vec<String>, operation: FileOperation) -> Result<()> {
+		self.record(ClipboardOperation::SetFilesWithOperation {
+			files: files.clone(),
+			operation,
+		});
+		self.inner.set_files_with_operation(files, operation)
+	}
+
+	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+		self.record(ClipboardOperation::Set(content_formats(&contents)));
+		self.inner.set(contents)
+	}
+
+	fn set_with_size_limit(&self, contents: Vec<ClipboardContent>, max_bytes: usize) -> Result<()> {
+		self.record(ClipboardOperation::SetWithSizeLimit {
+			formats: content_formats(&contents),
+			max_bytes,
+		});
+		self.inner.set_with_size_limit(contents, max_bytes)
+	}
+
+	fn set_with_lazy<F>(&self, formats: Vec<ContentFormat>, provider: F) -> Result<()>
+	where
+		F: Fn(ContentFormat) -> Option<Vec<u8>> + Send + 'static,
+	{
+		self.record(ClipboardOperation::SetWithLazy(formats.clone()));
+		self.inner.set_with_lazy(formats, provider)
+	}
+
+	fn clone_to(&self, target: &dyn Clipboard) -> Result<()> {
+		self.record(ClipboardOperation::CloneTo);
+		self.inner.clone_to(target)
+	}
+
+	fn snapshot(&self) -> Result<ClipboardSnapshot> {
+		let res = self.inner.snapshot();
+		let formats = match &res {
+			Ok(snapshot) => Ok(snapshot_formats(snapshot)),
+			Err(e) => Err(e.to_string()),
+		};
+		self.record(ClipboardOperation::Snapshot(formats));
+		res
+	}
+
+	fn restore(&self, snapshot: ClipboardSnapshot) -> Result<()> {
+		self.record(ClipboardOperation::Restore(snapshot_formats(&snapshot)));
+		self.inner.restore(snapshot)
+	}
+
+	fn content_fingerprint(&self) -> Result<u64> {
+		let res = self.inner.content_fingerprint();
+		self.record(ClipboardOperation::ContentFingerprint(recorded(&res)));
+		res
+	}
+
+	fn get_sequence_number(&self) -> Result<u64> {
+		let res = self.inner.get_sequence_number();
+		self.record(ClipboardOperation::GetSequenceNumber(recorded(&res)));
+		res
+	}
+
+	fn has_changed_since(&self, last: u64) -> Result<bool> {
+		let res = self.inner.has_changed_since(last);
+		self.record(ClipboardOperation::HasChangedSince {
+			last,
+			result: recorded(&res),
+		});
+		res
+	}
+}
@@ -1,18 +1,89 @@
+mod builder;
 pub mod common;
+#[cfg(feature = "debug-utils")]
+pub mod debug_utils;
 mod platform;
-pub use common::{ClipboardContent, ClipboardHandler, ContentFormat, Result, RustImageData};
+pub use builder::ClipboardContextBuilder;
+#[cfg(feature = "markdown")]
+use common::markdown_to_html;
+use common::{
+	append_text_to, content_from_bytes, format_from_name, validate_rtf, ContentData, Fnv1a,
+	RustImage,
+};
+pub use common::{
+	wrap_plain_as_rtf, ChangeSource, ClipboardContent, ClipboardError, ClipboardEvent,
+	ClipboardHandler, ClipboardOwner, ClipboardSnapshot, ContentFormat, FileOperation, FormatInfo,
+	FromClipboard, LinuxBackend, Result, RustImageData, ToClipboard, WatcherShutdown, XSelection,
+};
 pub use image::imageops::FilterType;
+pub use image::ImageFormat;
+use percent_encoding::percent_decode_str;
+pub use platform::{
+	ClipboardContext, ClipboardWatcherContext, MemoryClipboardContext,
+	MemoryClipboardWatcherContext,
+};
 #[cfg(target_os = "linux")]
-pub use platform::ClipboardContextX11Options;
-pub use platform::{ClipboardContext, ClipboardWatcherContext, WatcherShutdown};
+pub use platform::{ClipboardContextX11Options, ReadProgressCallback};
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+pub use platform::{WaylandClipboardContext, WaylandClipboardWatcherContext};
+use std::io::Write;
+use std::path::PathBuf;
 
 pub trait Clipboard: Send {
 	/// zh: 获得剪切板当前内容的所有格式
 	/// en: Get all formats of the current content in the clipboard
 	fn available_formats(&self) -> Result<Vec<String>>;
 
+	/// zh: 获得剪切板当前内容的所有格式及其平台标识，名称查询失败时仍会保留标识
+	/// en: Get all formats of the current content in the clipboard with their platform identifiers, the identifier is kept even when the name lookup fails
+	fn available_formats_detailed(&self) -> Result<Vec<FormatInfo>> {
+		Ok(self
+			.available_formats()?
+			.into_iter()
+			.map(|name| FormatInfo {
+				name: Some(name),
+				id: 0,
+			})
+			.collect())
+	}
+
+	/// zh: 获得剪切板当前内容的所有格式名称，去掉了没有名称的格式和重复的名称，适合在界面中显示
+	/// en: Get the names of all formats of the current content in the clipboard, formats without a name and repeated names are dropped, suitable to be shown in a UI
+	fn available_format_names(&self) -> Result<Vec<String>> {
+		let mut names: Vec<String> = Vec::new();
+		for name in self
+			.available_formats_detailed()?
+			.into_iter()
+			.filter_map(|format| format.name)
+		{
+			if !names.contains(&name) {
+				names.push(name);
+			}
+		}
+		Ok(names)
+	}
+
 	fn has(&self, format: ContentFormat) -> bool;
 
+	/// zh: 判断剪切板中是否至少有一种指定的格式
+	/// en: Check if the clipboard has at least one of the given formats
+	fn has_any(&self, formats: &[ContentFormat]) -> bool {
+		formats.iter().any(|format| self.has(format.clone()))
+	}
+
+	/// zh: 判断剪切板中是否有所有指定的格式
+	/// en: Check if the clipboard has every one of the given formats
+	fn has_all(&self, formats: &[ContentFormat]) -> bool {
+		formats.iter().all(|format| self.has(format.clone()))
+	}
+
+	/// zh: 判断剪切板是否为空
+	/// en: Check if the clipboard is empty
+	fn is_empty(&self) -> bool {
+		self.available_formats()
+			.map_or(true, |formats| formats.is_empty())
+	}
+
 	/// zh: 清空剪切板
 	/// en: clear clipboard
 	fn clear(&self) -> Result<()>;
@@ -25,6 +96,13 @@ pub trait Clipboard: Send {
 	/// en: Get plain text content in the clipboard as string
 	fn get_text(&self) -> Result<String>;
 
+	/// zh: 读取指定格式的数据并写入 sink，X11 上大数据（INCR 传输）的每个分块到达时就会写入，不会把全部数据保存在内存中，其他平台上会先读取全部数据
+	/// en: Read the data of the given format into the sink, on X11 every chunk of a large (INCR) transfer is written as soon as it arrives so the whole payload is never held in memory, other platforms read all the data first
+	fn read_streaming(&self, format: &str, sink: &mut dyn Write) -> Result<()> {
+		sink.write_all(&self.get_buffer(format)?)?;
+		Ok(())
+	}
+
 	/// zh: 获得剪贴板中的富文本内容，以字符串形式返回
 	/// en: Get the rich text content in the clipboard as string
 	fn get_rich_text(&self) -> Result<String>;
@@ -33,26 +111,376 @@ pub trait Clipboard: Send {
 	/// en: Get the html format content in the clipboard as string
 	fn get_html(&self) -> Result<String>;
 
+	/// zh: 获得剪贴板中完整的 html 文档，在 Windows 上返回 `StartHTML` 到 `EndHTML` 之间的内容，而 [`Clipboard::get_html`] 只返回复制的片段，其他平台与 `get_html` 相同
+	/// en: Get the whole html document in the clipboard, on Windows the content between `StartHTML` and `EndHTML` is returned while [`Clipboard::get_html`] only returns the copied fragment, the same as `get_html` on other platforms
+	fn get_html_full_document(&self) -> Result<String> {
+		self.get_html()
+	}
+
+	/// zh: 获得剪贴板中纯文本的原始字节，UTF-8 文本不做任何转换，X11 上只有 Latin-1 编码的 STRING 等旧格式时会转换为 UTF-8
+	/// en: Get the raw bytes of the plain text in the clipboard, UTF-8 text is not converted, on X11 legacy targets such as Latin-1 `STRING` are transcoded to UTF-8
+	fn get_text_bytes(&self) -> Result<Vec<u8>> {
+		self.get_text().map(String::into_bytes)
+	}
+
+	/// zh: 获得剪贴板中的纯文本，如果不是合法的 UTF-8 则返回错误，而不是替换为 U+FFFD
+	/// en: Get the plain text in the clipboard, returns an error on invalid UTF-8 instead of replacing it with U+FFFD
+	fn get_text_lossless(&self) -> Result<String> {
+		Ok(String::from_utf8(self.get_text_bytes()?)
+			.map_err(|e| format!("clipboard text is not valid UTF-8: {}", e))?)
+	}
+
+	/// zh: 获得剪贴板中的纯文本，没有文本时返回 Ok(None)，平台错误仍然返回 Err
+	/// en: Get the plain text in the clipboard, returns Ok(None) when there is no text, platform errors are still returned as Err
+	fn try_get_text(&self) -> Result<Option<String>> {
+		if !self.has(ContentFormat::Text) {
+			return Ok(None);
+		}
+		self.get_text().map(Some)
+	}
+
+	/// zh: 获得剪贴板中富文本的原始字节，不做任何编码转换
+	/// en: Get the raw bytes of the rich text in the clipboard, without any encoding conversion
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		self.get_rich_text().map(String::into_bytes)
+	}
+
+	/// zh: 获得剪贴板中的富文本，如果不是合法的 UTF-8 则返回错误，而不是替换为 U+FFFD
+	/// en: Get the rich text in the clipboard, returns an error on invalid UTF-8 instead of replacing it with U+FFFD
+	fn get_rich_text_lossless(&self) -> Result<String> {
+		Ok(String::from_utf8(self.get_rich_text_bytes()?)
+			.map_err(|e| format!("clipboard rich text is not valid UTF-8: {}", e))?)
+	}
+
+	/// zh: 获得剪贴板中 html 的原始字节，不做任何编码转换
+	/// en: Get the raw bytes of the html in the clipboard, without any encoding conversion
+	fn get_html_bytes(&self) -> Result<Vec<u8>> {
+		self.get_html().map(String::into_bytes)
+	}
+
+	/// zh: 获得剪贴板中的 html，如果不是合法的 UTF-8 则返回错误，而不是替换为 U+FFFD
+	/// en: Get the html in the clipboard, returns an error on invalid UTF-8 instead of replacing it with U+FFFD
+	fn get_html_lossless(&self) -> Result<String> {
+		Ok(String::from_utf8(self.get_html_bytes()?)
+			.map_err(|e| format!("clipboard html is not valid UTF-8: {}", e))?)
+	}
+
+	/// zh: 获得剪贴板中的 Markdown，优先读取原始的 Markdown 格式，没有时返回纯文本
+	/// en: Get the Markdown in the clipboard, the raw Markdown format is preferred, the plain text is returned when it is absent
+	fn get_markdown(&self) -> Result<String> {
+		if let Some(ClipboardContent::Markdown(markdown)) =
+			self.get(&[ContentFormat::Markdown])?.into_iter().next()
+		{
+			return Ok(markdown);
+		}
+		self.get_text()
+	}
+
+	/// zh: 获得剪贴板中的 URL，没有 URL 格式时返回错误
+	/// en: Get the URL in the clipboard, returns an error when there is no URL format
+	fn get_url(&self) -> Result<String> {
+		match self.get(&[ContentFormat::Url])?.into_iter().next() {
+			Some(ClipboardContent::Url(url)) => Ok(url),
+			_ => Err("No url in clipboard".into()),
+		}
+	}
+
 	fn get_image(&self) -> Result<RustImageData>;
 
+	/// zh: 获得剪贴板中的图片，没有图片时返回 Ok(None)，平台错误或解码失败仍然返回 Err
+	/// en: Get the image in the clipboard, returns Ok(None) when there is no image, platform or decoding errors are still returned as Err
+	fn try_get_image(&self) -> Result<Option<RustImageData>> {
+		if !self.has(ContentFormat::Image) {
+			return Ok(None);
+		}
+		self.get_image().map(Some)
+	}
+
+	/// zh: 获得剪切板中图片的宽、高和格式，只读取图片头部而不解码像素，适合在剪切板历史中显示 "1920×1080 PNG" 之类的信息。
+	/// Windows 上的 DIB 图片直接解析 BITMAPINFOHEADER，格式为 Bmp
+	/// en: Get the width, height and format of the image in the clipboard, only the image header is read and the pixels are not decoded, useful to show e.g. "1920×1080 PNG" in a clipboard history.
+	/// DIB images on Windows are read by parsing the BITMAPINFOHEADER directly, the format is Bmp
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		Err("image_dimensions is not supported on this platform".into())
+	}
+
 	fn get_files(&self) -> Result<Vec<String>>;
 
+	/// zh: 获得剪贴板中的文件列表，没有文件时返回 Ok(None)，平台错误仍然返回 Err
+	/// en: Get the files in the clipboard, returns Ok(None) when there are no files, platform errors are still returned as Err
+	fn try_get_files(&self) -> Result<Option<Vec<String>>> {
+		if !self.has(ContentFormat::Files) {
+			return Ok(None);
+		}
+		self.get_files().map(Some)
+	}
+
+	/// zh: 获取剪切板中的文件列表及其操作类型（复制或剪切），X11 和 Windows 能识别剪切，其他平台总是返回复制
+	/// en: Get the files in the clipboard with their operation (copy or cut), X11 and Windows recognize cut, other platforms always return copy
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		Ok((FileOperation::Copy, self.get_files()?))
+	}
+
+	/// zh: 以路径的形式获取剪切板中的文件列表，会去掉 `file://` 前缀并对 URI 做百分号解码，不带前缀的路径保持原样
+	/// en: Get the files in the clipboard as paths, the `file://` prefix is removed and URIs are percent-decoded, paths without the prefix are kept as they are
+	fn get_files_as_paths(&self) -> Result<Vec<PathBuf>> {
+		Ok(self
+			.get_files()?
+			.iter()
+			.map(|file| match file.strip_prefix("file://") {
+				Some(uri_path) => PathBuf::from(
+					percent_decode_str(uri_path)
+						.decode_utf8_lossy()
+						.into_owned(),
+				),
+				None => PathBuf::from(file),
+			})
+			.collect())
+	}
+
 	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>>;
 
 	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()>;
 
+	/// zh: 一次性写入多个自定义格式的数据，所有格式会同时存在于剪切板中
+	/// en: Write multiple custom formats at once, all of them will coexist on the clipboard
+	fn set_buffers(&self, entries: Vec<(String, Vec<u8>)>) -> Result<()> {
+		let contents = entries
+			.into_iter()
+			.map(|(format, buffer)| ClipboardContent::Other(format, buffer))
+			.collect();
+		self.set(contents)
+	}
+
 	fn set_text(&self, text: String) -> Result<()>;
 
 	fn set_rich_text(&self, text: String) -> Result<()>;
 
+	/// zh: 检查 RTF 以 `{\rtf` 开头且括号配对后再写入，否则返回错误，纯文本可以先用 [`wrap_plain_as_rtf`] 包装
+	/// en: Write the RTF after checking it starts with `{\rtf` and its braces are balanced, returns an error otherwise, plain text can be wrapped with [`wrap_plain_as_rtf`] first
+	fn set_rich_text_checked(&self, rtf: String) -> Result<()> {
+		validate_rtf(&rtf)?;
+		self.set_rich_text(rtf)
+	}
+
+	/// zh: 把文本追加到剪切板中已有的纯文本之后，以换行分隔，剪切板中没有文本时直接写入
+	/// en: Append the text to the plain text already on the clipboard, separated by a newline, the text is written as is when there is no text on the clipboard
+	fn append_text(&self, text: &str) -> Result<()> {
+		self.append_text_with_separator(text, Some("\n"))
+	}
+
+	/// zh: 以指定的分隔符（None 表示不加分隔符）把文本追加到剪切板中已有的纯文本之后。
+	/// Windows 上读取和写入在同一次打开剪切板期间完成，其他程序无法在中间修改剪切板；其他平台只是先读后写的便捷方法
+	/// en: Append the text to the plain text already on the clipboard with the given separator (None means no separator).
+	/// On Windows the read and the write happen while the clipboard is opened once, so no other program can change it in between; on other platforms it is only a convenience that reads then writes
+	fn append_text_with_separator(&self, text: &str, separator: Option<&str>) -> Result<()> {
+		let current = if self.has(ContentFormat::Text) {
+			self.get_text()?
+		} else {
+			String::new()
+		};
+		self.set_text(append_text_to(current, text, separator))
+	}
+
 	fn set_html(&self, html: String) -> Result<()>;
 
+	/// zh: 原样写入 html，不加 CF_HTML 的头部，用于与直接读取原始 html 的程序互操作，只有 Windows 上与 [`Clipboard::set_html`] 不同
+	/// en: Write the html as is without the CF_HTML header, for programs that read the raw html, only differs from [`Clipboard::set_html`] on Windows
+	fn set_html_raw(&self, html: String) -> Result<()> {
+		self.set_html(html)
+	}
+
+	/// zh: 写入 URL，同时写入纯文本，让不支持 URL 格式的程序也能粘贴
+	/// en: Write the URL together with it as plain text, so programs without URL support can still paste it
+	fn set_url(&self, url: String) -> Result<()> {
+		self.set(vec![
+			ClipboardContent::Text(url.clone()),
+			ClipboardContent::Url(url),
+		])
+	}
+
+	/// zh: 写入 Markdown，同时写入纯文本；启用 `markdown` 特性时还会写入渲染后的 HTML，让不支持 Markdown 的程序也能粘贴可读的内容
+	/// en: Write Markdown together with it as plain text; with the `markdown` feature the rendered HTML is written too, so programs without Markdown support still paste something readable
+	fn set_markdown(&self, markdown: String) -> Result<()> {
+		#[cfg(feature = "markdown")]
+		let html = markdown_to_html(&markdown);
+		#[allow(unused_mut)]
+		let mut contents = vec![
+			ClipboardContent::Text(markdown.clone()),
+			ClipboardContent::Markdown(markdown),
+		];
+		#[cfg(feature = "markdown")]
+		contents.push(ClipboardContent::Html(html));
+		self.set(contents)
+	}
+
 	fn set_image(&self, image: RustImageData) -> Result<()>;
 
+	/// zh: 读取图片文件并写入剪贴板，PNG 文件在支持的平台上直接写入原始数据而不重新编码
+	/// en: Load the image file and write it to the clipboard, PNG files are written as is without re-encoding on the platforms that support it
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		self.set_image(RustImageData::from_path(path)?)
+	}
+
 	fn set_files(&self, files: Vec<String>) -> Result<()>;
 
+	/// zh: 写入文件列表及其操作类型，剪切时文件管理器粘贴后会移动文件，macOS 上会忽略操作类型
+	/// en: Write the files with their operation, on cut the file manager moves the files when pasting, the operation is ignored on macOS
+	fn set_files_with_operation(&self, files: Vec<String>, operation: FileOperation) -> Result<()> {
+		self.set(vec![ClipboardContent::FilesWithOperation(files, operation)])
+	}
+
 	/// set image will clear clipboard
 	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()>;
+
+	/// zh: 写入前检查内容的总大小（见 [`ContentData::size_in_bytes`]），超过 `max_bytes` 时不写入并返回 `ClipboardError::Other`
+	/// en: Check the total size of the contents (see [`ContentData::size_in_bytes`]) before writing, nothing is written and `ClipboardError::Other` is returned when it exceeds `max_bytes`
+	fn set_with_size_limit(&self, contents: Vec<ClipboardContent>, max_bytes: usize) -> Result<()> {
+		let total = contents.iter().fold(0usize, |total, content| {
+			total.saturating_add(content.size_in_bytes())
+		});
+		if total > max_bytes {
+			return Err(ClipboardError::Other("payload too large".to_string()).into());
+		}
+		self.set(contents)
+	}
+
+	/// zh: 按类型读取剪切板，根据 `T` 调用对应的读取方法，例如 `let text: String = ctx.read()?;`
+	/// en: Read the clipboard by type, the getter matching `T` is called, e.g. `let text: String = ctx.read()?;`
+	fn read<T: FromClipboard>(&self) -> Result<T>
+	where
+		Self: Sized,
+	{
+		T::from_clipboard(self)
+	}
+
+	/// zh: 按类型写入剪切板，根据值的类型调用对应的写入方法，例如 `ctx.write(image)?;`
+	/// en: Write to the clipboard by type, the setter matching the type of the value is called, e.g. `ctx.write(image)?;`
+	fn write<T: ToClipboard>(&self, value: T) -> Result<()>
+	where
+		Self: Sized,
+	{
+		value.to_clipboard(self)
+	}
+
+	/// zh: 延迟写入：只声明剪切板中有哪些格式，数据在其他程序读取某种格式时才由 provider 生成，provider 返回 None 表示不提供该格式。
+	/// 文本类格式为 UTF-8 字节，图片为编码后的图片字节，文件为以换行分隔的路径。
+	/// 只有 Windows 支持延迟写入，provider 在后台线程中调用，生成失败的格式不会提供给读取的程序，错误不会返回；其他平台会立即调用 provider 写入所有格式
+	/// en: Lazy writing: only announce the formats on the clipboard, the data is produced by `provider` when another program reads a format, `provider` returns None to not provide the format.
+	/// Text formats are UTF-8 bytes, images are encoded image bytes, files are paths separated by newlines.
+	/// Only Windows supports delayed rendering, `provider` is called on a background thread there and a format whose data fails to be placed is left out for the reading program, the failure is not reported; other platforms call `provider` for every format right away
+	fn set_with_lazy<F>(&self, formats: Vec<ContentFormat>, provider: F) -> Result<()>
+	where
+		F: Fn(ContentFormat) -> Option<Vec<u8>> + Send + 'static,
+		Self: Sized,
+	{
+		let mut contents = Vec::new();
+		for format in formats {
+			if let Some(bytes) = provider(format.clone()) {
+				if let Some(content) = content_from_bytes(format, bytes)? {
+					contents.push(content);
+				}
+			}
+		}
+		self.set(contents)
+	}
+
+	/// zh: 将当前剪切板的内容复制到另一个剪切板，例如从 PRIMARY 选区复制到 CLIPBOARD，读取失败的格式会被跳过
+	/// en: Copy the content of this clipboard to another one, e.g. from the PRIMARY selection to CLIPBOARD, formats that fail to read are skipped
+	fn clone_to(&self, target: &dyn Clipboard) -> Result<()> {
+		let mut formats: Vec<ContentFormat> = Vec::new();
+		for name in self.available_formats()? {
+			let format = format_from_name(&name);
+			// several names of the same platform can map to one format, e.g. UTF8_STRING and STRING
+			if !formats.contains(&format) {
+				formats.push(format);
+			}
+		}
+		let contents = self.get(&formats)?;
+		if contents.is_empty() {
+			return target.clear();
+		}
+		target.set(contents)
+	}
+
+	/// zh: 保存剪切板当前所有可读取格式的原始数据，之后可以通过 [`Clipboard::restore`] 恢复，读取失败的格式会被跳过
+	/// en: Save the raw data of every readable format currently on the clipboard, it can be put back later with [`Clipboard::restore`], formats that fail to read are skipped
+	fn snapshot(&self) -> Result<ClipboardSnapshot> {
+		let mut item = Vec::new();
+		for info in self.available_formats_detailed()? {
+			let data = match info.name.as_deref() {
+				Some(name) => self.get_buffer(name),
+				None => continue,
+			};
+			if let Ok(data) = data {
+				item.push((info, data));
+			}
+		}
+		Ok(ClipboardSnapshot { items: vec![item] })
+	}
+
+	/// zh: 将快照中的所有格式一次性写回剪切板，空快照会清空剪切板
+	/// en: Write every format of the snapshot back to the clipboard at once, an empty snapshot clears the clipboard
+	fn restore(&self, snapshot: ClipboardSnapshot) -> Result<()> {
+		if snapshot.is_empty() {
+			return self.clear();
+		}
+		let contents = snapshot
+			.items
+			.into_iter()
+			.flatten()
+			.filter_map(|(info, data)| info.name.map(|name| ClipboardContent::Other(name, data)))
+			.collect();
+		self.set(contents)
+	}
+
+	/// zh: 计算剪切板当前内容的指纹：对所有可读取格式的名称和原始字节（图片为原始图片字节）做稳定的哈希，内容相同则指纹相同，可用于在监听回调中识别自己写入引起的变化。
+	/// 它会读取所有格式的数据，内容较大时有相应的开销
+	/// en: Compute a fingerprint of the current clipboard content: a stable hash over the names and raw bytes (the raw image bytes for images) of every readable format, the same content gives the same fingerprint, so a watcher handler can recognize changes caused by its own writes.
+	/// It reads the data of every format, so it costs as much as reading the whole content
+	fn content_fingerprint(&self) -> Result<u64> {
+		let mut entries: Vec<(String, Vec<u8>)> = self
+			.snapshot()?
+			.items
+			.into_iter()
+			.flatten()
+			.filter_map(|(info, data)| info.name.map(|name| (name, data)))
+			.collect();
+		// the platform may list the formats in any order
+		entries.sort();
+		entries.dedup();
+		let mut hasher = Fnv1a::new();
+		for (name, data) in entries {
+			// prefix the lengths so that the boundaries between fields are part of the hash
+			hasher.write(&(name.len() as u64).to_le_bytes());
+			hasher.write(name.as_bytes());
+			hasher.write(&(data.len() as u64).to_le_bytes());
+			hasher.write(&data);
+		}
+		Ok(hasher.finish())
+	}
+
+	/// zh: 获得剪切板当前的序列号，剪切板内容每次变化后序列号都会改变，可用于轮询判断剪切板是否变化，不支持的平台返回 `ClipboardError::PlatformError`
+	/// en: Get the current sequence number of the clipboard, it changes every time the clipboard content changes, so it can be used to poll for changes, `ClipboardError::PlatformError` is returned where it is not supported
+	fn get_sequence_number(&self) -> Result<u64> {
+		Err(ClipboardError::PlatformError {
+			code: 0,
+			message: "get_sequence_number is not supported on this platform".into(),
+		}
+		.into())
+	}
+
+	/// zh: 判断剪切板自从序列号为 `last` 之后是否发生了变化，适合不想启动监听线程的轮询程序。
+	/// Windows（GetClipboardSequenceNumber）和 macOS（changeCount）上只是一次很轻量的系统调用；
+	/// X11 没有原生的序列号，第一次调用时才向 X 服务器注册 XFIXES 的选区变化通知（一次往返），之后由上下文已有的事件线程维护，
+	/// 调用本身不需要与 X 服务器通信，但变化会在事件到达后才被观察到，第一次调用之前的变化不会被计入
+	/// en: Check whether the clipboard changed since the sequence number `last`, meant for polling programs that do not want a watcher thread.
+	/// On Windows (GetClipboardSequenceNumber) and macOS (changeCount) it is a single cheap system call;
+	/// X11 has no native counter, the XFIXES selection notifications are only registered with the X server on the first call (one round trip), the counter is then maintained by the event thread the context already has,
+	/// later calls do not talk to the X server, but a change is only observed once its event has arrived, changes before the first call are not counted
+	fn has_changed_since(&self, last: u64) -> Result<bool> {
+		Ok(self.get_sequence_number()? != last)
+	}
 }
 
 pub trait ClipboardWatcher<T: ClipboardHandler>: Send {
@@ -60,19 +488,44 @@ pub trait ClipboardWatcher<T: ClipboardHandler>: Send {
 	/// en: Add a clipboard change handler, you can add multiple handlers, the handler needs to implement the trait [`ClipboardHandler`]
 	fn add_handler(&mut self, handler: T) -> &mut Self;
 
+	/// zh: 添加一个只关心部分格式的处理器，只有变化后的剪切板内容包含其中至少一种格式时才会调用。
+	/// 默认实现忽略 formats，通过 [`ClipboardWatcher::add_handler`] 添加一个处理所有变化的处理器，本库的监听器都会按格式过滤
+	/// en: Add a handler that only cares about some formats, it is only called when the new clipboard content contains at least one of them.
+	/// The default implementation ignores `formats` and adds a handler for every change with [`ClipboardWatcher::add_handler`], the watchers of this crate all filter by format
+	fn add_filtered_handler(&mut self, handler: T, _formats: Vec<ContentFormat>) -> &mut Self {
+		self.add_handler(handler)
+	}
+
+	/// zh: 添加一个带名称的处理器，之后可以通过 [`ClipboardWatcher::remove_named_handler`] 按名称移除，已有同名处理器时会替换它。
+	/// 默认实现忽略名称，通过 [`ClipboardWatcher::add_handler`] 添加
+	/// en: Add a handler with a name, it can later be removed by name with [`ClipboardWatcher::remove_named_handler`], a handler already added with the same name is replaced.
+	/// The default implementation ignores the name and adds the handler with [`ClipboardWatcher::add_handler`]
+	fn add_named_handler(&mut self, _name: &str, handler: T) -> &mut Self {
+		self.add_handler(handler)
+	}
+
+	/// zh: 移除第 index 个添加的处理器（从 0 开始，包括所有方式添加的处理器）并返回它，下标越界时返回 None，需要在 [`ClipboardWatcher::start_watch`] 之前或停止之后调用。
+	/// 默认实现不支持移除，总是返回 None
+	/// en: Remove the handler added at `index` (starting from 0, counting handlers added by every method) and return it, None when the index is out of range, must be called before [`ClipboardWatcher::start_watch`] or after the watcher stopped.
+	/// The default implementation does not support removal and always returns None
+	fn remove_handler(&mut self, _index: usize) -> Option<T> {
+		None
+	}
+
+	/// zh: 移除指定名称的处理器并返回它，没有该名称的处理器时返回 None。默认实现不支持移除，总是返回 None
+	/// en: Remove the handler with the given name and return it, None when there is no handler with that name. The default implementation does not support removal and always returns None
+	fn remove_named_handler(&mut self, _name: &str) -> Option<T> {
+		None
+	}
+
 	/// zh: 开始监视剪切板变化，这是一个阻塞方法，直到监视结束，或者调用了stop方法，所以建议在单独的线程中调用
 	/// en: Start monitoring clipboard changes, this is a blocking method, until the monitoring ends, or the stop method is called, so it is recommended to call it in a separate thread
+	///
+	/// zh: 停止后可以再次调用此方法重新开始监视，停止之前发出的停止信号会被忽略
+	/// en: The watcher can be started again after it has been stopped, stop signals sent before the call are ignored
 	fn start_watch(&mut self);
 
 	/// zh: 获得停止监视的通道，可以通过这个通道停止监视
 	/// en: Get the channel to stop monitoring, you can stop monitoring through this channel
 	fn get_shutdown_channel(&self) -> WatcherShutdown;
 }
-
-impl WatcherShutdown {
-	/// zh: 停止监视
-	/// en: stop watching
-	pub fn stop(self) {
-		drop(self);
-	}
-}
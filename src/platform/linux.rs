@@ -0,0 +1,534 @@
+use super::memory;
+#[cfg(feature = "osc52")]
+use super::osc52;
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+use super::wayland;
+use super::x11::{self, ClipboardContextX11Options};
+use super::BACKEND_ENV;
+use crate::common::{
+	ClipboardSnapshot, FileOperation, FormatInfo, LinuxBackend, Result, WatcherShutdown, XSelection,
+};
+use crate::{
+	Clipboard, ClipboardContent, ClipboardContextBuilder, ClipboardError, ClipboardEvent,
+	ClipboardHandler, ClipboardWatcher, ContentFormat, RustImageData,
+};
+use image::ImageFormat;
+use std::error::Error;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+
+/// zh: 剪切板上下文，创建时根据环境变量在运行时选择 X11 或 Wayland 后端，也可以通过 [`ClipboardContextBuilder::force_backend`] 或环境变量 `CLIPBOARD_RS_BACKEND`（`x11`、`wayland`、`osc52` 或 `memory`）指定
+/// en: The clipboard context, the X11 or the Wayland backend is chosen at runtime from the environment when it is created, or given with [`ClipboardContextBuilder::force_backend`] or the environment variable `CLIPBOARD_RS_BACKEND` (`x11`, `wayland`, `osc52` or `memory`)
+pub struct ClipboardContext {
+	inner: LinuxClipboardContext,
+}
+
+enum LinuxClipboardContext {
+	// boxed, the X11 context is much larger than the others
+	X11(Box<x11::ClipboardContext>),
+	#[cfg(all(target_os = "linux", feature = "wayland"))]
+	Wayland(wayland::ClipboardContext),
+	#[cfg(feature = "osc52")]
+	Osc52(osc52::ClipboardContext),
+	Memory(memory::ClipboardContext),
+}
+
+pub struct ClipboardWatcherContext<T: ClipboardHandler> {
+	inner: LinuxClipboardWatcherContext<T>,
+}
+
+enum LinuxClipboardWatcherContext<T: ClipboardHandler> {
+	// boxed, the X11 watcher is much larger than the memory one
+	X11(Box<x11::ClipboardWatcherContext<T>>),
+	#[cfg(all(target_os = "linux", feature = "wayland"))]
+	Wayland(wayland::ClipboardWatcherContext<T>),
+	Memory(memory::ClipboardWatcherContext<T>),
+}
+
+// call the same method on whichever backend is in use
+macro_rules! dispatch {
+	($inner:expr, $backend:ident => $body:expr) => {
+		match $inner {
+			LinuxClipboardContext::X11($backend) => $body,
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardContext::Wayland($backend) => $body,
+			#[cfg(feature = "osc52")]
+			LinuxClipboardContext::Osc52($backend) => $body,
+			LinuxClipboardContext::Memory($backend) => $body,
+		}
+	};
+}
+
+macro_rules! dispatch_watcher {
+	($inner:expr, $backend:ident => $body:expr) => {
+		match $inner {
+			LinuxClipboardWatcherContext::X11($backend) => $body,
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardWatcherContext::Wayland($backend) => $body,
+			LinuxClipboardWatcherContext::Memory($backend) => $body,
+		}
+	};
+}
+
+fn env_is_set(name: &str) -> bool {
+	std::env::var_os(name).map_or(false, |value| !value.is_empty())
+}
+
+fn is_wayland_session() -> bool {
+	env_is_set("WAYLAND_DISPLAY")
+		|| std::env::var("XDG_SESSION_TYPE").map_or(false, |session| session == "wayland")
+}
+
+// why there is no display server to use
+fn no_display_message() -> &'static str {
+	if is_wayland_session() {
+		"This is a Wayland session without Xwayland (DISPLAY is unset), enable the `wayland` feature to use the Wayland backend"
+	} else {
+		"No display server available (DISPLAY and WAYLAND_DISPLAY are unset)"
+	}
+}
+
+// the backend asked for with CLIPBOARD_RS_BACKEND, e.g. the memory backend for tests without a display
+fn env_backend() -> Result<Option<LinuxBackend>> {
+	let value = match std::env::var(BACKEND_ENV) {
+		Ok(value) if !value.is_empty() => value,
+		_ => return Ok(None),
+	};
+	match value.to_ascii_lowercase().as_str() {
+		"x11" => Ok(Some(LinuxBackend::X11)),
+		"wayland" => Ok(Some(LinuxBackend::Wayland)),
+		"osc52" => Ok(Some(LinuxBackend::Osc52)),
+		"memory" => Ok(Some(LinuxBackend::Memory)),
+		_ => Err(format!(
+			"Unknown {} value {:?}, expected x11, wayland, osc52 or memory",
+			BACKEND_ENV, value
+		)
+		.into()),
+	}
+}
+
+// the backends worth trying in this session, in order of preference
+fn detect_backends() -> Vec<LinuxBackend> {
+	let mut backends = Vec::new();
+	if is_wayland_session() && cfg!(all(target_os = "linux", feature = "wayland")) {
+		backends.push(LinuxBackend::Wayland);
+	}
+	// also Xwayland, the fallback for compositors without wlr-data-control
+	if env_is_set("DISPLAY") {
+		backends.push(LinuxBackend::X11);
+	}
+	// e.g. over SSH, the terminal is the only clipboard there is
+	if backends.is_empty() && cfg!(feature = "osc52") {
+		backends.push(LinuxBackend::Osc52);
+	}
+	backends
+}
+
+// prefix the error with the backend so that it is clear which one failed
+fn backend_error(backend: LinuxBackend, e: Box<dyn Error + Send + Sync>) -> ClipboardError {
+	let (code, message) = match e.downcast::<ClipboardError>() {
+		Ok(e) => match *e {
+			ClipboardError::PlatformError { code, message } => (code, message),
+			e => (0, e.to_string()),
+		},
+		Err(e) => (0, e.to_string()),
+	};
+	ClipboardError::PlatformError {
+		code,
+		message: format!("{} backend: {}", backend, message),
+	}
+}
+
+impl ClipboardContext {
+	pub fn new() -> Result<Self> {
+		Self::from_builder(&ClipboardContextBuilder::default())
+	}
+
+	/// zh: 创建读写指定选区的上下文，例如读写中键粘贴使用的 PRIMARY 选区，仅在 Linux 上可用，等同于 `ClipboardContextBuilder::default().with_selection(selection).build()`
+	/// en: Create a context reading and writing the given selection, e.g. the PRIMARY selection used by middle-click paste, only available on Linux, the same as `ClipboardContextBuilder::default().with_selection(selection).build()`
+	pub fn new_for_selection(selection: XSelection) -> Result<Self> {
+		Self::from_builder(&ClipboardContextBuilder::default().with_selection(selection))
+	}
+
+	/// zh: 创建通过 OSC 52 转义序列读写终端剪切板的上下文，适用于通过 SSH 运行、没有显示服务的命令行程序，只支持纯文本
+	/// en: Create a context reading and writing the clipboard of the terminal with the OSC 52 escape sequence, for command line programs run over SSH without a display server, only plain text is supported
+	#[cfg(feature = "osc52")]
+	pub fn new_osc52() -> Result<Self> {
+		Self::from_builder(&ClipboardContextBuilder::default().force_backend(LinuxBackend::Osc52))
+	}
+
+	/// zh: 使用 X11 专有的选项创建上下文，总是使用 X11 后端
+	/// en: Create the context with the X11 specific options, the X11 backend is always used
+	pub fn new_with_options(options: ClipboardContextX11Options) -> Result<Self> {
+		let ctx = x11::ClipboardContext::new_with_options(options)
+			.map_err(|e| backend_error(LinuxBackend::X11, e))?;
+		Ok(Self {
+			inner: LinuxClipboardContext::X11(Box::new(ctx)),
+		})
+	}
+
+	pub(crate) fn from_builder(builder: &ClipboardContextBuilder) -> Result<Self> {
+		let forced = match builder.backend {
+			Some(backend) => Some(backend),
+			None => env_backend()?,
+		};
+		let backends = match forced {
+			Some(backend) => vec![backend],
+			None => detect_backends(),
+		};
+		let mut errors = Vec::new();
+		// only the terminal is left, or nothing at all
+		if forced.is_none()
+			&& !backends.contains(&LinuxBackend::X11)
+			&& !backends.contains(&LinuxBackend::Wayland)
+		{
+			errors.push((0, no_display_message().to_string()));
+		}
+		for backend in backends {
+			match Self::with_backend(backend, builder) {
+				Ok(ctx) => return Ok(ctx),
+				Err(e) => {
+					if let ClipboardError::PlatformError { code, message } =
+						backend_error(backend, e)
+					{
+						errors.push((code, message));
+					}
+				}
+			}
+		}
+		// the code of the platform only makes sense when a single backend failed
+		let code = match errors.as_slice() {
+			[(code, _)] => *code,
+			_ => 0,
+		};
+		let messages: Vec<String> = errors.into_iter().map(|(_, message)| message).collect();
+		Err(ClipboardError::PlatformError {
+			code,
+			message: messages.join("; "),
+		}
+		.into())
+	}
+
+	fn with_backend(backend: LinuxBackend, builder: &ClipboardContextBuilder) -> Result<Self> {
+		let inner = match backend {
+			LinuxBackend::X11 => {
+				LinuxClipboardContext::X11(Box::new(x11::ClipboardContext::from_builder(builder)?))
+			}
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxBackend::Wayland => {
+				LinuxClipboardContext::Wayland(wayland::ClipboardContext::from_builder(builder)?)
+			}
+			#[cfg(not(all(target_os = "linux", feature = "wayland")))]
+			LinuxBackend::Wayland => {
+				return Err("The Wayland backend needs the `wayland` feature".into());
+			}
+			#[cfg(feature = "osc52")]
+			LinuxBackend::Osc52 => {
+				LinuxClipboardContext::Osc52(osc52::ClipboardContext::from_builder(builder)?)
+			}
+			#[cfg(not(feature = "osc52"))]
+			LinuxBackend::Osc52 => {
+				return Err("The OSC 52 backend needs the `osc52` feature".into());
+			}
+			LinuxBackend::Memory => LinuxClipboardContext::Memory(memory::ClipboardContext::new()?),
+		};
+		Ok(Self { inner })
+	}
+
+	/// zh: 关闭上下文，X11 上会停止服务剪贴板请求的后台线程并等待它退出，丢弃上下文时也会自动执行，这里可以获得停止时的错误
+	/// en: Close the context, on X11 the background thread serving clipboard requests is stopped and joined, dropping the context does the same, this returns the error of stopping
+	pub fn close(self) -> Result<()> {
+		match self.inner {
+			LinuxClipboardContext::X11(ctx) => (*ctx).close(),
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardContext::Wayland(_) => Ok(()),
+			#[cfg(feature = "osc52")]
+			LinuxClipboardContext::Osc52(_) => Ok(()),
+			LinuxClipboardContext::Memory(_) => Ok(()),
+		}
+	}
+
+	/// zh: 获得正在使用的后端
+	/// en: Get the backend in use
+	pub fn backend(&self) -> LinuxBackend {
+		match self.inner {
+			LinuxClipboardContext::X11(_) => LinuxBackend::X11,
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardContext::Wayland(_) => LinuxBackend::Wayland,
+			#[cfg(feature = "osc52")]
+			LinuxClipboardContext::Osc52(_) => LinuxBackend::Osc52,
+			LinuxClipboardContext::Memory(_) => LinuxBackend::Memory,
+		}
+	}
+
+	/// zh: 设置失去剪贴板所有权时（其他程序写入了剪贴板）的回调，回调在后台线程中执行，再次设置会替换之前的回调，仅支持 X11 后端
+	/// en: Set the callback invoked when the ownership of the clipboard is lost (another program wrote to the clipboard), the callback runs on a background thread, setting it again replaces the previous callback, only supported by the X11 backend
+	pub fn set_ownership_lost_callback<F>(&self, callback: F) -> Result<()>
+	where
+		F: FnMut() + Send + 'static,
+	{
+		match &self.inner {
+			LinuxClipboardContext::X11(ctx) => ctx.set_ownership_lost_callback(callback),
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardContext::Wayland(_) => {
+				Err("The ownership lost callback is not supported on Wayland".into())
+			}
+			#[cfg(feature = "osc52")]
+			LinuxClipboardContext::Osc52(_) => {
+				Err("The ownership lost callback is not supported by OSC 52".into())
+			}
+			LinuxClipboardContext::Memory(_) => {
+				Err("The ownership lost callback is not supported by the memory backend".into())
+			}
+		}
+	}
+
+	/// zh: 将当前写入的剪贴板内容交给剪贴板管理器保存，使其在程序退出后依然可用，没有剪贴板管理器时返回错误，仅支持 X11 后端
+	/// en: Hand the clipboard content written by this context over to the clipboard manager, so that it is still available after the program exits, returns an error if there is no clipboard manager, only supported by the X11 backend
+	pub fn persist_on_exit(&self) -> Result<()> {
+		match &self.inner {
+			LinuxClipboardContext::X11(ctx) => ctx.persist_on_exit(),
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardContext::Wayland(_) => Err("persist_on_exit is not supported on Wayland".into()),
+			#[cfg(feature = "osc52")]
+			LinuxClipboardContext::Osc52(_) => Err("persist_on_exit is not supported by OSC 52".into()),
+			LinuxClipboardContext::Memory(_) => {
+				Err("persist_on_exit is not supported by the memory backend".into())
+			}
+		}
+	}
+}
+
+impl Clipboard for ClipboardContext {
+	fn available_formats(&self) -> Result<Vec<String>> {
+		dispatch!(&self.inner, ctx => ctx.available_formats())
+	}
+
+	fn available_formats_detailed(&self) -> Result<Vec<FormatInfo>> {
+		dispatch!(&self.inner, ctx => ctx.available_formats_detailed())
+	}
+
+	fn has(&self, format: ContentFormat) -> bool {
+		dispatch!(&self.inner, ctx => ctx.has(format))
+	}
+
+	fn has_any(&self, formats: &[ContentFormat]) -> bool {
+		dispatch!(&self.inner, ctx => ctx.has_any(formats))
+	}
+
+	fn has_all(&self, formats: &[ContentFormat]) -> bool {
+		dispatch!(&self.inner, ctx => ctx.has_all(formats))
+	}
+
+	fn is_empty(&self) -> bool {
+		dispatch!(&self.inner, ctx => ctx.is_empty())
+	}
+
+	fn clear(&self) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.clear())
+	}
+
+	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_buffer(format))
+	}
+
+	fn read_streaming(&self, format: &str, sink: &mut dyn Write) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.read_streaming(format, sink))
+	}
+
+	fn get_text(&self) -> Result<String> {
+		dispatch!(&self.inner, ctx => ctx.get_text())
+	}
+
+	fn get_rich_text(&self) -> Result<String> {
+		dispatch!(&self.inner, ctx => ctx.get_rich_text())
+	}
+
+	fn get_html(&self) -> Result<String> {
+		dispatch!(&self.inner, ctx => ctx.get_html())
+	}
+
+	fn get_text_bytes(&self) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_text_bytes())
+	}
+
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_rich_text_bytes())
+	}
+
+	fn get_html_bytes(&self) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_html_bytes())
+	}
+
+	fn get_image(&self) -> Result<RustImageData> {
+		dispatch!(&self.inner, ctx => ctx.get_image())
+	}
+
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		dispatch!(&self.inner, ctx => ctx.image_dimensions())
+	}
+
+	fn get_files(&self) -> Result<Vec<String>> {
+		dispatch!(&self.inner, ctx => ctx.get_files())
+	}
+
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		dispatch!(&self.inner, ctx => ctx.get_files_with_operation())
+	}
+
+	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
+		dispatch!(&self.inner, ctx => ctx.get(formats))
+	}
+
+	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_buffer(format, buffer))
+	}
+
+	fn set_text(&self, text: String) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_text(text))
+	}
+
+	fn set_rich_text(&self, text: String) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_rich_text(text))
+	}
+
+	fn set_html(&self, html: String) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_html(html))
+	}
+
+	fn set_image(&self, image: RustImageData) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_image(image))
+	}
+
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_image_from_path(path))
+	}
+
+	fn set_files(&self, files: Vec<String>) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_files(files))
+	}
+
+	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set(contents))
+	}
+
+	fn snapshot(&self) -> Result<ClipboardSnapshot> {
+		dispatch!(&self.inner, ctx => ctx.snapshot())
+	}
+
+	fn restore(&self, snapshot: ClipboardSnapshot) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.restore(snapshot))
+	}
+
+	fn get_sequence_number(&self) -> Result<u64> {
+		dispatch!(&self.inner, ctx => ctx.get_sequence_number())
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
+	pub fn new() -> Result<Self> {
+		Self::new_with_clipboard(ClipboardContext::new()?)
+	}
+
+	/// zh: 使用指定的剪切板上下文创建监听器，处理器会通过它读取剪切板内容，监听器使用与它相同的后端
+	/// en: Create a watcher with the given clipboard context, handlers read the clipboard through it, the watcher uses the same backend as the context
+	pub fn new_with_clipboard(clipboard: ClipboardContext) -> Result<Self> {
+		let inner = match clipboard.inner {
+			LinuxClipboardContext::X11(ctx) => LinuxClipboardWatcherContext::X11(Box::new(
+				x11::ClipboardWatcherContext::new_with_clipboard(*ctx)?,
+			)),
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardContext::Wayland(ctx) => LinuxClipboardWatcherContext::Wayland(
+				wayland::ClipboardWatcherContext::new_with_clipboard(ctx)?,
+			),
+			#[cfg(feature = "osc52")]
+			LinuxClipboardContext::Osc52(_) => {
+				return Err("The clipboard cannot be watched through OSC 52".into());
+			}
+			LinuxClipboardContext::Memory(ctx) => LinuxClipboardWatcherContext::Memory(
+				memory::ClipboardWatcherContext::new_with_clipboard(ctx)?,
+			),
+		};
+		Ok(Self { inner })
+	}
+
+	/// zh: 设置监听的选区，默认只监听 CLIPBOARD，变化的选区可以通过 [`ClipboardEvent::selection`] 获得，需要在开始监听前调用
+	/// en: Set the selections to watch, only CLIPBOARD is watched by default, the selection that changed is reported in [`ClipboardEvent::selection`], must be called before the watch starts
+	pub fn watch_selections(&mut self, selections: &[XSelection]) -> &mut Self {
+		match &mut self.inner {
+			LinuxClipboardWatcherContext::X11(watcher) => {
+				watcher.watch_selections(selections);
+			}
+			#[cfg(all(target_os = "linux", feature = "wayland"))]
+			LinuxClipboardWatcherContext::Wayland(watcher) => {
+				watcher.watch_selections(selections);
+			}
+			// the memory clipboard has no selections
+			LinuxClipboardWatcherContext::Memory(_) => {}
+		}
+		self
+	}
+
+	/// zh: 设置每秒最多通知处理器的次数，超出的变化会被合并，但一连串变化中的最后一次总会被通知，小于等于 0 表示不限制
+	/// en: Set the maximum number of events per second dispatched to handlers, changes beyond the rate are coalesced but the last change of a burst is always delivered, a value <= 0 means no limit
+	pub fn set_max_event_rate(&mut self, max_events_per_second: f32) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.set_max_event_rate(max_events_per_second);
+		});
+		self
+	}
+
+	/// zh: 设置丢弃 [`WatcherShutdown`] 时是否停止监听（旧版本的行为），默认只有调用 [`WatcherShutdown::stop`] 才会停止，只影响之后获得的停止通道
+	/// en: Set whether dropping a [`WatcherShutdown`] stops the watcher (the behavior of older versions), by default only [`WatcherShutdown::stop`] stops it, only affects shutdown channels obtained afterwards
+	pub fn set_stop_on_drop(&mut self, stop_on_drop: bool) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.set_stop_on_drop(stop_on_drop);
+		});
+		self
+	}
+
+	/// zh: 订阅剪切板变化事件，返回一个接收变化事件的通道，每次调用都会创建一个新的独立通道
+	/// en: Subscribe to clipboard change events, returns a receiver of the change events, every call creates a new independent channel
+	pub fn subscribe(&mut self) -> Receiver<ClipboardEvent> {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.subscribe())
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
+	fn add_handler(&mut self, handler: T) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.add_handler(handler);
+		});
+		self
+	}
+
+	fn add_filtered_handler(&mut self, handler: T, formats: Vec<ContentFormat>) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.add_filtered_handler(handler, formats);
+		});
+		self
+	}
+
+	fn add_named_handler(&mut self, name: &str, handler: T) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.add_named_handler(name, handler);
+		});
+		self
+	}
+
+	fn remove_handler(&mut self, index: usize) -> Option<T> {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.remove_handler(index))
+	}
+
+	fn remove_named_handler(&mut self, name: &str) -> Option<T> {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.remove_named_handler(name))
+	}
+
+	fn start_watch(&mut self) {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.start_watch())
+	}
+
+	fn get_shutdown_channel(&self) -> WatcherShutdown {
+		dispatch_watcher!(&self.inner, watcher => watcher.get_shutdown_channel())
+	}
+}
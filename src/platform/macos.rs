@@ -1,5 +1,14 @@
-use crate::common::{Result, RustImage, RustImageData};
-use crate::{Clipboard, ClipboardContent, ClipboardHandler, ClipboardWatcher, ContentFormat};
+use crate::common::{
+	dispatch_to_handlers, image_dimensions_from_bytes, insert_named_handler, is_png,
+	notify_subscribers, take_handler_at, take_named_handler, ChangeSource, ClipboardOwner,
+	ClipboardSnapshot, EventThrottle, FormatInfo, HandlerEntry, Result, RustImage, RustImageData,
+	WatcherShutdown,
+};
+use crate::{
+	Clipboard, ClipboardContent, ClipboardContextBuilder, ClipboardEvent, ClipboardHandler,
+	ClipboardWatcher, ContentFormat,
+};
+use image::ImageFormat;
 use objc2::rc::Retained;
 use objc2::{
 	rc::{autoreleasepool, Id},
@@ -8,60 +17,135 @@ use objc2::{
 };
 use objc2_app_kit::{
 	NSFilenamesPboardType, NSImage, NSPasteboard, NSPasteboardItem, NSPasteboardType,
-	NSPasteboardTypeHTML, NSPasteboardTypePNG, NSPasteboardTypeRTF, NSPasteboardTypeString,
-	NSPasteboardTypeTIFF, NSPasteboardWriting,
+	NSPasteboardTypeFileURL, NSPasteboardTypeHTML, NSPasteboardTypePNG, NSPasteboardTypeRTF,
+	NSPasteboardTypeString, NSPasteboardTypeTIFF, NSPasteboardTypeURL, NSPasteboardWriting,
+	NSRunningApplication, NSWorkspace,
 };
-use objc2_foundation::{NSArray, NSData, NSString};
+use objc2_foundation::{NSArray, NSData, NSString, NSURL};
 use std::ffi::c_void;
+use std::sync::atomic::{AtomicIsize, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::vec;
 
+// Markdown 的 UTI，AppKit 没有预定义
+const MARKDOWN_UTI: &str = "net.daringfireball.markdown";
+
+// 本进程最近一次写入粘贴板后的 changeCount，监听器据此判断变化是否来自本进程
+static LAST_OWN_WRITE: AtomicIsize = AtomicIsize::new(-1);
+
 pub struct ClipboardContext {
 	pasteboard: Id<NSPasteboard>,
+	// 最近一次查询到的写入者及当时的 changeCount，同一次变化重复查询时返回相同的结果
+	owner: Mutex<Option<(isize, ClipboardOwner)>>,
 }
 
 pub struct ClipboardWatcherContext<T: ClipboardHandler> {
 	pasteboard: Id<NSPasteboard>,
-	handlers: Vec<T>,
+	handlers: Vec<HandlerEntry<T>>,
 	stop_signal: Sender<()>,
 	stop_receiver: Receiver<()>,
+	// start_watch 退出后发送，WatcherShutdown::wait_for_completion 等待它
+	done_signal: Sender<()>,
+	done_receiver: Arc<Mutex<Receiver<()>>>,
 	running: bool,
+	// 旧的行为：WatcherShutdown 被丢弃时停止监听
+	stop_on_drop: bool,
+	throttle: EventThrottle,
+	subscribers: Vec<Sender<ClipboardEvent>>,
+	// 传给处理器用来读取剪切板内容
+	clipboard: ClipboardContext,
 }
 
 unsafe impl<T: ClipboardHandler> Send for ClipboardWatcherContext<T> {}
 
 impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
-	pub fn new() -> Result<Self> {
-		let ns_pasteboard = unsafe { NSPasteboard::generalPasteboard() };
+	/// zh: 使用指定的剪切板上下文创建监听器，处理器会通过它读取剪切板内容
+	/// en: Create a watcher with the given clipboard context, handlers read the clipboard through it
+	pub fn new_with_clipboard(clipboard: ClipboardContext) -> Result<Self> {
+		// watch the pasteboard the handlers read, which is not the general one with `pasteboard_name`
+		let ns_pasteboard = clipboard.pasteboard.clone();
 		let (tx, rx) = mpsc::channel();
+		let (done_tx, done_rx) = mpsc::channel();
 		Ok(ClipboardWatcherContext {
 			pasteboard: ns_pasteboard,
 			handlers: Vec::new(),
 			stop_signal: tx,
 			stop_receiver: rx,
+			done_signal: done_tx,
+			done_receiver: Arc::new(Mutex::new(done_rx)),
 			running: false,
+			stop_on_drop: false,
+			throttle: EventThrottle::default(),
+			subscribers: Vec::new(),
+			clipboard,
 		})
 	}
+
+	/// zh: 设置每秒最多通知处理器的次数，超出的变化会被合并，但一连串变化中的最后一次总会被通知，小于等于 0 表示不限制
+	/// en: Set the maximum number of events per second dispatched to handlers, changes beyond the rate are coalesced but the last change of a burst is always delivered, a value <= 0 means no limit
+	pub fn set_max_event_rate(&mut self, max_events_per_second: f32) -> &mut Self {
+		self.throttle.set_max_event_rate(max_events_per_second);
+		self
+	}
+
+	/// zh: 设置丢弃 [`WatcherShutdown`] 时是否停止监听（旧版本的行为），默认只有调用 [`WatcherShutdown::stop`] 才会停止，只影响之后获得的停止通道
+	/// en: Set whether dropping a [`WatcherShutdown`] stops the watcher (the behavior of older versions), by default only [`WatcherShutdown::stop`] stops it, only affects shutdown channels obtained afterwards
+	pub fn set_stop_on_drop(&mut self, stop_on_drop: bool) -> &mut Self {
+		self.stop_on_drop = stop_on_drop;
+		self
+	}
+
+	/// zh: 订阅剪切板变化事件，返回一个接收变化事件的通道，每次调用都会创建一个新的独立通道
+	/// en: Subscribe to clipboard change events, returns a receiver of the change events, every call creates a new independent channel
+	pub fn subscribe(&mut self) -> Receiver<ClipboardEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.push(tx);
+		rx
+	}
 }
 
 impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
 	fn add_handler(&mut self, handler: T) -> &mut Self {
-		self.handlers.push(handler);
+		self.handlers.push(HandlerEntry::new(handler, None));
 		self
 	}
 
+	fn add_filtered_handler(&mut self, handler: T, formats: Vec<ContentFormat>) -> &mut Self {
+		self.handlers
+			.push(HandlerEntry::new(handler, Some(formats)));
+		self
+	}
+
+	fn add_named_handler(&mut self, name: &str, handler: T) -> &mut Self {
+		insert_named_handler(&mut self.handlers, name, handler);
+		self
+	}
+
+	fn remove_handler(&mut self, index: usize) -> Option<T> {
+		take_handler_at(&mut self.handlers, index)
+	}
+
+	fn remove_named_handler(&mut self, name: &str) -> Option<T> {
+		take_named_handler(&mut self.handlers, name)
+	}
+
 	fn start_watch(&mut self) {
 		if self.running {
 			println!("already start watch!");
 			return;
 		}
-		if self.handlers.is_empty() {
+		if self.handlers.is_empty() && self.subscribers.is_empty() {
 			println!("no handler, no need to start watch!");
 			return;
 		}
 		self.running = true;
+		// drain the stop signals left over from a previous session (e.g. `WatcherShutdown::stop` called while the watcher was not running),
+		// so that the watcher can be restarted after it has been stopped
+		while self.stop_receiver.try_recv().is_ok() {}
 		let mut last_change_count = unsafe { self.pasteboard.changeCount() };
+		let mut last_event = None;
 		loop {
 			// if receive stop signal, break loop
 			if self
@@ -72,30 +156,49 @@ impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
 				break;
 			}
 			let change_count = unsafe { self.pasteboard.changeCount() };
+			let mut changed = false;
 			if last_change_count == 0 {
 				last_change_count = change_count;
 			} else if change_count != last_change_count {
-				self.handlers
-					.iter_mut()
-					.for_each(|handler| handler.on_clipboard_change());
+				changed = true;
 				last_change_count = change_count;
+				let mut event = ClipboardEvent::new(None);
+				if change_count == LAST_OWN_WRITE.load(Ordering::SeqCst) {
+					event.source = ChangeSource::OwnWrite;
+				}
+				last_event = Some(event);
+			}
+			if self.throttle.should_dispatch(changed, Instant::now()) {
+				if let Some(event) = last_event.take() {
+					dispatch_to_handlers(&mut self.handlers, &self.clipboard, &event, |format| {
+						self.clipboard.has(format.clone())
+					});
+					notify_subscribers(&mut self.subscribers, &event);
+				}
 			}
 		}
 		self.running = false;
+		let _ = self.done_signal.send(());
 	}
 
 	fn get_shutdown_channel(&self) -> WatcherShutdown {
-		WatcherShutdown {
-			stop_signal: self.stop_signal.clone(),
-		}
+		WatcherShutdown::new(
+			self.stop_signal.clone(),
+			self.done_receiver.clone(),
+			self.stop_on_drop,
+		)
 	}
 }
 
 impl ClipboardContext {
-	pub fn new() -> Result<ClipboardContext> {
-		let ns_pasteboard = unsafe { NSPasteboard::generalPasteboard() };
+	pub(crate) fn from_builder(builder: &ClipboardContextBuilder) -> Result<ClipboardContext> {
+		let ns_pasteboard = match &builder.pasteboard_name {
+			Some(name) => unsafe { NSPasteboard::pasteboardWithName(&NSString::from_str(name)) },
+			None => unsafe { NSPasteboard::generalPasteboard() },
+		};
 		let clipboard_ctx = ClipboardContext {
 			pasteboard: ns_pasteboard,
+			owner: Mutex::new(None),
 		};
 		Ok(clipboard_ctx)
 	}
@@ -113,6 +216,44 @@ impl ClipboardContext {
 		})
 	}
 
+	/// zh: 按剪切板中的每个 NSPasteboardItem 分别读取内容，返回的每一项对应一个 item 中可识别的所有内容
+	/// en: Read the content of every NSPasteboardItem on the clipboard separately, each entry of the result holds all recognized contents of one item
+	pub fn get_items(&self) -> Result<Vec<Vec<ClipboardContent>>> {
+		autoreleasepool(|_| {
+			let items = unsafe { self.pasteboard.pasteboardItems() }
+				.ok_or("NSPasteboard#pasteboardItems errored")?;
+			Ok(items.iter().map(item_contents).collect())
+		})
+	}
+
+	/// zh: 获得最近一次写入剪切板的程序。NSPasteboard 不记录写入者，这里返回第一次查询该次变化时的前台程序，
+	/// 本进程写入时返回本进程，因此应在变化后尽快调用（例如在 [`ClipboardHandler`] 中）
+	/// en: Get the program that wrote the clipboard last. NSPasteboard does not record the writer, the frontmost application
+	/// at the first query for the change is returned instead, or this process if it wrote the change, so call it soon after the change (e.g. in a [`ClipboardHandler`])
+	pub fn get_owner(&self) -> Option<ClipboardOwner> {
+		let change_count = unsafe { self.pasteboard.changeCount() };
+		let mut owner = self.owner.lock().ok()?;
+		if let Some((count, cached)) = owner.as_ref() {
+			if *count == change_count {
+				return Some(cached.clone());
+			}
+		}
+		let current = autoreleasepool(|_| unsafe {
+			let app = if LAST_OWN_WRITE.load(Ordering::SeqCst) == change_count {
+				NSRunningApplication::currentApplication()
+			} else {
+				NSWorkspace::sharedWorkspace().frontmostApplication()?
+			};
+			let pid = app.processIdentifier();
+			Some(ClipboardOwner {
+				app_name: app.localizedName().map(|name| name.to_string()),
+				pid: u32::try_from(pid).ok(),
+			})
+		})?;
+		*owner = Some((change_count, current.clone()));
+		Some(current)
+	}
+
 	fn set_files(&self, files: &[String]) -> Result<()> {
 		let ns_string_arr =
 			NSArray::from_vec(files.iter().map(|f| NSString::from_str(f)).collect());
@@ -120,9 +261,16 @@ impl ClipboardContext {
 			self.pasteboard
 				.setPropertyList_forType(&ns_string_arr, NSFilenamesPboardType)
 		};
+		self.record_own_write();
 		Ok(())
 	}
 
+	// remember the change count after a write of this process
+	fn record_own_write(&self) {
+		let change_count = unsafe { self.pasteboard.changeCount() };
+		LAST_OWN_WRITE.store(change_count, Ordering::SeqCst);
+	}
+
 	// learn from https://github.com/zed-industries/zed/blob/79c1003b344ee513cf97ee8313c38c7c3f02c916/crates/gpui/src/platform/mac/platform.rs#L793
 	fn write_to_clipboard(&self, data: &[ClipboardContent], with_clear: bool) -> Result<()> {
 		if with_clear {
@@ -131,7 +279,7 @@ impl ClipboardContext {
 			}
 		}
 		autoreleasepool(|_| unsafe {
-			let mut write_objects: Vec<Id<ProtocolObject<(dyn NSPasteboardWriting + 'static)>>> =
+			let mut write_objects: Vec<Id<ProtocolObject<dyn NSPasteboardWriting + 'static>>> =
 				vec![];
 			for d in data {
 				match d {
@@ -150,33 +298,33 @@ impl ClipboardContext {
 						item.setString_forType(&NSString::from_str(html), NSPasteboardTypeHTML);
 						write_objects.push(ProtocolObject::from_id(item));
 					}
+					ClipboardContent::Markdown(markdown) => {
+						let item = NSPasteboardItem::new();
+						item.setString_forType(
+							&NSString::from_str(markdown),
+							&NSString::from_str(MARKDOWN_UTI),
+						);
+						write_objects.push(ProtocolObject::from_id(item));
+					}
+					ClipboardContent::Url(url) => {
+						let item = NSPasteboardItem::new();
+						item.setString_forType(&NSString::from_str(url), NSPasteboardTypeURL);
+						write_objects.push(ProtocolObject::from_id(item));
+					}
 					ClipboardContent::Image(image) => {
 						let png_img = image.to_png();
 						if let Ok(png_buffer) = png_img {
-							let bytes = png_buffer.get_bytes();
-							let ns_data = {
-								NSData::initWithBytes_length(
-									NSData::alloc(),
-									bytes.as_ptr() as *mut c_void,
-									bytes.len(),
-								)
-							};
-							let item = NSPasteboardItem::new();
-							item.setData_forType(&ns_data, NSPasteboardTypePNG);
+							let item = image_item(png_buffer.get_bytes(), image);
 							write_objects.push(ProtocolObject::from_id(item));
 						};
 					}
-					ClipboardContent::Files(files) => {
+					// Finder has no cut of files on the pasteboard
+					ClipboardContent::Files(files)
+					| ClipboardContent::FilesWithOperation(files, _) => {
 						let _ = self.set_files(files);
 					}
 					ClipboardContent::Other(format, buffer) => {
-						let ns_data = {
-							NSData::initWithBytes_length(
-								NSData::alloc(),
-								buffer.as_ptr() as *mut c_void,
-								buffer.len(),
-							)
-						};
+						let ns_data = bytes_to_ns_data(buffer);
 						self.pasteboard.declareTypes_owner(
 							&NSArray::from_vec(vec![NSString::from_str(format)]),
 							None,
@@ -195,10 +343,88 @@ impl ClipboardContext {
 			}
 			Ok(())
 		})?;
+		self.record_own_write();
 		Ok(())
 	}
 }
 
+// an item with the PNG data and a TIFF copy of the image
+fn image_item(png: &[u8], image: &RustImageData) -> Id<NSPasteboardItem> {
+	unsafe {
+		let item = NSPasteboardItem::new();
+		item.setData_forType(&bytes_to_ns_data(png), NSPasteboardTypePNG);
+		// many applications (Preview, Finder) prefer TIFF
+		if let Ok(tiff_buffer) = image.to_tiff() {
+			item.setData_forType(
+				&bytes_to_ns_data(tiff_buffer.get_bytes()),
+				NSPasteboardTypeTIFF,
+			);
+		}
+		item
+	}
+}
+
+fn bytes_to_ns_data(bytes: &[u8]) -> Id<NSData> {
+	unsafe {
+		NSData::initWithBytes_length(NSData::alloc(), bytes.as_ptr() as *mut c_void, bytes.len())
+	}
+}
+
+fn item_contents(item: &NSPasteboardItem) -> Vec<ClipboardContent> {
+	let mut contents = Vec::new();
+	let mut has_image = false;
+	for r#type in unsafe { item.types() }.iter() {
+		unsafe {
+			if r#type == NSPasteboardTypeString {
+				if let Some(string) = item.stringForType(r#type) {
+					contents.push(ClipboardContent::Text(string.to_string()));
+				}
+			} else if r#type == NSPasteboardTypeRTF {
+				if let Some(string) = item.stringForType(r#type) {
+					contents.push(ClipboardContent::Rtf(string.to_string()));
+				}
+			} else if r#type == NSPasteboardTypeHTML {
+				if let Some(string) = item.stringForType(r#type) {
+					contents.push(ClipboardContent::Html(string.to_string()));
+				}
+			} else if r#type == NSPasteboardTypePNG || r#type == NSPasteboardTypeTIFF {
+				// PNG and TIFF of the same item are the same picture, only keep the first one
+				if has_image {
+					continue;
+				}
+				if let Some(data) = item.dataForType(r#type) {
+					if let Ok(image) = RustImageData::from_bytes(data.bytes()) {
+						contents.push(ClipboardContent::Image(image));
+						has_image = true;
+					}
+				}
+			} else if r#type.to_string() == MARKDOWN_UTI {
+				if let Some(string) = item.stringForType(r#type) {
+					contents.push(ClipboardContent::Markdown(string.to_string()));
+				}
+			} else if r#type == NSPasteboardTypeURL {
+				if let Some(string) = item.stringForType(r#type) {
+					contents.push(ClipboardContent::Url(string.to_string()));
+				}
+			} else if r#type == NSPasteboardTypeFileURL {
+				let path = item
+					.stringForType(r#type)
+					.and_then(|url| NSURL::URLWithString(&url))
+					.and_then(|url| url.path());
+				if let Some(path) = path {
+					contents.push(ClipboardContent::Files(vec![path.to_string()]));
+				}
+			} else if let Some(data) = item.dataForType(r#type) {
+				contents.push(ClipboardContent::Other(
+					r#type.to_string(),
+					data.bytes().to_vec(),
+				));
+			}
+		}
+	}
+	contents
+}
+
 unsafe impl Send for ClipboardContext {}
 
 unsafe impl Sync for ClipboardContext {}
@@ -240,6 +466,15 @@ impl Clipboard for ClipboardContext {
 				let types = NSArray::arrayWithObject(NSFilenamesPboardType);
 				self.pasteboard.availableTypeFromArray(&types).is_some()
 			},
+			ContentFormat::Markdown => unsafe {
+				let types = NSArray::from_vec(vec![NSString::from_str(MARKDOWN_UTI)]);
+				self.pasteboard.availableTypeFromArray(&types).is_some()
+			},
+			ContentFormat::Url => unsafe {
+				let types = NSArray::arrayWithObject(NSPasteboardTypeURL);
+				self.pasteboard.availableTypeFromArray(&types).is_some()
+			},
+			ContentFormat::Metafile => false,
 			ContentFormat::Other(format) => unsafe {
 				let types = NSArray::from_vec(vec![NSString::from_str(&format)]);
 				self.pasteboard.availableTypeFromArray(&types).is_some()
@@ -247,8 +482,13 @@ impl Clipboard for ClipboardContext {
 		}
 	}
 
+	fn is_empty(&self) -> bool {
+		unsafe { self.pasteboard.types() }.map_or(true, |types| types.count() == 0)
+	}
+
 	fn clear(&self) -> Result<()> {
 		unsafe { self.pasteboard.clearContents() };
+		self.record_own_write();
 		Ok(())
 	}
 
@@ -271,13 +511,36 @@ impl Clipboard for ClipboardContext {
 		self.plain(unsafe { NSPasteboardTypeHTML })
 	}
 
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		autoreleasepool(|_| {
+			let data = unsafe { self.pasteboard.dataForType(NSPasteboardTypePNG) }
+				.or_else(|| unsafe { self.pasteboard.dataForType(NSPasteboardTypeTIFF) });
+			match data {
+				Some(data) => image_dimensions_from_bytes(data.bytes()),
+				// other image types are only readable through NSImage, which decodes them
+				None => {
+					let image = self.get_image()?;
+					let (width, height) = image.get_size();
+					Ok((width, height, ImageFormat::Tiff))
+				}
+			}
+		})
+	}
+
 	fn get_image(&self) -> Result<RustImageData> {
 		autoreleasepool(|_| {
 			let png_data = unsafe { self.pasteboard.dataForType(NSPasteboardTypePNG) };
 			if let Some(data) = png_data {
 				return RustImageData::from_bytes(data.bytes());
 			};
-			// if no png data, read NSImage;
+			// screenshots and Preview often only write TIFF, decode it without going through NSImage
+			let tiff_data = unsafe { self.pasteboard.dataForType(NSPasteboardTypeTIFF) };
+			if let Some(data) = tiff_data {
+				if let Ok(image) = RustImageData::from_bytes(data.bytes()) {
+					return Ok(image);
+				}
+			};
+			// other image types, or a TIFF the image crate cannot decode, read NSImage;
 			let ns_image =
 				unsafe { NSImage::initWithPasteboard(NSImage::alloc(), &self.pasteboard) };
 			if let Some(image) = ns_image {
@@ -351,6 +614,22 @@ impl Clipboard for ClipboardContext {
 								break;
 							}
 						}
+						ContentFormat::Markdown => {
+							if let Some(string) =
+								unsafe { item.stringForType(&NSString::from_str(MARKDOWN_UTI)) }
+							{
+								results.push(ClipboardContent::Markdown(string.to_string()));
+								break;
+							}
+						}
+						ContentFormat::Url => {
+							if let Some(string) = unsafe { item.stringForType(NSPasteboardTypeURL) }
+							{
+								results.push(ClipboardContent::Url(string.to_string()));
+								break;
+							}
+						}
+						ContentFormat::Metafile => break,
 						ContentFormat::Other(format_name) => {
 							if let Some(data) =
 								unsafe { item.dataForType(&NSString::from_str(format_name)) }
@@ -389,6 +668,30 @@ impl Clipboard for ClipboardContext {
 		self.write_to_clipboard(&[ClipboardContent::Image(image)], true)
 	}
 
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		let bytes = std::fs::read(path)?;
+		let image = RustImageData::from_bytes(&bytes)?;
+		if !is_png(&bytes) {
+			return self.set_image(image);
+		}
+		// the PNG data of the file is written as is
+		unsafe {
+			self.pasteboard.clearContents();
+		}
+		autoreleasepool(|_| unsafe {
+			let item = image_item(&bytes, &image);
+			if !self
+				.pasteboard
+				.writeObjects(&NSArray::from_vec(vec![ProtocolObject::from_id(item)]))
+			{
+				return Err("writeObjects failed");
+			}
+			Ok(())
+		})?;
+		self.record_own_write();
+		Ok(())
+	}
+
 	fn set_files(&self, files: Vec<String>) -> Result<()> {
 		if files.is_empty() {
 			return Err("file list is empty".into());
@@ -405,14 +708,63 @@ impl Clipboard for ClipboardContext {
 		}
 		self.write_to_clipboard(&contents, true)
 	}
-}
 
-pub struct WatcherShutdown {
-	stop_signal: Sender<()>,
-}
+	fn snapshot(&self) -> Result<ClipboardSnapshot> {
+		autoreleasepool(|_| {
+			let items = unsafe { self.pasteboard.pasteboardItems() }
+				.ok_or("NSPasteboard#pasteboardItems errored")?;
+			let items = items
+				.iter()
+				.map(|item| {
+					let mut formats = Vec::new();
+					for r#type in unsafe { item.types() }.iter() {
+						if let Some(data) = unsafe { item.dataForType(r#type) } {
+							let info = FormatInfo {
+								name: Some(r#type.to_string()),
+								id: 0,
+							};
+							formats.push((info, data.bytes().to_vec()));
+						}
+					}
+					formats
+				})
+				.collect();
+			Ok(ClipboardSnapshot { items })
+		})
+	}
+
+	fn restore(&self, snapshot: ClipboardSnapshot) -> Result<()> {
+		if snapshot.is_empty() {
+			return self.clear();
+		}
+		unsafe { self.pasteboard.clearContents() };
+		autoreleasepool(|_| unsafe {
+			let mut write_objects: Vec<Id<ProtocolObject<dyn NSPasteboardWriting + 'static>>> =
+				vec![];
+			// keep every NSPasteboardItem separate, e.g. one item per copied file
+			for formats in snapshot.items {
+				let item = NSPasteboardItem::new();
+				for (info, data) in formats {
+					if let Some(name) = info.name {
+						item.setData_forType(&bytes_to_ns_data(&data), &NSString::from_str(&name));
+					}
+				}
+				write_objects.push(ProtocolObject::from_id(item));
+			}
+			if !self
+				.pasteboard
+				.writeObjects(&NSArray::from_vec(write_objects))
+			{
+				return Err("writeObjects failed");
+			}
+			Ok(())
+		})?;
+		self.record_own_write();
+		Ok(())
+	}
 
-impl Drop for WatcherShutdown {
-	fn drop(&mut self) {
-		let _ = self.stop_signal.send(());
+	fn get_sequence_number(&self) -> Result<u64> {
+		let change_count = unsafe { self.pasteboard.changeCount() };
+		Ok(change_count as u64)
 	}
 }
@@ -0,0 +1,450 @@
+use crate::common::{
+	dispatch_to_handlers, image_dimensions_from_bytes, insert_named_handler, is_png,
+	notify_subscribers, take_handler_at, take_named_handler, ChangeSource, EventThrottle,
+	FileOperation, HandlerEntry, Result, RustImage, RustImageData, WatcherShutdown,
+};
+use crate::{
+	Clipboard, ClipboardContent, ClipboardEvent, ClipboardHandler, ClipboardWatcher, ContentFormat,
+};
+use image::ImageFormat;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+const TEXT: &str = "text/plain";
+const RTF: &str = "text/rtf";
+const HTML: &str = "text/html";
+const PNG: &str = "image/png";
+const FILE_LIST: &str = "text/uri-list";
+const MARKDOWN: &str = "text/markdown";
+const URL: &str = "text/x-moz-url";
+// 剪切文件时写入 "1"，与 KDE 的做法相同
+const CUT_SELECTION: &str = "application/x-kde-cutselection";
+
+// 进程内共享的剪切板内容，按写入顺序保存 (格式名, 数据)
+static STORE: Mutex<Vec<(String, Vec<u8>)>> = Mutex::new(Vec::new());
+static SEQUENCE_NUMBER: AtomicU64 = AtomicU64::new(0);
+// 正在监听的监听器，写入剪切板时会同步通知它们
+static WATCHERS: Mutex<Vec<WatcherEntry>> = Mutex::new(Vec::new());
+
+// 监听器处理完事件后会回复确认
+type ChangeNotify = (ClipboardEvent, Sender<()>);
+
+struct WatcherEntry {
+	id: u64,
+	thread: ThreadId,
+	notify: Sender<ChangeNotify>,
+}
+
+static NEXT_WATCHER_ID: AtomicU64 = AtomicU64::new(0);
+
+// 等待监听器处理变化的最长时间，防止处理器卡住写入方
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// zh: 保存在进程内存中的剪切板，同一进程内的所有实例共享同样的内容，不依赖任何显示服务，用于测试。
+/// 在 Linux 上设置环境变量 `CLIPBOARD_RS_BACKEND=memory` 后 [`crate::ClipboardContext::new`] 也会使用它
+/// en: A clipboard kept in the process memory, all instances in the same process share the same content, no display server is required, meant for tests.
+/// On Linux [`crate::ClipboardContext::new`] uses it as well when the environment variable `CLIPBOARD_RS_BACKEND=memory` is set
+pub struct ClipboardContext {}
+
+/// zh: 监听 [`ClipboardContext`] 的变化，写入时会同步通知处理器
+/// en: Watches the changes of the [`ClipboardContext`], handlers are notified synchronously when it is written
+pub struct ClipboardWatcherContext<T: ClipboardHandler> {
+	handlers: Vec<HandlerEntry<T>>,
+	stop_signal: Sender<()>,
+	stop_receiver: Receiver<()>,
+	// start_watch 退出后发送，WatcherShutdown::wait_for_completion 等待它
+	done_signal: Sender<()>,
+	done_receiver: Arc<Mutex<Receiver<()>>>,
+	running: bool,
+	// 旧的行为：WatcherShutdown 被丢弃时停止监听
+	stop_on_drop: bool,
+	throttle: EventThrottle,
+	subscribers: Vec<Sender<ClipboardEvent>>,
+	// 传给处理器用来读取剪切板内容
+	clipboard: ClipboardContext,
+}
+
+unsafe impl<T: ClipboardHandler> Send for ClipboardWatcherContext<T> {}
+
+impl ClipboardContext {
+	pub fn new() -> Result<ClipboardContext> {
+		Ok(ClipboardContext {})
+	}
+
+	fn read(&self, format: &str) -> Result<Vec<u8>> {
+		let store = STORE.lock().map_err(|_| "Failed to lock clipboard store")?;
+		store
+			.iter()
+			.find(|(name, _)| name == format)
+			.map(|(_, data)| data.clone())
+			.ok_or_else(|| format!("no data of format {}", format).into())
+	}
+
+	fn read_string(&self, format: &str) -> Result<String> {
+		Ok(String::from_utf8(self.read(format)?)?)
+	}
+
+	fn write(&self, data: Vec<(String, Vec<u8>)>) -> Result<()> {
+		{
+			let mut store = STORE.lock().map_err(|_| "Failed to lock clipboard store")?;
+			store.clear();
+			for (format, buffer) in data {
+				// a format written twice keeps the position of its first write
+				match store.iter_mut().find(|(name, _)| *name == format) {
+					Some(entry) => entry.1 = buffer,
+					None => store.push((format, buffer)),
+				}
+			}
+		}
+		notify_watchers();
+		Ok(())
+	}
+}
+
+fn encode(content: ClipboardContent) -> Result<Vec<(String, Vec<u8>)>> {
+	if let ClipboardContent::FilesWithOperation(files, operation) = content {
+		let cut = match operation {
+			FileOperation::Copy => b"0".to_vec(),
+			FileOperation::Cut => b"1".to_vec(),
+		};
+		return Ok(vec![
+			(FILE_LIST.to_owned(), files.join("\n").into_bytes()),
+			(CUT_SELECTION.to_owned(), cut),
+		]);
+	}
+	Ok(vec![match content {
+		ClipboardContent::Text(text) => (TEXT.to_owned(), text.into_bytes()),
+		ClipboardContent::Rtf(rtf) => (RTF.to_owned(), rtf.into_bytes()),
+		ClipboardContent::Html(html) => (HTML.to_owned(), html.into_bytes()),
+		ClipboardContent::Image(image) => (PNG.to_owned(), image.to_png()?.get_bytes().to_vec()),
+		ClipboardContent::Files(files) | ClipboardContent::FilesWithOperation(files, _) => {
+			(FILE_LIST.to_owned(), files.join("\n").into_bytes())
+		}
+		ClipboardContent::Markdown(markdown) => (MARKDOWN.to_owned(), markdown.into_bytes()),
+		ClipboardContent::Url(url) => (URL.to_owned(), url.into_bytes()),
+		ClipboardContent::Other(format, buffer) => (format, buffer),
+	}])
+}
+
+fn format_name(format: &ContentFormat) -> Option<&str> {
+	match format {
+		ContentFormat::Text => Some(TEXT),
+		ContentFormat::Rtf => Some(RTF),
+		ContentFormat::Html => Some(HTML),
+		ContentFormat::Image => Some(PNG),
+		ContentFormat::Files => Some(FILE_LIST),
+		ContentFormat::Markdown => Some(MARKDOWN),
+		ContentFormat::Url => Some(URL),
+		ContentFormat::Metafile => None,
+		ContentFormat::Other(format) => Some(format),
+	}
+}
+
+// notify the running watchers of a change, waiting for them to run their handlers
+fn notify_watchers() {
+	SEQUENCE_NUMBER.fetch_add(1, Ordering::SeqCst);
+	let mut event = ClipboardEvent::new(None);
+	// only this process can write to the memory clipboard
+	event.source = ChangeSource::OwnWrite;
+	let current = thread::current().id();
+	let mut acks = Vec::new();
+	if let Ok(watchers) = WATCHERS.lock() {
+		for watcher in watchers.iter() {
+			let (ack_tx, ack_rx) = mpsc::channel();
+			if watcher.notify.send((event.clone(), ack_tx)).is_err() {
+				continue;
+			}
+			// a handler writing to the clipboard must not wait for itself
+			if watcher.thread != current {
+				acks.push(ack_rx);
+			}
+		}
+	}
+	for ack in acks {
+		let _ = ack.recv_timeout(NOTIFY_TIMEOUT);
+	}
+}
+
+impl Clipboard for ClipboardContext {
+	fn available_formats(&self) -> Result<Vec<String>> {
+		let store = STORE.lock().map_err(|_| "Failed to lock clipboard store")?;
+		Ok(store.iter().map(|(name, _)| name.clone()).collect())
+	}
+
+	fn has(&self, format: ContentFormat) -> bool {
+		let format = match format_name(&format) {
+			Some(format) => format,
+			None => return false,
+		};
+		STORE
+			.lock()
+			.map(|store| store.iter().any(|(name, _)| name == format))
+			.unwrap_or(false)
+	}
+
+	fn clear(&self) -> Result<()> {
+		self.write(Vec::new())
+	}
+
+	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		self.read(format)
+	}
+
+	fn get_text(&self) -> Result<String> {
+		self.read_string(TEXT)
+	}
+
+	fn get_rich_text(&self) -> Result<String> {
+		self.read_string(RTF)
+	}
+
+	fn get_html(&self) -> Result<String> {
+		self.read_string(HTML)
+	}
+
+	fn get_text_bytes(&self) -> Result<Vec<u8>> {
+		self.read(TEXT)
+	}
+
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		self.read(RTF)
+	}
+
+	fn get_html_bytes(&self) -> Result<Vec<u8>> {
+		self.read(HTML)
+	}
+
+	fn get_image(&self) -> Result<RustImageData> {
+		RustImageData::from_bytes(&self.read(PNG)?)
+	}
+
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		image_dimensions_from_bytes(&self.read(PNG)?)
+	}
+
+	fn get_files(&self) -> Result<Vec<String>> {
+		let files = self.read_string(FILE_LIST)?;
+		Ok(files.lines().map(|file| file.to_owned()).collect())
+	}
+
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		let files = self.get_files()?;
+		let operation = match self.read(CUT_SELECTION) {
+			Ok(cut) if cut == b"1" => FileOperation::Cut,
+			_ => FileOperation::Copy,
+		};
+		Ok((operation, files))
+	}
+
+	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
+		let mut contents = Vec::new();
+		for format in formats {
+			let content = match format {
+				ContentFormat::Text => self.get_text().map(ClipboardContent::Text),
+				ContentFormat::Rtf => self.get_rich_text().map(ClipboardContent::Rtf),
+				ContentFormat::Html => self.get_html().map(ClipboardContent::Html),
+				ContentFormat::Image => self.get_image().map(ClipboardContent::Image),
+				ContentFormat::Files => self.get_files().map(ClipboardContent::Files),
+				ContentFormat::Markdown => {
+					self.read_string(MARKDOWN).map(ClipboardContent::Markdown)
+				}
+				ContentFormat::Url => self.read_string(URL).map(ClipboardContent::Url),
+				ContentFormat::Metafile => continue,
+				ContentFormat::Other(format) => self
+					.get_buffer(format)
+					.map(|buffer| ClipboardContent::Other(format.clone(), buffer)),
+			};
+			if let Ok(content) = content {
+				contents.push(content);
+			}
+		}
+		Ok(contents)
+	}
+
+	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
+		self.write(vec![(format.to_owned(), buffer)])
+	}
+
+	fn set_text(&self, text: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Text(text)])
+	}
+
+	fn set_rich_text(&self, text: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Rtf(text)])
+	}
+
+	fn set_html(&self, html: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Html(html)])
+	}
+
+	fn set_image(&self, image: RustImageData) -> Result<()> {
+		self.set(vec![ClipboardContent::Image(image)])
+	}
+
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		let bytes = std::fs::read(path)?;
+		if is_png(&bytes) {
+			return self.set_buffer(PNG, bytes);
+		}
+		self.set_image(RustImageData::from_bytes(&bytes)?)
+	}
+
+	fn set_files(&self, files: Vec<String>) -> Result<()> {
+		self.set(vec![ClipboardContent::Files(files)])
+	}
+
+	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+		let data = contents
+			.into_iter()
+			.map(encode)
+			.collect::<Result<Vec<_>>>()?;
+		self.write(data.into_iter().flatten().collect())
+	}
+
+	fn get_sequence_number(&self) -> Result<u64> {
+		Ok(SEQUENCE_NUMBER.load(Ordering::SeqCst))
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
+	pub fn new() -> Result<Self> {
+		Self::new_with_clipboard(ClipboardContext::new()?)
+	}
+
+	/// zh: 使用指定的剪切板上下文创建监听器，处理器会通过它读取剪切板内容
+	/// en: Create a watcher with the given clipboard context, handlers read the clipboard through it
+	pub fn new_with_clipboard(clipboard: ClipboardContext) -> Result<Self> {
+		let (tx, rx) = mpsc::channel();
+		let (done_tx, done_rx) = mpsc::channel();
+		Ok(Self {
+			handlers: Vec::new(),
+			stop_signal: tx,
+			stop_receiver: rx,
+			done_signal: done_tx,
+			done_receiver: Arc::new(Mutex::new(done_rx)),
+			running: false,
+			stop_on_drop: false,
+			throttle: EventThrottle::default(),
+			subscribers: Vec::new(),
+			clipboard,
+		})
+	}
+
+	/// zh: 设置每秒最多通知处理器的次数，超出的变化会被合并，但一连串变化中的最后一次总会被通知，小于等于 0 表示不限制
+	/// en: Set the maximum number of events per second dispatched to handlers, changes beyond the rate are coalesced but the last change of a burst is always delivered, a value <= 0 means no limit
+	pub fn set_max_event_rate(&mut self, max_events_per_second: f32) -> &mut Self {
+		self.throttle.set_max_event_rate(max_events_per_second);
+		self
+	}
+
+	/// zh: 设置丢弃 [`WatcherShutdown`] 时是否停止监听（旧版本的行为），默认只有调用 [`WatcherShutdown::stop`] 才会停止，只影响之后获得的停止通道
+	/// en: Set whether dropping a [`WatcherShutdown`] stops the watcher (the behavior of older versions), by default only [`WatcherShutdown::stop`] stops it, only affects shutdown channels obtained afterwards
+	pub fn set_stop_on_drop(&mut self, stop_on_drop: bool) -> &mut Self {
+		self.stop_on_drop = stop_on_drop;
+		self
+	}
+
+	/// zh: 订阅剪切板变化事件，返回一个接收变化事件的通道，每次调用都会创建一个新的独立通道
+	/// en: Subscribe to clipboard change events, returns a receiver of the change events, every call creates a new independent channel
+	pub fn subscribe(&mut self) -> Receiver<ClipboardEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.push(tx);
+		rx
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
+	fn add_handler(&mut self, handler: T) -> &mut Self {
+		self.handlers.push(HandlerEntry::new(handler, None));
+		self
+	}
+
+	fn add_filtered_handler(&mut self, handler: T, formats: Vec<ContentFormat>) -> &mut Self {
+		self.handlers
+			.push(HandlerEntry::new(handler, Some(formats)));
+		self
+	}
+
+	fn add_named_handler(&mut self, name: &str, handler: T) -> &mut Self {
+		insert_named_handler(&mut self.handlers, name, handler);
+		self
+	}
+
+	fn remove_handler(&mut self, index: usize) -> Option<T> {
+		take_handler_at(&mut self.handlers, index)
+	}
+
+	fn remove_named_handler(&mut self, name: &str) -> Option<T> {
+		take_named_handler(&mut self.handlers, name)
+	}
+
+	fn start_watch(&mut self) {
+		if self.running {
+			println!("already start watch!");
+			return;
+		}
+		if self.handlers.is_empty() && self.subscribers.is_empty() {
+			println!("no handler, no need to start watch!");
+			return;
+		}
+		self.running = true;
+		// drain the stop signals left over from a previous session (e.g. `WatcherShutdown::stop` called while the watcher was not running),
+		// so that the watcher can be restarted after it has been stopped
+		while self.stop_receiver.try_recv().is_ok() {}
+
+		let (notify_tx, notify_rx) = mpsc::channel();
+		let id = NEXT_WATCHER_ID.fetch_add(1, Ordering::SeqCst);
+		if let Ok(mut watchers) = WATCHERS.lock() {
+			watchers.push(WatcherEntry {
+				id,
+				thread: thread::current().id(),
+				notify: notify_tx,
+			});
+		}
+
+		let mut last_event = None;
+		let mut acks = Vec::new();
+		loop {
+			if self.stop_receiver.try_recv().is_ok() {
+				break;
+			}
+			let changed = match notify_rx.recv_timeout(Duration::from_millis(50)) {
+				Ok((event, ack)) => {
+					last_event = Some(event);
+					acks.push(ack);
+					true
+				}
+				Err(_) => false,
+			};
+			if self.throttle.should_dispatch(changed, Instant::now()) {
+				if let Some(event) = last_event.take() {
+					dispatch_to_handlers(&mut self.handlers, &self.clipboard, &event, |format| {
+						self.clipboard.has(format.clone())
+					});
+					notify_subscribers(&mut self.subscribers, &event);
+				}
+			}
+			// changes coalesced by the throttle are acknowledged as well
+			for ack in acks.drain(..) {
+				let _ = ack.send(());
+			}
+		}
+
+		if let Ok(mut watchers) = WATCHERS.lock() {
+			watchers.retain(|watcher| watcher.id != id);
+		}
+		self.running = false;
+		let _ = self.done_signal.send(());
+	}
+
+	fn get_shutdown_channel(&self) -> WatcherShutdown {
+		WatcherShutdown::new(
+			self.stop_signal.clone(),
+			self.done_receiver.clone(),
+			self.stop_on_drop,
+		)
+	}
+}
@@ -1,11 +1,20 @@
+mod memory;
+// 指定后端的环境变量，优先级低于 ClipboardContextBuilder::force_backend
+// unused on platforms without a system clipboard backend
+#[allow(dead_code)]
+const BACKEND_ENV: &str = "CLIPBOARD_RS_BACKEND";
+pub use memory::{
+	ClipboardContext as MemoryClipboardContext,
+	ClipboardWatcherContext as MemoryClipboardWatcherContext,
+};
 #[cfg(target_os = "macos")]
 mod macos;
-#[cfg(target_os = "macos")]
-pub use macos::{ClipboardContext, ClipboardWatcherContext, WatcherShutdown};
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+mod system;
 #[cfg(target_os = "windows")]
 mod win;
-#[cfg(target_os = "windows")]
-pub use win::{ClipboardContext, ClipboardWatcherContext, WatcherShutdown};
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+pub use system::{ClipboardContext, ClipboardWatcherContext};
 #[cfg(all(
 	unix,
 	not(any(
@@ -25,6 +34,42 @@ mod x11;
 		target_os = "emscripten"
 	))
 ))]
-pub use x11::{
-	ClipboardContext, ClipboardContextX11Options, ClipboardWatcherContext, WatcherShutdown,
+pub use x11::{ClipboardContextX11Options, ReadProgressCallback};
+#[cfg(all(
+	unix,
+	not(any(
+		target_os = "macos",
+		target_os = "ios",
+		target_os = "android",
+		target_os = "emscripten"
+	))
+))]
+mod linux;
+#[cfg(all(
+	unix,
+	not(any(
+		target_os = "macos",
+		target_os = "ios",
+		target_os = "android",
+		target_os = "emscripten"
+	)),
+	feature = "osc52"
+))]
+mod osc52;
+#[cfg(all(
+	unix,
+	not(any(
+		target_os = "macos",
+		target_os = "ios",
+		target_os = "android",
+		target_os = "emscripten"
+	))
+))]
+pub use linux::{ClipboardContext, ClipboardWatcherContext};
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+mod wayland;
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+pub use wayland::{
+	ClipboardContext as WaylandClipboardContext,
+	ClipboardWatcherContext as WaylandClipboardWatcherContext,
 };
@@ -0,0 +1,339 @@
+use crate::common::{format_from_name, Result, RustImageData, XSelection};
+use crate::{Clipboard, ClipboardContent, ClipboardContextBuilder, ClipboardError, ContentFormat};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+const TEXT: &str = "text/plain;charset=utf-8";
+
+// 很多终端会截断大约 100 KB 以上的序列，base64 编码后超过该长度的内容不写入
+const MAX_PAYLOAD_SIZE: usize = 100_000;
+
+// tmux 透传序列每段的长度
+const TMUX_CHUNK_SIZE: usize = 4096;
+
+// screen 限制 DCS 序列的长度
+const SCREEN_CHUNK_SIZE: usize = 768;
+
+/// zh: 通过 OSC 52 转义序列读写终端的剪切板，适用于通过 SSH 运行、没有显示服务的命令行程序。
+/// 只支持纯文本，读取需要终端允许查询剪切板，否则会超时；在 tmux 和 screen 中会通过透传序列分段发送
+/// en: Reads and writes the clipboard of the terminal with the OSC 52 escape sequence, for command line programs run over SSH without a display server.
+/// Only plain text is supported, reads need the terminal to allow querying the clipboard, they time out otherwise; inside tmux and screen the sequence is sent in chunks through their passthrough
+pub struct ClipboardContext {
+	// 选区对应的 OSC 52 参数
+	selection: char,
+	read_timeout: Option<Duration>,
+	// 创建时根据环境变量确定
+	multiplexer: Multiplexer,
+}
+
+// 需要透传序列的终端复用器
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Multiplexer {
+	None,
+	Tmux,
+	Screen,
+}
+
+impl ClipboardContext {
+	pub(crate) fn from_builder(builder: &ClipboardContextBuilder) -> Result<Self> {
+		// fail now rather than on the first write when there is no terminal at all
+		open_tty()?;
+		let is_set = |name| std::env::var_os(name).map_or(false, |value| !value.is_empty());
+		let multiplexer = if is_set("TMUX") {
+			Multiplexer::Tmux
+		} else if is_set("STY") {
+			Multiplexer::Screen
+		} else {
+			Multiplexer::None
+		};
+		Ok(Self {
+			selection: match builder.selection {
+				XSelection::Clipboard => 'c',
+				XSelection::Primary => 'p',
+				XSelection::Secondary => 's',
+			},
+			read_timeout: builder.read_timeout,
+			multiplexer,
+		})
+	}
+
+	fn send(&self, tty: &mut File, payload: &str) -> Result<()> {
+		let sequence = format!("\x1b]52;{};{}\x07", self.selection, payload);
+		tty.write_all(&wrap_for_multiplexer(sequence.as_bytes(), self.multiplexer))?;
+		tty.flush()?;
+		Ok(())
+	}
+
+	fn write_text(&self, text: &str) -> Result<()> {
+		let payload = BASE64_STANDARD.encode(text);
+		if payload.len() > MAX_PAYLOAD_SIZE {
+			return Err(ClipboardError::Other("payload too large".to_string()).into());
+		}
+		self.send(&mut open_tty()?, &payload)
+	}
+
+	// the terminal only answers the query in raw mode, otherwise the answer waits for a newline
+	fn read_text(&self) -> Result<String> {
+		let mut tty = open_tty()?;
+		let original = tcgetattr(&tty)?;
+		let mut raw = original.clone();
+		cfmakeraw(&mut raw);
+		tcsetattr(&tty, SetArg::TCSANOW, &raw)?;
+		let res = self.query(&mut tty);
+		tcsetattr(&tty, SetArg::TCSANOW, &original)?;
+		res
+	}
+
+	fn query(&self, tty: &mut File) -> Result<String> {
+		self.send(tty, "?")?;
+		let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+		let mut response = Vec::new();
+		let mut buf = [0u8; 4096];
+		loop {
+			if let Some(text) = parse_response(&response) {
+				return text;
+			}
+			let timeout_ms = match deadline {
+				Some(deadline) => {
+					let remaining = deadline.saturating_duration_since(Instant::now());
+					if remaining.is_zero() {
+						return Err(ClipboardError::Timeout.into());
+					}
+					remaining.as_millis().clamp(1, i32::MAX as u128) as i32
+				}
+				None => -1,
+			};
+			let readable = {
+				let mut fds = [PollFd::new(&*tty, PollFlags::POLLIN)];
+				match poll(&mut fds, timeout_ms) {
+					Ok(count) => count > 0,
+					Err(Errno::EINTR) => false,
+					Err(e) => return Err(format!("poll error: {}", e).into()),
+				}
+			};
+			if readable {
+				let count = tty.read(&mut buf)?;
+				if count == 0 {
+					return Err("The terminal was closed while waiting for the answer".into());
+				}
+				response.extend_from_slice(&buf[..count]);
+			}
+		}
+	}
+}
+
+fn open_tty() -> Result<File> {
+	OpenOptions::new()
+		.read(true)
+		.write(true)
+		.open("/dev/tty")
+		.map_err(|e| {
+			ClipboardError::PlatformError {
+				code: e.raw_os_error().unwrap_or(0),
+				message: format!("No controlling terminal: {}", e),
+			}
+			.into()
+		})
+}
+
+fn unsupported(what: &str) -> Box<dyn Error + Send + Sync> {
+	format!("{} is not supported by the OSC 52 backend", what).into()
+}
+
+// tmux and screen only pass a sequence through to the terminal when it is wrapped in a DCS sequence
+fn wrap_for_multiplexer(sequence: &[u8], multiplexer: Multiplexer) -> Vec<u8> {
+	let mut wrapped = Vec::new();
+	match multiplexer {
+		Multiplexer::None => wrapped.extend_from_slice(sequence),
+		Multiplexer::Tmux => {
+			for chunk in sequence.chunks(TMUX_CHUNK_SIZE) {
+				wrapped.extend_from_slice(b"\x1bPtmux;");
+				// tmux wants the escape characters of the wrapped sequence doubled
+				for byte in chunk {
+					if *byte == 0x1b {
+						wrapped.push(0x1b);
+					}
+					wrapped.push(*byte);
+				}
+				wrapped.extend_from_slice(b"\x1b\\");
+			}
+		}
+		Multiplexer::Screen => {
+			for chunk in sequence.chunks(SCREEN_CHUNK_SIZE) {
+				wrapped.extend_from_slice(b"\x1bP");
+				wrapped.extend_from_slice(chunk);
+				wrapped.extend_from_slice(b"\x1b\\");
+			}
+		}
+	}
+	wrapped
+}
+
+// the answer is `ESC ] 52 ; <selection> ; <base64> BEL` (or ST instead of BEL), None while it is incomplete
+fn parse_response(response: &[u8]) -> Option<Result<String>> {
+	const START: &[u8] = b"\x1b]52;";
+	let start = response
+		.windows(START.len())
+		.position(|window| window == START)?
+		+ START.len();
+	let body = &response[start..];
+	let end = body.iter().enumerate().position(|(i, byte)| {
+		*byte == 0x07 || (*byte == 0x1b && body.get(i + 1) == Some(&b'\\'))
+	})?;
+	let body = &body[..end];
+	let data = match body.iter().position(|byte| *byte == b';') {
+		Some(separator) => &body[separator + 1..],
+		None => return Some(Err("Invalid OSC 52 response".into())),
+	};
+	Some(
+		BASE64_STANDARD
+			.decode(data)
+			.map_err(|e| e.into())
+			.and_then(|bytes| String::from_utf8(bytes).map_err(|e| e.into())),
+	)
+}
+
+impl Clipboard for ClipboardContext {
+	fn available_formats(&self) -> Result<Vec<String>> {
+		if self.read_text()?.is_empty() {
+			return Ok(Vec::new());
+		}
+		Ok(vec![TEXT.to_string()])
+	}
+
+	fn has(&self, format: ContentFormat) -> bool {
+		format == ContentFormat::Text && self.read_text().map_or(false, |text| !text.is_empty())
+	}
+
+	// terminals clear the selection when the data is not valid base64
+	fn clear(&self) -> Result<()> {
+		self.send(&mut open_tty()?, "!")
+	}
+
+	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		if format_from_name(format) != ContentFormat::Text {
+			return Err(unsupported(format));
+		}
+		self.read_text().map(String::into_bytes)
+	}
+
+	fn get_text(&self) -> Result<String> {
+		self.read_text()
+	}
+
+	fn get_rich_text(&self) -> Result<String> {
+		Err(unsupported("Rich text"))
+	}
+
+	fn get_html(&self) -> Result<String> {
+		Err(unsupported("Html"))
+	}
+
+	fn get_image(&self) -> Result<RustImageData> {
+		Err(unsupported("Image"))
+	}
+
+	fn get_files(&self) -> Result<Vec<String>> {
+		Err(unsupported("Files"))
+	}
+
+	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
+		if !formats.contains(&ContentFormat::Text) {
+			return Ok(Vec::new());
+		}
+		let text = self.read_text()?;
+		if text.is_empty() {
+			return Ok(Vec::new());
+		}
+		Ok(vec![ClipboardContent::Text(text)])
+	}
+
+	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
+		if format_from_name(format) != ContentFormat::Text {
+			return Err(unsupported(format));
+		}
+		self.write_text(&String::from_utf8(buffer)?)
+	}
+
+	fn set_text(&self, text: String) -> Result<()> {
+		self.write_text(&text)
+	}
+
+	fn set_rich_text(&self, _text: String) -> Result<()> {
+		Err(unsupported("Rich text"))
+	}
+
+	fn set_html(&self, _html: String) -> Result<()> {
+		Err(unsupported("Html"))
+	}
+
+	fn set_image(&self, _image: RustImageData) -> Result<()> {
+		Err(unsupported("Image"))
+	}
+
+	fn set_files(&self, _files: Vec<String>) -> Result<()> {
+		Err(unsupported("Files"))
+	}
+
+	// only the plain text of the contents can be written
+	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+		match contents.iter().find_map(|content| match content {
+			ClipboardContent::Text(text) => Some(text),
+			_ => None,
+		}) {
+			Some(text) => self.write_text(text),
+			None => Err(unsupported("Content without plain text")),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_wrap_for_tmux() {
+		let sequence = b"\x1b]52;c;aGk=\x07";
+		assert_eq!(
+			wrap_for_multiplexer(sequence, Multiplexer::None),
+			sequence.to_vec()
+		);
+		assert_eq!(
+			wrap_for_multiplexer(sequence, Multiplexer::Tmux),
+			b"\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\".to_vec()
+		);
+
+		let long = vec![b'a'; TMUX_CHUNK_SIZE * 2 + 1];
+		let wrapped = wrap_for_multiplexer(&long, Multiplexer::Tmux);
+		assert_eq!(
+			wrapped
+				.windows(7)
+				.filter(|window| window == b"\x1bPtmux;")
+				.count(),
+			3
+		);
+	}
+
+	#[test]
+	fn test_parse_response() {
+		assert!(parse_response(b"\x1b]52;c;aGVs").is_none());
+		assert_eq!(
+			parse_response(b"\x1b]52;c;aGVsbG8=\x07").unwrap().unwrap(),
+			"hello"
+		);
+		assert_eq!(
+			parse_response(b"noise\x1b]52;p;aGVsbG8=\x1b\\")
+				.unwrap()
+				.unwrap(),
+			"hello"
+		);
+		assert_eq!(parse_response(b"\x1b]52;c;\x07").unwrap().unwrap(), "");
+		assert!(parse_response(b"\x1b]52;c;!!!\x07").unwrap().is_err());
+	}
+}
@@ -0,0 +1,352 @@
+#[cfg(target_os = "macos")]
+use super::macos as native;
+use super::memory;
+#[cfg(target_os = "windows")]
+use super::win as native;
+use super::BACKEND_ENV;
+use crate::common::{
+	ClipboardOwner, ClipboardSnapshot, FileOperation, FormatInfo, Result, WatcherShutdown,
+};
+use crate::{
+	Clipboard, ClipboardContent, ClipboardContextBuilder, ClipboardEvent, ClipboardHandler,
+	ClipboardWatcher, ContentFormat, RustImageData,
+};
+use image::ImageFormat;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+#[cfg(target_os = "windows")]
+use std::time::Duration;
+
+/// zh: 剪切板上下文，默认使用系统剪切板，环境变量 `CLIPBOARD_RS_BACKEND` 为 `memory` 时使用进程内存中的剪切板，用于没有显示服务的测试环境
+/// en: The clipboard context, the system clipboard is used by default, the in-process memory clipboard is used when the environment variable `CLIPBOARD_RS_BACKEND` is `memory`, for test environments without a display server
+pub struct ClipboardContext {
+	inner: SystemClipboardContext,
+}
+
+enum SystemClipboardContext {
+	Native(native::ClipboardContext),
+	Memory(memory::ClipboardContext),
+}
+
+pub struct ClipboardWatcherContext<T: ClipboardHandler> {
+	inner: SystemClipboardWatcherContext<T>,
+}
+
+enum SystemClipboardWatcherContext<T: ClipboardHandler> {
+	Native(native::ClipboardWatcherContext<T>),
+	Memory(memory::ClipboardWatcherContext<T>),
+}
+
+// call the same method on whichever backend is in use
+macro_rules! dispatch {
+	($inner:expr, $backend:ident => $body:expr) => {
+		match $inner {
+			SystemClipboardContext::Native($backend) => $body,
+			SystemClipboardContext::Memory($backend) => $body,
+		}
+	};
+}
+
+macro_rules! dispatch_watcher {
+	($inner:expr, $backend:ident => $body:expr) => {
+		match $inner {
+			SystemClipboardWatcherContext::Native($backend) => $body,
+			SystemClipboardWatcherContext::Memory($backend) => $body,
+		}
+	};
+}
+
+// whether CLIPBOARD_RS_BACKEND asks for the memory backend, the system clipboard is the only other one here
+fn memory_requested() -> Result<bool> {
+	match std::env::var(BACKEND_ENV) {
+		Ok(value) if value.eq_ignore_ascii_case("memory") => Ok(true),
+		Ok(value) if !value.is_empty() => Err(format!(
+			"Unknown {} value {:?}, only memory is supported on this platform",
+			BACKEND_ENV, value
+		)
+		.into()),
+		_ => Ok(false),
+	}
+}
+
+impl ClipboardContext {
+	pub fn new() -> Result<Self> {
+		Self::from_builder(&ClipboardContextBuilder::default())
+	}
+
+	pub(crate) fn from_builder(builder: &ClipboardContextBuilder) -> Result<Self> {
+		let inner = if memory_requested()? {
+			SystemClipboardContext::Memory(memory::ClipboardContext::new()?)
+		} else {
+			SystemClipboardContext::Native(native::ClipboardContext::from_builder(builder)?)
+		};
+		Ok(Self { inner })
+	}
+
+	/// zh: 获得当前剪切板内容的所有者程序，无法确定时返回 None，内存剪切板总是返回 None。
+	/// macOS 的 NSPasteboard 不记录写入者，返回的是第一次查询该次变化时的前台程序，因此应在变化后尽快调用（例如在 [`ClipboardHandler`] 中）
+	/// en: Get the program owning the current clipboard content, None if it cannot be determined, always None for the memory clipboard.
+	/// NSPasteboard on macOS does not record the writer, the frontmost application at the first query for the change is returned instead, so call it soon after the change (e.g. in a [`ClipboardHandler`])
+	pub fn get_owner(&self) -> Option<ClipboardOwner> {
+		match &self.inner {
+			SystemClipboardContext::Native(ctx) => ctx.get_owner(),
+			SystemClipboardContext::Memory(_) => None,
+		}
+	}
+
+	/// zh: 获得当前剪切板内容的所有者程序名称，见 [`ClipboardContext::get_owner`]
+	/// en: Get the name of the program owning the current clipboard content, see [`ClipboardContext::get_owner`]
+	pub fn get_owner_app_name(&self) -> Option<String> {
+		self.get_owner()?.app_name
+	}
+
+	/// zh: 按剪切板中的每个 NSPasteboardItem 分别读取内容，返回的每一项对应一个 item 中可识别的所有内容，内存剪切板不支持
+	/// en: Read the content of every NSPasteboardItem on the clipboard separately, each entry of the result holds all recognized contents of one item, the memory clipboard does not support it
+	#[cfg(target_os = "macos")]
+	pub fn get_items(&self) -> Result<Vec<Vec<ClipboardContent>>> {
+		match &self.inner {
+			SystemClipboardContext::Native(ctx) => ctx.get_items(),
+			SystemClipboardContext::Memory(_) => {
+				Err("get_items is not supported by the memory backend".into())
+			}
+		}
+	}
+}
+
+impl Clipboard for ClipboardContext {
+	fn available_formats(&self) -> Result<Vec<String>> {
+		dispatch!(&self.inner, ctx => ctx.available_formats())
+	}
+
+	fn available_formats_detailed(&self) -> Result<Vec<FormatInfo>> {
+		dispatch!(&self.inner, ctx => ctx.available_formats_detailed())
+	}
+
+	fn has(&self, format: ContentFormat) -> bool {
+		dispatch!(&self.inner, ctx => ctx.has(format))
+	}
+
+	fn has_any(&self, formats: &[ContentFormat]) -> bool {
+		dispatch!(&self.inner, ctx => ctx.has_any(formats))
+	}
+
+	fn has_all(&self, formats: &[ContentFormat]) -> bool {
+		dispatch!(&self.inner, ctx => ctx.has_all(formats))
+	}
+
+	fn is_empty(&self) -> bool {
+		dispatch!(&self.inner, ctx => ctx.is_empty())
+	}
+
+	fn clear(&self) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.clear())
+	}
+
+	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_buffer(format))
+	}
+
+	fn read_streaming(&self, format: &str, sink: &mut dyn Write) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.read_streaming(format, sink))
+	}
+
+	fn get_text(&self) -> Result<String> {
+		dispatch!(&self.inner, ctx => ctx.get_text())
+	}
+
+	fn get_rich_text(&self) -> Result<String> {
+		dispatch!(&self.inner, ctx => ctx.get_rich_text())
+	}
+
+	fn get_html(&self) -> Result<String> {
+		dispatch!(&self.inner, ctx => ctx.get_html())
+	}
+
+	fn get_html_full_document(&self) -> Result<String> {
+		dispatch!(&self.inner, ctx => ctx.get_html_full_document())
+	}
+
+	fn get_text_bytes(&self) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_text_bytes())
+	}
+
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_rich_text_bytes())
+	}
+
+	fn get_html_bytes(&self) -> Result<Vec<u8>> {
+		dispatch!(&self.inner, ctx => ctx.get_html_bytes())
+	}
+
+	fn get_image(&self) -> Result<RustImageData> {
+		dispatch!(&self.inner, ctx => ctx.get_image())
+	}
+
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		dispatch!(&self.inner, ctx => ctx.image_dimensions())
+	}
+
+	fn get_files(&self) -> Result<Vec<String>> {
+		dispatch!(&self.inner, ctx => ctx.get_files())
+	}
+
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		dispatch!(&self.inner, ctx => ctx.get_files_with_operation())
+	}
+
+	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
+		dispatch!(&self.inner, ctx => ctx.get(formats))
+	}
+
+	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_buffer(format, buffer))
+	}
+
+	fn set_text(&self, text: String) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_text(text))
+	}
+
+	fn append_text_with_separator(&self, text: &str, separator: Option<&str>) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.append_text_with_separator(text, separator))
+	}
+
+	fn set_rich_text(&self, text: String) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_rich_text(text))
+	}
+
+	fn set_html(&self, html: String) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_html(html))
+	}
+
+	fn set_html_raw(&self, html: String) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_html_raw(html))
+	}
+
+	fn set_image(&self, image: RustImageData) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_image(image))
+	}
+
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_image_from_path(path))
+	}
+
+	fn set_files(&self, files: Vec<String>) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set_files(files))
+	}
+
+	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.set(contents))
+	}
+
+	fn set_with_lazy<F>(&self, formats: Vec<ContentFormat>, provider: F) -> Result<()>
+	where
+		F: Fn(ContentFormat) -> Option<Vec<u8>> + Send + 'static,
+	{
+		dispatch!(&self.inner, ctx => ctx.set_with_lazy(formats, provider))
+	}
+
+	fn snapshot(&self) -> Result<ClipboardSnapshot> {
+		dispatch!(&self.inner, ctx => ctx.snapshot())
+	}
+
+	fn restore(&self, snapshot: ClipboardSnapshot) -> Result<()> {
+		dispatch!(&self.inner, ctx => ctx.restore(snapshot))
+	}
+
+	fn get_sequence_number(&self) -> Result<u64> {
+		dispatch!(&self.inner, ctx => ctx.get_sequence_number())
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
+	pub fn new() -> Result<Self> {
+		Self::new_with_clipboard(ClipboardContext::new()?)
+	}
+
+	/// zh: 使用指定的剪切板上下文创建监听器，处理器会通过它读取剪切板内容，监听器使用与它相同的后端
+	/// en: Create a watcher with the given clipboard context, handlers read the clipboard through it, the watcher uses the same backend as the context
+	pub fn new_with_clipboard(clipboard: ClipboardContext) -> Result<Self> {
+		let inner = match clipboard.inner {
+			SystemClipboardContext::Native(ctx) => SystemClipboardWatcherContext::Native(
+				native::ClipboardWatcherContext::new_with_clipboard(ctx)?,
+			),
+			SystemClipboardContext::Memory(ctx) => SystemClipboardWatcherContext::Memory(
+				memory::ClipboardWatcherContext::new_with_clipboard(ctx)?,
+			),
+		};
+		Ok(Self { inner })
+	}
+
+	/// zh: 设置剪切板监听失败后的最大重试次数和重试间隔，超过后会调用处理器的 on_watch_error 并停止监听，内存剪切板不会失败
+	/// en: Set the maximum number of retries and the delay between them after the clipboard monitor fails, beyond which the handlers' on_watch_error is called and the watch stops, the memory clipboard never fails
+	#[cfg(target_os = "windows")]
+	pub fn set_monitor_retries(&mut self, max_retries: u32, retry_delay: Duration) -> &mut Self {
+		if let SystemClipboardWatcherContext::Native(watcher) = &mut self.inner {
+			watcher.set_monitor_retries(max_retries, retry_delay);
+		}
+		self
+	}
+
+	/// zh: 设置每秒最多通知处理器的次数，超出的变化会被合并，但一连串变化中的最后一次总会被通知，小于等于 0 表示不限制
+	/// en: Set the maximum number of events per second dispatched to handlers, changes beyond the rate are coalesced but the last change of a burst is always delivered, a value <= 0 means no limit
+	pub fn set_max_event_rate(&mut self, max_events_per_second: f32) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.set_max_event_rate(max_events_per_second);
+		});
+		self
+	}
+
+	/// zh: 设置丢弃 [`WatcherShutdown`] 时是否停止监听（旧版本的行为），默认只有调用 [`WatcherShutdown::stop`] 才会停止，只影响之后获得的停止通道
+	/// en: Set whether dropping a [`WatcherShutdown`] stops the watcher (the behavior of older versions), by default only [`WatcherShutdown::stop`] stops it, only affects shutdown channels obtained afterwards
+	pub fn set_stop_on_drop(&mut self, stop_on_drop: bool) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.set_stop_on_drop(stop_on_drop);
+		});
+		self
+	}
+
+	/// zh: 订阅剪切板变化事件，返回一个接收变化事件的通道，每次调用都会创建一个新的独立通道
+	/// en: Subscribe to clipboard change events, returns a receiver of the change events, every call creates a new independent channel
+	pub fn subscribe(&mut self) -> Receiver<ClipboardEvent> {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.subscribe())
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
+	fn add_handler(&mut self, handler: T) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.add_handler(handler);
+		});
+		self
+	}
+
+	fn add_filtered_handler(&mut self, handler: T, formats: Vec<ContentFormat>) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.add_filtered_handler(handler, formats);
+		});
+		self
+	}
+
+	fn add_named_handler(&mut self, name: &str, handler: T) -> &mut Self {
+		dispatch_watcher!(&mut self.inner, watcher => {
+			watcher.add_named_handler(name, handler);
+		});
+		self
+	}
+
+	fn remove_handler(&mut self, index: usize) -> Option<T> {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.remove_handler(index))
+	}
+
+	fn remove_named_handler(&mut self, name: &str) -> Option<T> {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.remove_named_handler(name))
+	}
+
+	fn start_watch(&mut self) {
+		dispatch_watcher!(&mut self.inner, watcher => watcher.start_watch())
+	}
+
+	fn get_shutdown_channel(&self) -> WatcherShutdown {
+		dispatch_watcher!(&self.inner, watcher => watcher.get_shutdown_channel())
+	}
+}
@@ -0,0 +1,703 @@
+use super::x11::{parse_moz_url, parse_uri_list};
+use crate::common::{
+	dispatch_to_handlers, image_dimensions_from_bytes, insert_named_handler, is_png,
+	notify_subscribers, take_handler_at, take_named_handler, EventThrottle, FileOperation,
+	HandlerEntry, Result, RustImage, RustImageData, WatcherShutdown, XSelection,
+};
+use crate::{
+	Clipboard, ClipboardContent, ClipboardContextBuilder, ClipboardError, ClipboardEvent,
+	ClipboardHandler, ClipboardWatcher, ContentFormat,
+};
+use image::ImageFormat;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wayland_client::{
+	backend::WaylandError,
+	event_created_child,
+	globals::{registry_queue_init, GlobalListContents},
+	protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+	Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+	zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+	zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+	zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+};
+use wl_clipboard_rs::{copy, paste, utils::is_text};
+
+const RTF: &str = "text/rtf";
+const HTML: &str = "text/html";
+const MARKDOWN: &str = "text/markdown";
+const PNG: &str = "image/png";
+const FILE_LIST: &str = "text/uri-list";
+const GNOME_COPY_FILES: &str = "x-special/gnome-copied-files";
+const KDE_CUT_SELECTION: &str = "application/x-kde-cutselection";
+// UTF-16 text with the URL and the title on separate lines, written by Firefox
+const MOZ_URL: &str = "text/x-moz-url";
+const FILE_PATH_PREFIX: &str = "file://";
+
+// 监听循环每次等待事件的最长时间，之后检查停止信号
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// zh: 通过 wlr-data-control 协议访问 Wayland 剪切板，不需要窗口或焦点，要求合成器支持该协议（wlroots 系列、KDE 等）
+/// en: Accesses the Wayland clipboard through the wlr-data-control protocol, which needs no window or focus, the compositor must support the protocol (wlroots based ones, KDE, ...)
+pub struct ClipboardContext {
+	// 读写的选区，只支持 CLIPBOARD 和 PRIMARY
+	selection: XSelection,
+}
+
+pub struct ClipboardWatcherContext<T: ClipboardHandler> {
+	handlers: Vec<HandlerEntry<T>>,
+	stop_signal: Sender<()>,
+	stop_receiver: Receiver<()>,
+	// start_watch 退出后发送，WatcherShutdown::wait_for_completion 等待它
+	done_signal: Sender<()>,
+	done_receiver: Arc<Mutex<Receiver<()>>>,
+	running: bool,
+	// 旧的行为：WatcherShutdown 被丢弃时停止监听
+	stop_on_drop: bool,
+	throttle: EventThrottle,
+	subscribers: Vec<Sender<ClipboardEvent>>,
+	// 传给处理器用来读取剪切板内容
+	clipboard: ClipboardContext,
+	// 监听的选区，默认只有 CLIPBOARD
+	selections: Vec<XSelection>,
+}
+
+unsafe impl<T: ClipboardHandler> Send for ClipboardWatcherContext<T> {}
+
+impl ClipboardContext {
+	pub fn new() -> Result<Self> {
+		Self::from_builder(&ClipboardContextBuilder::default())
+	}
+
+	pub(crate) fn from_builder(builder: &ClipboardContextBuilder) -> Result<Self> {
+		if builder.selection == XSelection::Secondary {
+			return Err("The SECONDARY selection is not available on Wayland".into());
+		}
+		// fail now rather than on the first read when there is no compositor at all, or it lacks the protocol (e.g. GNOME)
+		let conn = Connection::connect_to_env().map_err(|e| ClipboardError::PlatformError {
+			code: 0,
+			message: format!("Failed to connect to the Wayland compositor: {}", e),
+		})?;
+		let (globals, _queue) = registry_queue_init::<WatchState>(&conn)?;
+		let has_data_control = globals.contents().with_list(|list| {
+			list.iter()
+				.any(|global| global.interface == ZwlrDataControlManagerV1::interface().name)
+		});
+		if !has_data_control {
+			return Err(ClipboardError::PlatformError {
+				code: 0,
+				message: "The compositor does not support wlr-data-control".to_string(),
+			}
+			.into());
+		}
+		Ok(Self {
+			selection: builder.selection,
+		})
+	}
+
+	fn paste_type(&self) -> paste::ClipboardType {
+		match self.selection {
+			XSelection::Primary => paste::ClipboardType::Primary,
+			_ => paste::ClipboardType::Regular,
+		}
+	}
+
+	fn copy_type(&self) -> copy::ClipboardType {
+		match self.selection {
+			XSelection::Primary => copy::ClipboardType::Primary,
+			_ => copy::ClipboardType::Regular,
+		}
+	}
+
+	// the offered MIME types, an empty clipboard offers none
+	fn mime_types(&self) -> Result<HashSet<String>> {
+		match paste::get_mime_types(self.paste_type(), paste::Seat::Unspecified) {
+			Ok(mime_types) => Ok(mime_types),
+			Err(paste::Error::ClipboardEmpty | paste::Error::NoSeats) => Ok(HashSet::new()),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	fn read_mime(&self, mime_type: paste::MimeType) -> Result<Vec<u8>> {
+		let (mut pipe, _) =
+			match paste::get_contents(self.paste_type(), paste::Seat::Unspecified, mime_type) {
+				Ok(contents) => contents,
+				Err(paste::Error::ClipboardEmpty | paste::Error::NoSeats) => {
+					return Err("The clipboard is empty".into())
+				}
+				Err(paste::Error::NoMimeType) => {
+					return Err(format!("no data of format {:?}", mime_type).into())
+				}
+				Err(e) => return Err(e.into()),
+			};
+		let mut data = Vec::new();
+		pipe.read_to_end(&mut data)?;
+		Ok(data)
+	}
+
+	fn read(&self, mime_type: &str) -> Result<Vec<u8>> {
+		self.read_mime(paste::MimeType::Specific(mime_type))
+	}
+
+	fn read_string(&self, mime_type: &str) -> Result<String> {
+		Ok(String::from_utf8_lossy(&self.read(mime_type)?).to_string())
+	}
+
+	// the encoded bytes of the image, PNG first and then any other image type offered
+	fn read_image_bytes(&self) -> Result<Vec<u8>> {
+		let mime_types = self.mime_types()?;
+		if mime_types.contains(PNG) {
+			return self.read(PNG);
+		}
+		match mime_types.iter().find(|mime| mime.starts_with("image/")) {
+			Some(mime) => self.read(mime),
+			None => Err("no image data".into()),
+		}
+	}
+
+	fn write(&self, data: Vec<(copy::MimeType, Vec<u8>)>) -> Result<()> {
+		let sources = data
+			.into_iter()
+			.map(|(mime_type, data)| copy::MimeSource {
+				source: copy::Source::Bytes(data.into_boxed_slice()),
+				mime_type,
+			})
+			.collect();
+		let mut options = copy::Options::new();
+		options.clipboard(self.copy_type());
+		// the data is served by a background thread until another client takes the selection
+		options.copy_multi(sources)?;
+		Ok(())
+	}
+}
+
+fn encode(content: ClipboardContent) -> Result<Vec<(copy::MimeType, Vec<u8>)>> {
+	let specific = |mime_type: &str| copy::MimeType::Specific(mime_type.to_owned());
+	Ok(match content {
+		// the common plain text MIME types are all offered
+		ClipboardContent::Text(text) => vec![(copy::MimeType::Text, text.into_bytes())],
+		ClipboardContent::Rtf(rtf) => vec![(specific(RTF), rtf.into_bytes())],
+		ClipboardContent::Html(html) => vec![(specific(HTML), html.into_bytes())],
+		ClipboardContent::Image(image) => {
+			vec![(specific(PNG), image.to_png()?.get_bytes().to_vec())]
+		}
+		ClipboardContent::Files(files) => encode_files(files, FileOperation::Copy),
+		ClipboardContent::FilesWithOperation(files, operation) => encode_files(files, operation),
+		ClipboardContent::Markdown(markdown) => vec![(specific(MARKDOWN), markdown.into_bytes())],
+		ClipboardContent::Url(url) => vec![(
+			specific(MOZ_URL),
+			url.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+		)],
+		ClipboardContent::Other(format, buffer) => vec![(copy::MimeType::Specific(format), buffer)],
+	})
+}
+
+// the URI list, with the operation for GNOME and KDE file managers
+fn encode_files(files: Vec<String>, operation: FileOperation) -> Vec<(copy::MimeType, Vec<u8>)> {
+	let uri_list: Vec<String> = files
+		.into_iter()
+		.map(|f| match f.starts_with(FILE_PATH_PREFIX) {
+			true => f,
+			false => format!("{}{}", FILE_PATH_PREFIX, f),
+		})
+		.collect();
+	let operation_line = match operation {
+		FileOperation::Copy => "copy\n",
+		FileOperation::Cut => "cut\n",
+	};
+	let mut data = vec![
+		(
+			copy::MimeType::Specific(FILE_LIST.to_owned()),
+			uri_list.join("\r\n").into_bytes(),
+		),
+		(
+			copy::MimeType::Specific(GNOME_COPY_FILES.to_owned()),
+			[operation_line, uri_list.join("\n").as_str()]
+				.concat()
+				.into_bytes(),
+		),
+	];
+	if operation == FileOperation::Cut {
+		data.push((
+			copy::MimeType::Specific(KDE_CUT_SELECTION.to_owned()),
+			b"1".to_vec(),
+		));
+	}
+	data
+}
+
+impl Clipboard for ClipboardContext {
+	fn available_formats(&self) -> Result<Vec<String>> {
+		let mut mime_types: Vec<String> = self.mime_types()?.into_iter().collect();
+		mime_types.sort();
+		Ok(mime_types)
+	}
+
+	fn has(&self, format: ContentFormat) -> bool {
+		let mime_types = match self.mime_types() {
+			Ok(mime_types) => mime_types,
+			Err(_) => return false,
+		};
+		match format {
+			ContentFormat::Text => mime_types.iter().any(|mime| is_text(mime)),
+			ContentFormat::Rtf => mime_types.contains(RTF),
+			ContentFormat::Html => mime_types.contains(HTML),
+			ContentFormat::Image => mime_types.iter().any(|mime| mime.starts_with("image/")),
+			ContentFormat::Files => mime_types.contains(FILE_LIST),
+			ContentFormat::Markdown => mime_types.contains(MARKDOWN),
+			ContentFormat::Url => mime_types.contains(MOZ_URL),
+			ContentFormat::Metafile => false,
+			ContentFormat::Other(format) => mime_types.contains(&format),
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.mime_types()
+			.map_or(true, |mime_types| mime_types.is_empty())
+	}
+
+	fn clear(&self) -> Result<()> {
+		copy::clear(self.copy_type(), copy::Seat::All)?;
+		Ok(())
+	}
+
+	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		self.read(format)
+	}
+
+	fn get_text(&self) -> Result<String> {
+		let data = self.read_mime(paste::MimeType::Text)?;
+		Ok(String::from_utf8_lossy(&data).to_string())
+	}
+
+	fn get_rich_text(&self) -> Result<String> {
+		self.read_string(RTF)
+	}
+
+	fn get_html(&self) -> Result<String> {
+		self.read_string(HTML)
+	}
+
+	fn get_text_bytes(&self) -> Result<Vec<u8>> {
+		self.read_mime(paste::MimeType::Text)
+	}
+
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		self.read(RTF)
+	}
+
+	fn get_html_bytes(&self) -> Result<Vec<u8>> {
+		self.read(HTML)
+	}
+
+	fn get_image(&self) -> Result<RustImageData> {
+		RustImageData::from_bytes(&self.read_image_bytes()?)
+	}
+
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		image_dimensions_from_bytes(&self.read_image_bytes()?)
+	}
+
+	fn get_files(&self) -> Result<Vec<String>> {
+		Ok(parse_uri_list(&self.read_string(FILE_LIST)?))
+	}
+
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		let files = self.get_files()?;
+		let mime_types = self.mime_types().unwrap_or_default();
+		// GNOME file managers put the operation on the first line, KDE marks a cut with "1"
+		let is_cut = (mime_types.contains(GNOME_COPY_FILES)
+			&& self
+				.read(GNOME_COPY_FILES)
+				.map_or(false, |data| data.starts_with(b"cut\n")))
+			|| (mime_types.contains(KDE_CUT_SELECTION)
+				&& self
+					.read(KDE_CUT_SELECTION)
+					.map_or(false, |data| data.starts_with(b"1")));
+		let operation = match is_cut {
+			true => FileOperation::Cut,
+			false => FileOperation::Copy,
+		};
+		Ok((operation, files))
+	}
+
+	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
+		let mut contents = Vec::new();
+		for format in formats {
+			let content = match format {
+				ContentFormat::Text => self.get_text().map(ClipboardContent::Text),
+				ContentFormat::Rtf => self.get_rich_text().map(ClipboardContent::Rtf),
+				ContentFormat::Html => self.get_html().map(ClipboardContent::Html),
+				ContentFormat::Image => self.get_image().map(ClipboardContent::Image),
+				ContentFormat::Files => self.get_files().map(ClipboardContent::Files),
+				ContentFormat::Markdown => {
+					self.read_string(MARKDOWN).map(ClipboardContent::Markdown)
+				}
+				ContentFormat::Url => self.read(MOZ_URL).and_then(|data| {
+					parse_moz_url(&data)
+						.map(ClipboardContent::Url)
+						.ok_or_else(|| "No url data found".into())
+				}),
+				ContentFormat::Metafile => continue,
+				ContentFormat::Other(format) => self
+					.get_buffer(format)
+					.map(|buffer| ClipboardContent::Other(format.clone(), buffer)),
+			};
+			if let Ok(content) = content {
+				contents.push(content);
+			}
+		}
+		Ok(contents)
+	}
+
+	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
+		self.write(vec![(copy::MimeType::Specific(format.to_owned()), buffer)])
+	}
+
+	fn set_text(&self, text: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Text(text)])
+	}
+
+	fn set_rich_text(&self, text: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Rtf(text)])
+	}
+
+	fn set_html(&self, html: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Html(html)])
+	}
+
+	fn set_image(&self, image: RustImageData) -> Result<()> {
+		self.set(vec![ClipboardContent::Image(image)])
+	}
+
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		let bytes = std::fs::read(path)?;
+		if is_png(&bytes) {
+			return self.set_buffer(PNG, bytes);
+		}
+		self.set_image(RustImageData::from_bytes(&bytes)?)
+	}
+
+	fn set_files(&self, files: Vec<String>) -> Result<()> {
+		self.set(vec![ClipboardContent::Files(files)])
+	}
+
+	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+		let data = contents
+			.into_iter()
+			.map(encode)
+			.collect::<Result<Vec<_>>>()?;
+		self.write(data.into_iter().flatten().collect())
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
+	pub fn new() -> Result<Self> {
+		Self::new_with_clipboard(ClipboardContext::new()?)
+	}
+
+	/// zh: 使用指定的剪切板上下文创建监听器，处理器会通过它读取剪切板内容
+	/// en: Create a watcher with the given clipboard context, handlers read the clipboard through it
+	pub fn new_with_clipboard(clipboard: ClipboardContext) -> Result<Self> {
+		let (tx, rx) = mpsc::channel();
+		let (done_tx, done_rx) = mpsc::channel();
+		Ok(Self {
+			handlers: Vec::new(),
+			stop_signal: tx,
+			stop_receiver: rx,
+			done_signal: done_tx,
+			done_receiver: Arc::new(Mutex::new(done_rx)),
+			running: false,
+			stop_on_drop: false,
+			throttle: EventThrottle::default(),
+			subscribers: Vec::new(),
+			clipboard,
+			selections: vec![XSelection::Clipboard],
+		})
+	}
+
+	/// zh: 设置监听的选区，默认只监听 CLIPBOARD，监听 PRIMARY 需要合成器支持 wlr-data-control 第 2 版，需要在开始监听前调用
+	/// en: Set the selections to watch, only CLIPBOARD is watched by default, watching PRIMARY requires version 2 of wlr-data-control, must be called before the watch starts
+	pub fn watch_selections(&mut self, selections: &[XSelection]) -> &mut Self {
+		self.selections = selections.to_vec();
+		self
+	}
+
+	/// zh: 设置每秒最多通知处理器的次数，超出的变化会被合并，但一连串变化中的最后一次总会被通知，小于等于 0 表示不限制
+	/// en: Set the maximum number of events per second dispatched to handlers, changes beyond the rate are coalesced but the last change of a burst is always delivered, a value <= 0 means no limit
+	pub fn set_max_event_rate(&mut self, max_events_per_second: f32) -> &mut Self {
+		self.throttle.set_max_event_rate(max_events_per_second);
+		self
+	}
+
+	/// zh: 设置丢弃 [`WatcherShutdown`] 时是否停止监听（旧版本的行为），默认只有调用 [`WatcherShutdown::stop`] 才会停止，只影响之后获得的停止通道
+	/// en: Set whether dropping a [`WatcherShutdown`] stops the watcher (the behavior of older versions), by default only [`WatcherShutdown::stop`] stops it, only affects shutdown channels obtained afterwards
+	pub fn set_stop_on_drop(&mut self, stop_on_drop: bool) -> &mut Self {
+		self.stop_on_drop = stop_on_drop;
+		self
+	}
+
+	/// zh: 订阅剪切板变化事件，返回一个接收变化事件的通道，每次调用都会创建一个新的独立通道
+	/// en: Subscribe to clipboard change events, returns a receiver of the change events, every call creates a new independent channel
+	pub fn subscribe(&mut self) -> Receiver<ClipboardEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.push(tx);
+		rx
+	}
+
+	// the selection events of every seat are received through a data device of the data-control manager
+	// https://wayland.app/protocols/wlr-data-control-unstable-v1
+	fn watch(&mut self) -> Result<()> {
+		let conn = Connection::connect_to_env()?;
+		let (globals, mut queue) = registry_queue_init::<WatchState>(&conn)?;
+		let qh = queue.handle();
+		let manager: ZwlrDataControlManagerV1 = globals.bind(&qh, 1..=2, ()).map_err(|e| {
+			format!(
+				"The compositor does not support wlr-data-control ({}), the clipboard cannot be watched",
+				e
+			)
+		})?;
+		if manager.version() < 2 && self.selections.contains(&XSelection::Primary) {
+			return Err("The compositor does not support watching the PRIMARY selection".into());
+		}
+		let seats: Vec<WlSeat> = globals.contents().with_list(|list| {
+			list.iter()
+				.filter(|global| global.interface == WlSeat::interface().name)
+				.map(|global| globals.registry().bind(global.name, 1, &qh, ()))
+				.collect()
+		});
+		if seats.is_empty() {
+			return Err("There are no seats".into());
+		}
+		let devices: Vec<ZwlrDataControlDeviceV1> = seats
+			.iter()
+			.map(|seat| manager.get_data_device(seat, &qh, ()))
+			.collect();
+
+		let mut state = WatchState::default();
+		// the devices report the current selections first, they are not changes
+		queue.roundtrip(&mut state)?;
+		state.changes.clear();
+
+		let mut last_events: Vec<ClipboardEvent> = Vec::new();
+		let res = loop {
+			if self.stop_receiver.try_recv().is_ok() {
+				break Ok(());
+			}
+			if let Err(e) = wait_for_events(&conn, &mut queue, &mut state) {
+				break Err(e);
+			}
+			if state.finished {
+				break Err("The data-control device was destroyed by the compositor".into());
+			}
+			let mut changed = false;
+			for selection in state.changes.drain(..) {
+				if !self.selections.contains(&selection) {
+					continue;
+				}
+				changed = true;
+				let mut event = ClipboardEvent::new(None);
+				event.selection = selection;
+				last_events.retain(|e| e.selection != selection);
+				last_events.push(event);
+			}
+			if self.throttle.should_dispatch(changed, Instant::now()) {
+				for event in last_events.drain(..) {
+					dispatch_to_handlers(&mut self.handlers, &self.clipboard, &event, |format| {
+						self.clipboard.has(format.clone())
+					});
+					notify_subscribers(&mut self.subscribers, &event);
+				}
+			}
+		};
+		for device in devices {
+			device.destroy();
+		}
+		manager.destroy();
+		let _ = conn.flush();
+		res
+	}
+}
+
+// dispatch the events received within the poll interval
+fn wait_for_events(
+	conn: &Connection,
+	queue: &mut wayland_client::EventQueue<WatchState>,
+	state: &mut WatchState,
+) -> Result<()> {
+	queue.dispatch_pending(state)?;
+	conn.flush()?;
+	if let Some(guard) = queue.prepare_read() {
+		let readable = {
+			let fd = guard.connection_fd();
+			let mut fds = [PollFd::new(&fd, PollFlags::POLLIN)];
+			match poll(&mut fds, WATCH_POLL_INTERVAL.as_millis() as i32) {
+				Ok(count) => count > 0,
+				Err(Errno::EINTR) => false,
+				Err(e) => return Err(format!("poll error: {}", e).into()),
+			}
+		};
+		if readable {
+			match guard.read() {
+				Ok(_) => {}
+				Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+				Err(e) => return Err(e.into()),
+			}
+		}
+	}
+	queue.dispatch_pending(state)?;
+	Ok(())
+}
+
+impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
+	fn add_handler(&mut self, handler: T) -> &mut Self {
+		self.handlers.push(HandlerEntry::new(handler, None));
+		self
+	}
+
+	fn add_filtered_handler(&mut self, handler: T, formats: Vec<ContentFormat>) -> &mut Self {
+		self.handlers
+			.push(HandlerEntry::new(handler, Some(formats)));
+		self
+	}
+
+	fn add_named_handler(&mut self, name: &str, handler: T) -> &mut Self {
+		insert_named_handler(&mut self.handlers, name, handler);
+		self
+	}
+
+	fn remove_handler(&mut self, index: usize) -> Option<T> {
+		take_handler_at(&mut self.handlers, index)
+	}
+
+	fn remove_named_handler(&mut self, name: &str) -> Option<T> {
+		take_named_handler(&mut self.handlers, name)
+	}
+
+	fn start_watch(&mut self) {
+		if self.running {
+			println!("already start watch!");
+			return;
+		}
+		if self.handlers.is_empty() && self.subscribers.is_empty() {
+			println!("no handler, no need to start watch!");
+			return;
+		}
+		self.running = true;
+		// drain the stop signals left over from a previous session (e.g. `WatcherShutdown::stop` called while the watcher was not running),
+		// so that the watcher can be restarted after it has been stopped
+		while self.stop_receiver.try_recv().is_ok() {}
+		// start_watch cannot return the error, the handlers get it
+		if let Err(e) = self.watch() {
+			self.handlers.iter_mut().for_each(|entry| {
+				entry.handler.on_watch_error(&*e);
+			});
+		}
+		self.running = false;
+		let _ = self.done_signal.send(());
+	}
+
+	fn get_shutdown_channel(&self) -> WatcherShutdown {
+		WatcherShutdown::new(
+			self.stop_signal.clone(),
+			self.done_receiver.clone(),
+			self.stop_on_drop,
+		)
+	}
+}
+
+#[derive(Default)]
+struct WatchState {
+	// 收到的选区变化，按到达顺序保存
+	changes: Vec<XSelection>,
+	// 合成器销毁了数据设备，之后不会再有事件
+	finished: bool,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for WatchState {
+	fn event(
+		_state: &mut Self,
+		_registry: &WlRegistry,
+		_event: <WlRegistry as Proxy>::Event,
+		_data: &GlobalListContents,
+		_conn: &Connection,
+		_qh: &QueueHandle<Self>,
+	) {
+	}
+}
+
+impl Dispatch<WlSeat, ()> for WatchState {
+	fn event(
+		_state: &mut Self,
+		_seat: &WlSeat,
+		_event: <WlSeat as Proxy>::Event,
+		_data: &(),
+		_conn: &Connection,
+		_qh: &QueueHandle<Self>,
+	) {
+	}
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for WatchState {
+	fn event(
+		_state: &mut Self,
+		_manager: &ZwlrDataControlManagerV1,
+		_event: <ZwlrDataControlManagerV1 as Proxy>::Event,
+		_data: &(),
+		_conn: &Connection,
+		_qh: &QueueHandle<Self>,
+	) {
+	}
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for WatchState {
+	fn event(
+		state: &mut Self,
+		_device: &ZwlrDataControlDeviceV1,
+		event: zwlr_data_control_device_v1::Event,
+		_data: &(),
+		_conn: &Connection,
+		_qh: &QueueHandle<Self>,
+	) {
+		// the watcher reads nothing through the offers, they are destroyed right away
+		match event {
+			zwlr_data_control_device_v1::Event::Selection { id } => {
+				state.changes.push(XSelection::Clipboard);
+				if let Some(offer) = id {
+					offer.destroy();
+				}
+			}
+			zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+				state.changes.push(XSelection::Primary);
+				if let Some(offer) = id {
+					offer.destroy();
+				}
+			}
+			zwlr_data_control_device_v1::Event::Finished => state.finished = true,
+			_ => {}
+		}
+	}
+
+	event_created_child!(WatchState, ZwlrDataControlDeviceV1, [
+		zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+	]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for WatchState {
+	fn event(
+		_state: &mut Self,
+		_offer: &ZwlrDataControlOfferV1,
+		_event: <ZwlrDataControlOfferV1 as Proxy>::Event,
+		_data: &(),
+		_conn: &Connection,
+		_qh: &QueueHandle<Self>,
+	) {
+	}
+}
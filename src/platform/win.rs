@@ -1,39 +1,171 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::ffi::c_void;
 use std::io::Cursor;
-use std::sync::mpsc::{Receiver, Sender};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Once};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::{mem, ptr};
 
-use crate::common::{ContentData, Result, RustImage, RustImageData};
-use crate::{Clipboard, ClipboardContent, ClipboardHandler, ClipboardWatcher, ContentFormat};
+use crate::common::{
+	append_text_to, content_from_bytes, dispatch_to_handlers, image_dimensions_from_bytes,
+	insert_named_handler, is_png, notify_subscribers, take_handler_at, take_named_handler,
+	ChangeSource, ClipboardOwner, ClipboardSnapshot, ContentData, EventThrottle, FileOperation,
+	FormatInfo, HandlerEntry, Result, RustImage, RustImageData, WatcherShutdown,
+};
+use crate::{
+	Clipboard, ClipboardContent, ClipboardContextBuilder, ClipboardEvent, ClipboardHandler,
+	ClipboardWatcher, ContentFormat,
+};
 use clipboard_win::raw::{set_bitmap_with, set_file_list_with, set_string_with, set_without_clear};
 use clipboard_win::types::c_uint;
-use clipboard_win::{
-	formats, get, get_clipboard, options, raw, set_clipboard, Clipboard as ClipboardWin, Monitor,
-	SysResult,
-};
+use clipboard_win::{formats, get, options, raw, Clipboard as ClipboardWin, Monitor, SysResult};
 use image::codecs::bmp::BmpDecoder;
-use image::DynamicImage;
-
-pub struct WatcherShutdown {
-	stop_signal: Sender<()>,
-}
+use image::{DynamicImage, ImageFormat};
 
 static UNKNOWN_FORMAT: &str = "unknown format";
 static CF_RTF: &str = "Rich Text Format";
 static CF_HTML: &str = "HTML Format";
 static CF_PNG: &str = "PNG";
+static CF_MARKDOWN: &str = "text/markdown";
+// 以 NUL 结尾的 URL，浏览器拖放和复制链接时使用
+static CF_URL: &str = "UniformResourceLocator";
+// Explorer 用来区分复制和剪切文件的格式，内容为 DROPEFFECT 值
+static CF_PREFERRED_DROP_EFFECT: &str = "Preferred DropEffect";
+// CF_METAFILEPICT is a predefined format, this name only selects it in `get_buffer`
+static CF_METAFILEPICT: &str = "CF_METAFILEPICT";
+
+// 本进程最近一次写入剪切板后的序列号，监听器据此判断变化是否来自本进程，0 表示没有写入过
+static LAST_OWN_WRITE: AtomicU64 = AtomicU64::new(0);
+
+// https://learn.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-metafilepict
+#[repr(C)]
+struct MetafilePict {
+	mm: i32,
+	x_ext: i32,
+	y_ext: i32,
+	hmf: *mut c_void,
+}
+
+#[link(name = "gdi32")]
+extern "system" {
+	fn GetMetaFileBitsEx(hmf: *mut c_void, size: u32, data: *mut c_void) -> u32;
+}
+
+const QS_ALLINPUT: u32 = 0x04FF;
+const DROPEFFECT_COPY: u32 = 1;
+const DROPEFFECT_MOVE: u32 = 2;
+const WM_RENDERFORMAT: u32 = 0x0305;
+const WM_RENDERALLFORMATS: u32 = 0x0306;
+const WM_DESTROYCLIPBOARD: u32 = 0x0307;
+// the parent of message-only windows
+const HWND_MESSAGE: isize = -3;
+
+type WndProc = unsafe extern "system" fn(*mut c_void, u32, usize, isize) -> isize;
+
+// https://learn.microsoft.com/en-us/windows/win32/api/winuser/ns-winuser-wndclassw
+#[repr(C)]
+struct WndClassW {
+	style: u32,
+	wnd_proc: Option<WndProc>,
+	cls_extra: i32,
+	wnd_extra: i32,
+	instance: *mut c_void,
+	icon: *mut c_void,
+	cursor: *mut c_void,
+	background: *mut c_void,
+	menu_name: *const u16,
+	class_name: *const u16,
+}
+
+// https://learn.microsoft.com/en-us/windows/win32/api/winuser/ns-winuser-msg
+#[repr(C)]
+struct Msg {
+	hwnd: *mut c_void,
+	message: u32,
+	wparam: usize,
+	lparam: isize,
+	time: u32,
+	pt_x: i32,
+	pt_y: i32,
+	private: u32,
+}
+
+#[link(name = "user32")]
+extern "system" {
+	fn MsgWaitForMultipleObjects(
+		count: u32,
+		handles: *const *mut c_void,
+		wait_all: i32,
+		milliseconds: u32,
+		wake_mask: u32,
+	) -> u32;
+	fn RegisterClassW(class: *const WndClassW) -> u16;
+	fn CreateWindowExW(
+		ex_style: u32,
+		class_name: *const u16,
+		window_name: *const u16,
+		style: u32,
+		x: i32,
+		y: i32,
+		width: i32,
+		height: i32,
+		parent: *mut c_void,
+		menu: *mut c_void,
+		instance: *mut c_void,
+		param: *mut c_void,
+	) -> *mut c_void;
+	fn DestroyWindow(hwnd: *mut c_void) -> i32;
+	fn DefWindowProcW(hwnd: *mut c_void, msg: u32, wparam: usize, lparam: isize) -> isize;
+	fn GetMessageW(msg: *mut Msg, hwnd: *mut c_void, min: u32, max: u32) -> i32;
+	fn DispatchMessageW(msg: *const Msg) -> isize;
+	fn PostQuitMessage(exit_code: i32);
+	fn SetClipboardData(format: u32, data: *mut c_void) -> *mut c_void;
+	fn GetClipboardOwner() -> *mut c_void;
+	fn GetWindowThreadProcessId(hwnd: *mut c_void, process_id: *mut u32) -> u32;
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+	fn GetModuleHandleW(module_name: *const u16) -> *mut c_void;
+	fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> *mut c_void;
+	fn QueryFullProcessImageNameW(
+		process: *mut c_void,
+		flags: u32,
+		exe_name: *mut u16,
+		size: *mut u32,
+	) -> i32;
+	fn CloseHandle(handle: *mut c_void) -> i32;
+}
+
+// enough to query the image name of elevated processes as well, unlike PROCESS_QUERY_INFORMATION | PROCESS_VM_READ of GetModuleFileNameEx
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
 
 pub struct ClipboardContext {
 	format_map: HashMap<&'static str, c_uint>,
 	html_format: formats::Html,
+	open_retries: u32,
+	retry_delay: Duration,
 }
 
 pub struct ClipboardWatcherContext<T: ClipboardHandler> {
-	handlers: Vec<T>,
+	handlers: Vec<HandlerEntry<T>>,
 	stop_signal: Sender<()>,
 	stop_receiver: Receiver<()>,
+	// start_watch 退出后发送，WatcherShutdown::wait_for_completion 等待它
+	done_signal: Sender<()>,
+	done_receiver: Arc<Mutex<Receiver<()>>>,
 	running: bool,
+	// 旧的行为：WatcherShutdown 被丢弃时停止监听
+	stop_on_drop: bool,
+	throttle: EventThrottle,
+	subscribers: Vec<Sender<ClipboardEvent>>,
+	// 传给处理器用来读取剪切板内容
+	clipboard: ClipboardContext,
+	monitor_retry: MonitorRetry,
 }
 
 unsafe impl Send for ClipboardContext {}
@@ -42,11 +174,14 @@ unsafe impl<T: ClipboardHandler> Send for ClipboardWatcherContext<T> {}
 unsafe impl<T: ClipboardHandler> Sync for ClipboardWatcherContext<T> {}
 
 impl ClipboardContext {
-	pub fn new() -> Result<ClipboardContext> {
+	pub(crate) fn from_builder(builder: &ClipboardContextBuilder) -> Result<ClipboardContext> {
 		let (format_map, html_format) = {
 			let cf_html_format = formats::Html::new();
 			let cf_rtf_uint = clipboard_win::register_format(CF_RTF);
 			let cf_png_uint = clipboard_win::register_format(CF_PNG);
+			let cf_markdown_uint = clipboard_win::register_format(CF_MARKDOWN);
+			let cf_url_uint = clipboard_win::register_format(CF_URL);
+			let cf_drop_effect_uint = clipboard_win::register_format(CF_PREFERRED_DROP_EFFECT);
 			let mut m: HashMap<&str, c_uint> = HashMap::new();
 			if let Some(cf_html) = cf_html_format {
 				m.insert(CF_HTML, cf_html.code());
@@ -57,14 +192,206 @@ impl ClipboardContext {
 			if let Some(cf_png) = cf_png_uint {
 				m.insert(CF_PNG, cf_png.get());
 			}
+			if let Some(cf_markdown) = cf_markdown_uint {
+				m.insert(CF_MARKDOWN, cf_markdown.get());
+			}
+			if let Some(cf_url) = cf_url_uint {
+				m.insert(CF_URL, cf_url.get());
+			}
+			if let Some(cf_drop_effect) = cf_drop_effect_uint {
+				m.insert(CF_PREFERRED_DROP_EFFECT, cf_drop_effect.get());
+			}
 			(m, cf_html_format)
 		};
 		Ok(ClipboardContext {
 			format_map,
 			html_format: html_format.ok_or("register html format error")?,
+			open_retries: builder.open_retries,
+			retry_delay: builder.retry_delay,
 		})
 	}
 
+	/// zh: 获得当前剪切板内容的所有者程序，剪切板没有所有者（例如被清空）时返回 None
+	/// en: Get the program owning the current clipboard content, None if the clipboard has no owner (e.g. it has been emptied)
+	pub fn get_owner(&self) -> Option<ClipboardOwner> {
+		let hwnd = unsafe { GetClipboardOwner() };
+		if hwnd.is_null() {
+			return None;
+		}
+		let mut pid = 0;
+		unsafe { GetWindowThreadProcessId(hwnd, &mut pid) };
+		if pid == 0 {
+			return None;
+		}
+		Some(ClipboardOwner {
+			app_name: process_name(pid),
+			pid: Some(pid),
+		})
+	}
+
+	// the ids the format may be stored as, the format is available if any of them is
+	fn format_ids(&self, format: &ContentFormat) -> Vec<u32> {
+		match format {
+			ContentFormat::Text => vec![formats::CF_UNICODETEXT],
+			ContentFormat::Rtf => vec![*self.format_map.get(CF_RTF).unwrap()],
+			ContentFormat::Html => vec![*self.format_map.get(CF_HTML).unwrap()],
+			// Currently only judge whether there is a png format
+			ContentFormat::Image => vec![*self.format_map.get(CF_PNG).unwrap(), formats::CF_DIB],
+			ContentFormat::Files => vec![formats::CF_HDROP],
+			ContentFormat::Markdown => vec![*self.format_map.get(CF_MARKDOWN).unwrap()],
+			ContentFormat::Url => vec![*self.format_map.get(CF_URL).unwrap()],
+			ContentFormat::Metafile => vec![formats::CF_METAFILEPICT],
+			ContentFormat::Other(format) => clipboard_win::register_format(format.as_str())
+				.map(|format_uint| vec![format_uint.get()])
+				.unwrap_or_default(),
+		}
+	}
+
+	// the ids of all formats on the clipboard, listed with the clipboard opened only once
+	fn enum_formats(&self) -> Result<Vec<u32>> {
+		let _clip = self.open()?;
+		Ok(clipboard_win::raw::EnumFormats::new().collect())
+	}
+
+	/// open the clipboard, retrying while it is held open by another program
+	fn open(&self) -> Result<ClipboardWin> {
+		let mut retries = 0;
+		loop {
+			match ClipboardWin::new() {
+				Ok(clip) => return Ok(clip),
+				Err(_) if retries < self.open_retries => {
+					retries += 1;
+					thread::sleep(self.retry_delay);
+				}
+				Err(code) => {
+					return Err(format!(
+						"Open clipboard error after {} attempts, code = {}",
+						retries + 1,
+						code
+					)
+					.into())
+				}
+			}
+		}
+	}
+
+	/// write png and bitmap representations of the image, the clipboard must be opened and will not be cleared
+	fn write_image(&self, image: &RustImageData) -> Result<()> {
+		// chromium source code
+		// @link {https://source.chromium.org/chromium/chromium/src/+/main:ui/base/clipboard/clipboard_win.cc;l=771;drc=2a5aaed0ff3a0895c8551495c2656ed49baf742c;bpv=0;bpt=1}
+		if self.format_map.contains_key(CF_PNG) {
+			let png = image.to_png()?;
+			self.write_png(png.get_bytes())?;
+		}
+		self.write_bitmap(image)
+	}
+
+	/// tell Explorer whether the files are copied or moved, the clipboard must be opened
+	fn write_drop_effect(&self, operation: FileOperation) -> Result<()> {
+		let Some(cf_drop_effect) = self.format_map.get(CF_PREFERRED_DROP_EFFECT) else {
+			return Ok(());
+		};
+		let effect = match operation {
+			FileOperation::Copy => DROPEFFECT_COPY,
+			FileOperation::Cut => DROPEFFECT_MOVE,
+		};
+		set_without_clear(*cf_drop_effect, &effect.to_le_bytes())
+			.map_err(|e| format!("set drop effect error, code = {}", e).into())
+	}
+
+	fn write_png(&self, png: &[u8]) -> Result<()> {
+		if let Some(cf_png_format) = self.format_map.get(CF_PNG) {
+			let write_png_res = set_without_clear(*cf_png_format, png);
+			if let Err(e) = write_png_res {
+				return Err(format!("set png image error, code = {}", e).into());
+			}
+		}
+		Ok(())
+	}
+
+	fn write_bitmap(&self, image: &RustImageData) -> Result<()> {
+		let bmp = image
+			.to_bitmap()
+			.map_err(|e| format!("to bitmap error, code = {}", e))?;
+		let res = set_bitmap_with(bmp.get_bytes(), options::NoClear);
+		res.map_err(|e| format!("set image error, code = {}", e).into())
+	}
+
+	/// read the given range of the CF_HTML data, the clipboard must be opened
+	fn read_html(&self, range: HtmlRange) -> Result<String> {
+		let data = get(formats::RawData(self.html_format.code()))
+			.map_err(|e| format!("Get buffer error, code = {}", e))?;
+		let html_full_str = String::from_utf8(data).map_err(|_| "Get html error")?;
+		// some programs write raw html without the header, or with broken offsets
+		Ok(extract_html_from_clipboard_data(&html_full_str, range).unwrap_or(html_full_str))
+	}
+
+	/// read the image in the PNG, DIBV5 or DIB format, the clipboard must be opened
+	fn read_image(&self) -> Result<RustImageData> {
+		let cf_png_format = self
+			.format_map
+			.get(CF_PNG)
+			.filter(|format| clipboard_win::is_format_avail(**format));
+		if let Some(cf_png_format) = cf_png_format {
+			let res = get(formats::RawData(*cf_png_format));
+			match res {
+				Ok(data) => RustImageData::from_bytes(&data),
+				Err(e) => Err(format!("Get image error, code = {}", e).into()),
+			}
+		} else if clipboard_win::is_format_avail(formats::CF_DIBV5) {
+			let res = get(formats::RawData(formats::CF_DIBV5));
+			match res {
+				Ok(data) => {
+					let decoder = {
+						// if data.as_slice().starts_with(b"BM") {
+						// 	BmpDecoder::new(Cursor::new(data.as_slice()))
+						// } else {
+						BmpDecoder::new_without_file_header(Cursor::new(data.as_slice()))
+						// }
+					};
+					let decoder = decoder.map_err(|e| format!("{}", e))?;
+					let dynamic_image =
+						DynamicImage::from_decoder(decoder).map_err(|e| format!("{}", e))?;
+					Ok(RustImageData::from_dynamic_image(dynamic_image))
+				}
+				Err(e) => Err(format!("Get image error, code = {}", e).into()),
+			}
+		} else if clipboard_win::is_format_avail(formats::CF_DIB) {
+			let res = get(formats::Bitmap);
+			match res {
+				Ok(data) => RustImageData::from_bytes(&data),
+				Err(e) => Err(format!("Get image error, code = {}", e).into()),
+			}
+		} else if clipboard_win::is_format_avail(formats::CF_METAFILEPICT) {
+			Err("Metafile images can not be decoded, read them with get_buffer(\"CF_METAFILEPICT\")".into())
+		} else {
+			Err("No image data in clipboard".into())
+		}
+	}
+
+	/// read the Windows Metafile bits of CF_METAFILEPICT, the clipboard must be opened
+	fn read_metafile(&self) -> Result<Vec<u8>> {
+		let mut buffer = Vec::new();
+		raw::get_vec(formats::CF_METAFILEPICT, &mut buffer)
+			.map_err(|e| format!("Get metafile error, code = {}", e))?;
+		if buffer.len() < mem::size_of::<MetafilePict>() {
+			return Err("Invalid METAFILEPICT data".into());
+		}
+		let pict = unsafe { ptr::read_unaligned(buffer.as_ptr() as *const MetafilePict) };
+		let size = unsafe { GetMetaFileBitsEx(pict.hmf, 0, ptr::null_mut()) };
+		if size == 0 {
+			return Err("Get metafile size error".into());
+		}
+		let mut bits = vec![0u8; size as usize];
+		let written =
+			unsafe { GetMetaFileBitsEx(pict.hmf, size, bits.as_mut_ptr() as *mut c_void) };
+		if written == 0 {
+			return Err("Get metafile bits error".into());
+		}
+		bits.truncate(written as usize);
+		Ok(bits)
+	}
+
 	fn get_format(&self, format: &ContentFormat) -> c_uint {
 		match format {
 			ContentFormat::Text => formats::CF_UNICODETEXT,
@@ -72,27 +399,69 @@ impl ClipboardContext {
 			ContentFormat::Html => *self.format_map.get(CF_HTML).unwrap(),
 			ContentFormat::Image => formats::CF_DIB,
 			ContentFormat::Files => formats::CF_HDROP,
+			ContentFormat::Markdown => *self.format_map.get(CF_MARKDOWN).unwrap(),
+			ContentFormat::Url => *self.format_map.get(CF_URL).unwrap(),
+			ContentFormat::Metafile => formats::CF_METAFILEPICT,
 			ContentFormat::Other(format) => clipboard_win::register_format(format).unwrap().get(),
 		}
 	}
 }
 
 impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
-	pub fn new() -> Result<Self> {
+	/// zh: 使用指定的剪切板上下文创建监听器，处理器会通过它读取剪切板内容
+	/// en: Create a watcher with the given clipboard context, handlers read the clipboard through it
+	pub fn new_with_clipboard(clipboard: ClipboardContext) -> Result<Self> {
 		let (tx, rx) = std::sync::mpsc::channel();
+		let (done_tx, done_rx) = std::sync::mpsc::channel();
 		Ok(Self {
 			handlers: Vec::new(),
 			stop_signal: tx,
 			stop_receiver: rx,
+			done_signal: done_tx,
+			done_receiver: Arc::new(Mutex::new(done_rx)),
 			running: false,
+			stop_on_drop: false,
+			throttle: EventThrottle::default(),
+			subscribers: Vec::new(),
+			clipboard,
+			monitor_retry: MonitorRetry::default(),
 		})
 	}
+
+	/// zh: 设置剪切板监听器出错后重建的最大连续重试次数和初始重试间隔（每次重试间隔翻倍），默认 5 次、100 毫秒
+	/// en: Set the maximum consecutive attempts to recreate the clipboard monitor after an error and the initial delay between them (doubled on every attempt), defaults to 5 attempts and 100 milliseconds
+	pub fn set_monitor_retries(&mut self, max_retries: u32, retry_delay: Duration) -> &mut Self {
+		self.monitor_retry.max_retries = max_retries;
+		self.monitor_retry.retry_delay = retry_delay;
+		self
+	}
+
+	/// zh: 设置每秒最多通知处理器的次数，超出的变化会被合并，但一连串变化中的最后一次总会被通知，小于等于 0 表示不限制
+	/// en: Set the maximum number of events per second dispatched to handlers, changes beyond the rate are coalesced but the last change of a burst is always delivered, a value <= 0 means no limit
+	pub fn set_max_event_rate(&mut self, max_events_per_second: f32) -> &mut Self {
+		self.throttle.set_max_event_rate(max_events_per_second);
+		self
+	}
+
+	/// zh: 设置丢弃 [`WatcherShutdown`] 时是否停止监听（旧版本的行为），默认只有调用 [`WatcherShutdown::stop`] 才会停止，只影响之后获得的停止通道
+	/// en: Set whether dropping a [`WatcherShutdown`] stops the watcher (the behavior of older versions), by default only [`WatcherShutdown::stop`] stops it, only affects shutdown channels obtained afterwards
+	pub fn set_stop_on_drop(&mut self, stop_on_drop: bool) -> &mut Self {
+		self.stop_on_drop = stop_on_drop;
+		self
+	}
+
+	/// zh: 订阅剪切板变化事件，返回一个接收变化事件的通道，每次调用都会创建一个新的独立通道
+	/// en: Subscribe to clipboard change events, returns a receiver of the change events, every call creates a new independent channel
+	pub fn subscribe(&mut self) -> Receiver<ClipboardEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.push(tx);
+		rx
+	}
 }
 
 impl Clipboard for ClipboardContext {
 	fn available_formats(&self) -> Result<Vec<String>> {
-		let _clip = ClipboardWin::new_attempts(10)
-			.map_err(|code| format!("Open clipboard error, code = {}", code));
+		let _clip = self.open()?;
 		let format_count = clipboard_win::count_formats();
 		if format_count.is_none() {
 			return Ok(Vec::new());
@@ -103,6 +472,8 @@ impl Clipboard for ClipboardContext {
 			let f_name = raw::format_name_big(format);
 			match f_name {
 				Some(name) => res.push(name),
+				// formats without a name are listed once
+				None if res.iter().any(|name| name == UNKNOWN_FORMAT) => {}
 				None => {
 					res.push(UNKNOWN_FORMAT.to_string());
 				}
@@ -111,51 +482,71 @@ impl Clipboard for ClipboardContext {
 		Ok(res)
 	}
 
+	fn available_formats_detailed(&self) -> Result<Vec<FormatInfo>> {
+		let _clip = self.open()?;
+		Ok(clipboard_win::raw::EnumFormats::new()
+			.map(|format| FormatInfo {
+				name: raw::format_name_big(format),
+				id: format,
+			})
+			.collect())
+	}
+
 	fn has(&self, format: ContentFormat) -> bool {
-		match format {
-			ContentFormat::Text => clipboard_win::is_format_avail(formats::CF_UNICODETEXT),
-			ContentFormat::Rtf => {
-				let cf_rtf_uint = self.format_map.get(CF_RTF).unwrap();
-				clipboard_win::is_format_avail(*cf_rtf_uint)
-			}
-			ContentFormat::Html => {
-				let cf_html_uint = self.format_map.get(CF_HTML).unwrap();
-				clipboard_win::is_format_avail(*cf_html_uint)
-			}
-			ContentFormat::Image => {
-				// Currently only judge whether there is a png format
-				let cf_png_uint = self.format_map.get(CF_PNG).unwrap();
-				clipboard_win::is_format_avail(*cf_png_uint)
-					|| clipboard_win::is_format_avail(formats::CF_DIB)
-			}
-			ContentFormat::Files => clipboard_win::is_format_avail(formats::CF_HDROP),
-			ContentFormat::Other(format) => {
-				let format_uint = clipboard_win::register_format(format.as_str());
-				if let Some(format_uint) = format_uint {
-					return clipboard_win::is_format_avail(format_uint.get());
-				}
-				false
-			}
+		self.format_ids(&format)
+			.into_iter()
+			.any(clipboard_win::is_format_avail)
+	}
+
+	fn has_any(&self, formats: &[ContentFormat]) -> bool {
+		match self.enum_formats() {
+			Ok(available) => formats.iter().any(|format| {
+				self.format_ids(format)
+					.iter()
+					.any(|id| available.contains(id))
+			}),
+			Err(_) => false,
 		}
 	}
 
+	fn has_all(&self, formats: &[ContentFormat]) -> bool {
+		match self.enum_formats() {
+			Ok(available) => formats.iter().all(|format| {
+				self.format_ids(format)
+					.iter()
+					.any(|id| available.contains(id))
+			}),
+			Err(_) => false,
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		// CountClipboardFormats does not require the clipboard to be opened
+		!matches!(clipboard_win::count_formats(), Some(count) if count > 0)
+	}
+
 	fn clear(&self) -> Result<()> {
-		let _clip = ClipboardWin::new_attempts(10)
-			.map_err(|code| format!("Open clipboard error, code = {}", code));
+		let _clip = self.open()?;
 		let res = clipboard_win::empty();
 		if let Err(e) = res {
 			return Err(format!("Empty clipboard error, code = {}", e).into());
 		}
+		record_own_write();
 		Ok(())
 	}
 
 	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		if format == CF_METAFILEPICT {
+			let _clip = self.open()?;
+			return self.read_metafile();
+		}
 		let format_uint = clipboard_win::register_format(format);
 		if format_uint.is_none() {
 			return Err("register format error".into());
 		}
 		let format_uint = format_uint.unwrap().get();
-		let buffer = get_clipboard(formats::RawData(format_uint));
+		let _clip = self.open()?;
+		let buffer = get(formats::RawData(format_uint));
 		match buffer {
 			Ok(data) => Ok(data),
 			Err(e) => Err(format!("Get buffer error, code = {}", e).into()),
@@ -163,7 +554,8 @@ impl Clipboard for ClipboardContext {
 	}
 
 	fn get_text(&self) -> Result<String> {
-		let string: SysResult<String> = get_clipboard(formats::Unicode);
+		let _clip = self.open()?;
+		let string: SysResult<String> = get(formats::Unicode);
 		match string {
 			Ok(s) => Ok(s),
 			Err(e) => Err(format!("Get text error, code = {}", e).into()),
@@ -175,68 +567,77 @@ impl Clipboard for ClipboardContext {
 		Ok(String::from_utf8_lossy(&rtf_raw_data).to_string())
 	}
 
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		self.get_buffer(CF_RTF)
+	}
+
 	fn get_html(&self) -> Result<String> {
-		let buffer = get_clipboard(formats::RawData(self.html_format.code()));
-		match buffer {
-			Ok(data) => {
-				let html_res = String::from_utf8(data);
-				if let Ok(html_full_str) = html_res {
-					let html = extract_html_from_clipboard_data(html_full_str.as_str());
-					if let Ok(html) = html {
-						return Ok(html);
-					}
-				}
-				Err("Get html error".into())
-			}
-			Err(e) => Err(format!("Get buffer error, code = {}", e).into()),
-		}
+		let _clip = self.open()?;
+		self.read_html(HtmlRange::Fragment)
+	}
+
+	fn get_html_full_document(&self) -> Result<String> {
+		let _clip = self.open()?;
+		self.read_html(HtmlRange::Document)
 	}
 
 	fn get_image(&self) -> Result<RustImageData> {
-		let cf_png_format = self.format_map.get(CF_PNG);
-		if cf_png_format.is_some() && clipboard_win::is_format_avail(*cf_png_format.unwrap()) {
-			let image_raw_data = self.get_buffer(CF_PNG)?;
-			RustImageData::from_bytes(&image_raw_data)
-		} else if clipboard_win::is_format_avail(formats::CF_DIBV5) {
-			let res = get_clipboard(formats::RawData(formats::CF_DIBV5));
-			match res {
-				Ok(data) => {
-					let decoder = {
-						// if data.as_slice().starts_with(b"BM") {
-						// 	BmpDecoder::new(Cursor::new(data.as_slice()))
-						// } else {
-						BmpDecoder::new_without_file_header(Cursor::new(data.as_slice()))
-						// }
-					};
-					let decoder = decoder.map_err(|e| format!("{}", e))?;
-					let dynamic_image =
-						DynamicImage::from_decoder(decoder).map_err(|e| format!("{}", e))?;
-					Ok(RustImageData::from_dynamic_image(dynamic_image))
-				}
-				Err(e) => Err(format!("Get image error, code = {}", e).into()),
-			}
-		} else if clipboard_win::is_format_avail(formats::CF_DIB) {
-			let res = get_clipboard(formats::Bitmap);
-			match res {
-				Ok(data) => RustImageData::from_bytes(&data),
-				Err(e) => Err(format!("Get image error, code = {}", e).into()),
+		let _clip = self.open()?;
+		self.read_image()
+	}
+
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		let _clip = self.open()?;
+		let cf_png_format = self
+			.format_map
+			.get(CF_PNG)
+			.filter(|format| clipboard_win::is_format_avail(**format));
+		if let Some(cf_png_format) = cf_png_format {
+			let data = get(formats::RawData(*cf_png_format))
+				.map_err(|e| format!("Get image error, code = {}", e))?;
+			return image_dimensions_from_bytes(&data);
+		}
+		for format in [formats::CF_DIBV5, formats::CF_DIB] {
+			if clipboard_win::is_format_avail(format) {
+				// only the beginning of the header is needed
+				let mut header = [0u8; 12];
+				let size = raw::get(format, &mut header)
+					.map_err(|e| format!("Get image error, code = {}", e))?;
+				let (width, height) = dib_dimensions(&header[..size])?;
+				return Ok((width, height, ImageFormat::Bmp));
 			}
-		} else {
-			Err("No image data in clipboard".into())
 		}
+		Err("No image data in clipboard".into())
 	}
 
 	fn get_files(&self) -> Result<Vec<String>> {
-		let files: SysResult<Vec<String>> = get_clipboard(formats::FileList);
+		let _clip = self.open()?;
+		let files: SysResult<Vec<String>> = get(formats::FileList);
 		match files {
 			Ok(f) => Ok(f),
 			Err(e) => Err(format!("Get files error, code = {}", e).into()),
 		}
 	}
 
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		let _clip = self.open()?;
+		let files: Vec<String> =
+			get(formats::FileList).map_err(|e| format!("Get files error, code = {}", e))?;
+		let effect = self
+			.format_map
+			.get(CF_PREFERRED_DROP_EFFECT)
+			.filter(|format| clipboard_win::is_format_avail(**format))
+			.and_then(|format| get(formats::RawData(*format)).ok())
+			.and_then(|data| Some(u32::from_le_bytes(data.get(..4)?.try_into().ok()?)));
+		let operation = match effect {
+			Some(effect) if effect & DROPEFFECT_MOVE != 0 => FileOperation::Cut,
+			_ => FileOperation::Copy,
+		};
+		Ok((operation, files))
+	}
+
 	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
-		let _clip = ClipboardWin::new_attempts(10)
-			.map_err(|code| format!("Open clipboard error, code = {}", code));
+		let _clip = self.open()?;
 		let mut res = Vec::new();
 		for format in formats {
 			match format {
@@ -261,22 +662,12 @@ impl Clipboard for ClipboardContext {
 					}
 				}
 				ContentFormat::Html => {
-					let html_buffer = get(formats::RawData(self.html_format.code()));
-					match html_buffer {
-						Ok(html) => {
-							let html_res = String::from_utf8(html);
-							if let Ok(html_full_str) = html_res {
-								let html = extract_html_from_clipboard_data(html_full_str.as_str());
-								if let Ok(html) = html {
-									res.push(ClipboardContent::Html(html));
-								}
-							}
-						}
-						Err(_) => continue,
+					if let Ok(html) = self.read_html(HtmlRange::Fragment) {
+						res.push(ClipboardContent::Html(html));
 					}
 				}
 				ContentFormat::Image => {
-					let img = self.get_image();
+					let img = self.read_image();
 					match img {
 						Ok(img) => {
 							res.push(ClipboardContent::Image(img));
@@ -284,6 +675,30 @@ impl Clipboard for ClipboardContext {
 						Err(_) => continue,
 					}
 				}
+				ContentFormat::Markdown => {
+					let format_uint = self.get_format(format);
+					let buffer = get(formats::RawData(format_uint));
+					match buffer {
+						Ok(buffer) => {
+							let markdown = String::from_utf8_lossy(&buffer);
+							res.push(ClipboardContent::Markdown(markdown.to_string()));
+						}
+						Err(_) => continue,
+					}
+				}
+				ContentFormat::Url => {
+					let format_uint = self.get_format(format);
+					let buffer = get(formats::RawData(format_uint));
+					match buffer {
+						Ok(buffer) => {
+							let url = String::from_utf8_lossy(&buffer);
+							res.push(ClipboardContent::Url(
+								url.trim_end_matches('\0').to_string(),
+							));
+						}
+						Err(_) => continue,
+					}
+				}
 				ContentFormat::Other(fmt) => {
 					let format_uint = self.get_format(format);
 					let buffer = get(formats::RawData(format_uint));
@@ -295,7 +710,7 @@ impl Clipboard for ClipboardContext {
 					}
 				}
 				ContentFormat::Files => {
-					let files = self.get_files();
+					let files: SysResult<Vec<String>> = get(formats::FileList);
 					match files {
 						Ok(files) => {
 							res.push(ClipboardContent::Files(files));
@@ -303,6 +718,12 @@ impl Clipboard for ClipboardContext {
 						Err(_) => continue,
 					}
 				}
+				ContentFormat::Metafile => match self.read_metafile() {
+					Ok(bits) => {
+						res.push(ClipboardContent::Other(CF_METAFILEPICT.to_string(), bits));
+					}
+					Err(_) => continue,
+				},
 			}
 		}
 		Ok(res)
@@ -314,16 +735,36 @@ impl Clipboard for ClipboardContext {
 			return Err("register format error".into());
 		}
 		let format_uint = format_uint.unwrap().get();
-		let res = set_clipboard(formats::RawData(format_uint), buffer);
+		let _clip = self.open()?;
+		let res = clipboard_win::set(formats::RawData(format_uint), buffer);
 		if res.is_err() {
 			return Err("set buffer error".into());
 		}
+		record_own_write();
 		Ok(())
 	}
 
 	fn set_text(&self, text: String) -> Result<()> {
-		let res = set_clipboard(formats::Unicode, text);
-		res.map_err(|e| format!("set text error, code = {}", e).into())
+		let _clip = self.open()?;
+		let res = clipboard_win::set(formats::Unicode, text);
+		res.map_err(|e| format!("set text error, code = {}", e))?;
+		record_own_write();
+		Ok(())
+	}
+
+	fn append_text_with_separator(&self, text: &str, separator: Option<&str>) -> Result<()> {
+		// read and write in a single session, so that no other program changes the clipboard in between
+		let _clip = self.open()?;
+		let current = if clipboard_win::is_format_avail(formats::CF_UNICODETEXT) {
+			let current: SysResult<String> = get(formats::Unicode);
+			current.map_err(|e| format!("Get text error, code = {}", e))?
+		} else {
+			String::new()
+		};
+		let res = clipboard_win::set(formats::Unicode, append_text_to(current, text, separator));
+		res.map_err(|e| format!("set text error, code = {}", e))?;
+		record_own_write();
+		Ok(())
 	}
 
 	fn set_rich_text(&self, text: String) -> Result<()> {
@@ -333,150 +774,630 @@ impl Clipboard for ClipboardContext {
 
 	fn set_html(&self, html: String) -> Result<()> {
 		let cf_html = plain_html_to_cf_html(&html);
-		let res = set_clipboard(
+		let _clip = self.open()?;
+		let res = clipboard_win::set(
 			formats::RawData(self.html_format.code()),
 			cf_html.as_bytes(),
 		);
-		res.map_err(|e| format!("set html error, code = {}", e).into())
+		res.map_err(|e| format!("set html error, code = {}", e))?;
+		record_own_write();
+		Ok(())
+	}
+
+	fn set_html_raw(&self, html: String) -> Result<()> {
+		let _clip = self.open()?;
+		let res = clipboard_win::set(formats::RawData(self.html_format.code()), html.as_bytes());
+		res.map_err(|e| format!("set html error, code = {}", e))?;
+		record_own_write();
+		Ok(())
 	}
 
 	fn set_image(&self, image: RustImageData) -> Result<()> {
-		let _clip = ClipboardWin::new_attempts(10)
-			.map_err(|code| format!("Open clipboard error, code = {}", code));
+		let _clip = self.open()?;
 		let res = clipboard_win::empty();
 		if let Err(e) = res {
 			return Err(format!("Empty clipboard error, code = {}", e).into());
 		}
-		// chromium source code
-		// @link {https://source.chromium.org/chromium/chromium/src/+/main:ui/base/clipboard/clipboard_win.cc;l=771;drc=2a5aaed0ff3a0895c8551495c2656ed49baf742c;bpv=0;bpt=1}
-		let cf_png_format = self.format_map.get(CF_PNG);
-		if cf_png_format.is_some() {
-			let png = image.to_png()?;
-			let write_png_res = set_without_clear(*cf_png_format.unwrap(), png.get_bytes());
-			if let Err(e) = write_png_res {
-				return Err(format!("set png image error, code = {}", e).into());
-			}
+		self.write_image(&image)?;
+		record_own_write();
+		Ok(())
+	}
+
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		let bytes = std::fs::read(path)?;
+		let image = RustImageData::from_bytes(&bytes)?;
+		let _clip = self.open()?;
+		let res = clipboard_win::empty();
+		if let Err(e) = res {
+			return Err(format!("Empty clipboard error, code = {}", e).into());
 		}
-		let bmp = image
-			.to_bitmap()
-			.map_err(|e| format!("to bitmap error, code = {}", e))?;
-		let res = set_bitmap_with(bmp.get_bytes(), options::NoClear);
-		res.map_err(|e| format!("set image error, code = {}", e).into())
+		if is_png(&bytes) {
+			// the PNG data of the file is written as is, only the bitmap is encoded
+			self.write_png(&bytes)?;
+			self.write_bitmap(&image)?;
+		} else {
+			self.write_image(&image)?;
+		}
+		record_own_write();
+		Ok(())
 	}
 
 	fn set_files(&self, files: Vec<String>) -> Result<()> {
-		let _clip = ClipboardWin::new_attempts(10)
-			.map_err(|code| format!("Open clipboard error, code = {}", code));
+		let _clip = self.open()?;
 		let res = set_file_list_with(&files, options::DoClear);
-		res.map_err(|e| format!("set files error, code = {}", e).into())
+		res.map_err(|e| format!("set files error, code = {}", e))?;
+		record_own_write();
+		Ok(())
 	}
 
 	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
-		let _clip = ClipboardWin::new_attempts(10)
-			.map_err(|code| format!("Open clipboard error, code = {}", code));
+		let _clip = self.open()?;
 		let res = clipboard_win::empty();
 		if let Err(e) = res {
 			return Err(format!("Empty clipboard error, code = {}", e).into());
 		}
+		let total = contents.len();
+		let mut failed = Vec::new();
 		for content in contents {
-			match content {
-				ClipboardContent::Text(txt) => {
-					let res = set_string_with(txt.as_str(), options::NoClear);
-					if res.is_err() {
-						continue;
-					}
-				}
+			let res: Result<()> = match &content {
+				ClipboardContent::Text(txt) => set_string_with(txt.as_str(), options::NoClear)
+					.map_err(|e| format!("code = {}", e).into()),
 				ClipboardContent::Html(html) => {
-					let format_uint_html = self.html_format.code();
-					let res = set_without_clear(format_uint_html, html.as_bytes());
-					if res.is_err() {
-						continue;
-					}
-				}
-				ClipboardContent::Image(img) => {
-					// set image will clear clipboard
-					let res = self.set_image(img);
-					if res.is_err() {
-						continue;
-					}
+					let cf_html = plain_html_to_cf_html(html);
+					set_without_clear(self.html_format.code(), cf_html.as_bytes())
+						.map_err(|e| format!("code = {}", e).into())
 				}
-				ClipboardContent::Rtf(_) | ClipboardContent::Other(_, _) => {
+				// the clipboard has been emptied above, so writing the image must not clear it again
+				ClipboardContent::Image(img) => self.write_image(img),
+				ClipboardContent::Rtf(_)
+				| ClipboardContent::Markdown(_)
+				| ClipboardContent::Other(_, _) => {
 					let format_uint = self.get_format(&content.get_format());
-					let res = set_without_clear(format_uint, content.as_bytes());
-					if res.is_err() {
-						continue;
-					}
+					set_without_clear(format_uint, content.as_bytes())
+						.map_err(|e| format!("code = {}", e).into())
 				}
 				ClipboardContent::Files(file_list) => {
-					let res = set_file_list_with(&file_list, options::NoClear);
-					if res.is_err() {
-						continue;
+					set_file_list_with(file_list, options::NoClear)
+						.map_err(|e| format!("code = {}", e).into())
+				}
+				ClipboardContent::FilesWithOperation(file_list, operation) => {
+					set_file_list_with(file_list, options::NoClear)
+						.map_err(|e| format!("code = {}", e).into())
+						.and_then(|_| self.write_drop_effect(*operation))
+				}
+				ClipboardContent::Url(url) => {
+					let format_uint = self.get_format(&ContentFormat::Url);
+					set_without_clear(format_uint, &url_to_cf_url(url))
+						.map_err(|e| format!("code = {}", e).into())
+				}
+			};
+			if let Err(e) = res {
+				let name = match &content {
+					ClipboardContent::Text(_) => "Text",
+					ClipboardContent::Rtf(_) => "Rtf",
+					ClipboardContent::Html(_) => "Html",
+					ClipboardContent::Image(_) => "Image",
+					ClipboardContent::Files(_) | ClipboardContent::FilesWithOperation(_, _) => {
+						"Files"
 					}
+					ClipboardContent::Markdown(_) => "Markdown",
+					ClipboardContent::Url(_) => "Url",
+					ClipboardContent::Other(format, _) => format.as_str(),
+				};
+				failed.push(format!("{}: {}", name, e));
+			}
+		}
+		// the clipboard has been emptied even if some contents failed
+		record_own_write();
+		if !failed.is_empty() {
+			return Err(format!(
+				"set clipboard error, {} of {} contents failed: {}",
+				failed.len(),
+				total,
+				failed.join("; ")
+			)
+			.into());
+		}
+		Ok(())
+	}
+
+	fn set_with_lazy<F>(&self, formats: Vec<ContentFormat>, provider: F) -> Result<()>
+	where
+		F: Fn(ContentFormat) -> Option<Vec<u8>> + Send + 'static,
+	{
+		let mut announced = Vec::new();
+		for format in formats {
+			let codes = match &format {
+				ContentFormat::Image => {
+					let mut codes = vec![formats::CF_DIB];
+					codes.extend(self.format_map.get(CF_PNG));
+					codes
+				}
+				ContentFormat::Metafile => {
+					return Err("Metafile can not be rendered lazily".into());
+				}
+				ContentFormat::Other(name) => match clipboard_win::register_format(name) {
+					Some(code) => vec![code.get()],
+					None => return Err("register format error".into()),
+				},
+				format => vec![self.get_format(format)],
+			};
+			announced.extend(codes.into_iter().map(|code| (code, format.clone())));
+		}
+		let renderer = LazyRenderer {
+			formats: announced,
+			provider: Box::new(provider),
+			html_format: self.html_format.code(),
+			png_format: self.format_map.get(CF_PNG).copied(),
+		};
+		let open_retries = self.open_retries;
+		let retry_delay = self.retry_delay;
+		let (ready_tx, ready_rx) = mpsc::channel();
+		// the owner window must live on its own thread to receive WM_RENDERFORMAT until the ownership is lost
+		thread::spawn(move || unsafe {
+			let hwnd = match create_lazy_window() {
+				Ok(hwnd) => hwnd,
+				Err(e) => {
+					let _ = ready_tx.send(Err(e));
+					return;
 				}
+			};
+			let res = announce_lazy_formats(hwnd, &renderer, open_retries, retry_delay);
+			let announced = res.is_ok();
+			if announced {
+				record_own_write();
 			}
+			let _ = ready_tx.send(res);
+			if announced {
+				LAZY_RENDERER.with(|r| *r.borrow_mut() = Some(renderer));
+				let mut msg: Msg = mem::zeroed();
+				while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {
+					DispatchMessageW(&msg);
+				}
+				LAZY_RENDERER.with(|r| r.borrow_mut().take());
+			}
+			DestroyWindow(hwnd);
+		});
+		ready_rx
+			.recv()
+			.map_err(|_| "the lazy rendering thread exited unexpectedly")?
+	}
+
+	fn snapshot(&self) -> Result<ClipboardSnapshot> {
+		let _clip = self.open()?;
+		let mut item = Vec::new();
+		for format in clipboard_win::raw::EnumFormats::new() {
+			if is_handle_format(format) {
+				continue;
+			}
+			let mut buffer = Vec::new();
+			if raw::get_vec(format, &mut buffer).is_ok() {
+				let info = FormatInfo {
+					name: raw::format_name_big(format),
+					id: format,
+				};
+				item.push((info, buffer));
+			}
+		}
+		Ok(ClipboardSnapshot { items: vec![item] })
+	}
+
+	fn restore(&self, snapshot: ClipboardSnapshot) -> Result<()> {
+		let _clip = self.open()?;
+		if let Err(e) = clipboard_win::empty() {
+			return Err(format!("Empty clipboard error, code = {}", e).into());
+		}
+		// the format numbers are shared by the whole session, so they can be written back directly
+		for (info, buffer) in snapshot.items.into_iter().flatten() {
+			set_without_clear(info.id, &buffer)
+				.map_err(|e| format!("restore format {} error, code = {}", info.id, e))?;
 		}
+		record_own_write();
 		Ok(())
 	}
+
+	fn get_sequence_number(&self) -> Result<u64> {
+		// GetClipboardSequenceNumber returns zero if we do not have WINSTA_ACCESSCLIPBOARD access to the window station
+		match clipboard_win::seq_num() {
+			Some(num) => Ok(num.get() as u64),
+			None => Err("Get clipboard sequence number error".into()),
+		}
+	}
 }
 
 impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
 	fn add_handler(&mut self, f: T) -> &mut Self {
-		self.handlers.push(f);
+		self.handlers.push(HandlerEntry::new(f, None));
 		self
 	}
 
+	fn add_filtered_handler(&mut self, handler: T, formats: Vec<ContentFormat>) -> &mut Self {
+		self.handlers
+			.push(HandlerEntry::new(handler, Some(formats)));
+		self
+	}
+
+	fn add_named_handler(&mut self, name: &str, handler: T) -> &mut Self {
+		insert_named_handler(&mut self.handlers, name, handler);
+		self
+	}
+
+	fn remove_handler(&mut self, index: usize) -> Option<T> {
+		take_handler_at(&mut self.handlers, index)
+	}
+
+	fn remove_named_handler(&mut self, name: &str) -> Option<T> {
+		take_named_handler(&mut self.handlers, name)
+	}
+
 	fn start_watch(&mut self) {
 		if self.running {
 			println!("already start watch!");
 			return;
 		}
-		if self.handlers.is_empty() {
+		if self.handlers.is_empty() && self.subscribers.is_empty() {
 			println!("no handler, no need to start watch!");
 			return;
 		}
 		self.running = true;
-		let mut monitor = Monitor::new().expect("create monitor error");
-		let shutdown = monitor.shutdown_channel();
+		// drain the stop signals left over from a previous session (e.g. `WatcherShutdown::stop` called while the watcher was not running),
+		// so that the watcher can be restarted after it has been stopped
+		while self.stop_receiver.try_recv().is_ok() {}
+		self.monitor_retry.reset();
+		let mut monitor = match create_monitor() {
+			Ok(monitor) => monitor,
+			Err(e) => {
+				self.report_watch_error(e);
+				self.running = false;
+				let _ = self.done_signal.send(());
+				return;
+			}
+		};
+		let mut shutdown = monitor.shutdown_channel();
+		let mut last_event = None;
 		loop {
 			if self.stop_receiver.try_recv().is_ok() {
 				break;
 			}
-			let msg = monitor.try_recv();
-			match msg {
-				Ok(true) => {
-					self.handlers.iter_mut().for_each(|f| {
-						f.on_clipboard_change();
-					});
-				}
-				Ok(false) => {
-					// no change
-					thread::park_timeout(Duration::from_millis(200));
-					continue;
+			let changed = match monitor.try_recv() {
+				Ok(changed) => {
+					self.monitor_retry.reset();
+					changed
 				}
 				Err(e) => {
-					eprintln!("watch error, code = {}", e);
-					break;
+					let error = format!("watch error, code = {}", e).into();
+					match self.monitor_retry.recover(error, create_monitor) {
+						Ok(new_monitor) => {
+							monitor = new_monitor;
+							shutdown = monitor.shutdown_channel();
+							continue;
+						}
+						Err(e) => {
+							self.report_watch_error(e);
+							break;
+						}
+					}
+				}
+			};
+			if changed {
+				let mut event = ClipboardEvent::new(None);
+				event.source = change_source();
+				last_event = Some(event);
+			}
+			if self.throttle.should_dispatch(changed, Instant::now()) {
+				if let Some(event) = last_event.take() {
+					dispatch_to_handlers(&mut self.handlers, &self.clipboard, &event, |format| {
+						self.clipboard.has(format.clone())
+					});
+					notify_subscribers(&mut self.subscribers, &event);
 				}
 			}
+			if !changed {
+				// no change
+				wait_for_message(Duration::from_millis(200));
+			}
 		}
 		drop(shutdown);
 		self.running = false;
+		let _ = self.done_signal.send(());
 	}
 
 	fn get_shutdown_channel(&self) -> WatcherShutdown {
-		WatcherShutdown {
-			stop_signal: self.stop_signal.clone(),
+		WatcherShutdown::new(
+			self.stop_signal.clone(),
+			self.done_receiver.clone(),
+			self.stop_on_drop,
+		)
+	}
+}
+
+impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
+	fn report_watch_error(&mut self, error: Box<dyn std::error::Error + Send + Sync>) {
+		self.handlers.iter_mut().for_each(|entry| {
+			entry.handler.on_watch_error(&*error);
+		});
+	}
+}
+
+// formats whose data is a GDI or private handle instead of global memory, they can not be copied as bytes
+fn is_handle_format(format: c_uint) -> bool {
+	matches!(
+		format,
+		formats::CF_BITMAP
+			| formats::CF_METAFILEPICT
+			| formats::CF_PALETTE
+			| formats::CF_ENHMETAFILE
+			| formats::CF_OWNERDISPLAY
+			| formats::CF_DSPBITMAP
+			| formats::CF_DSPMETAFILEPICT
+			| formats::CF_DSPENHMETAFILE
+			| formats::CF_PRIVATEFIRST..=formats::CF_PRIVATELAST
+			| formats::CF_GDIOBJFIRST..=formats::CF_GDIOBJLAST
+	)
+}
+
+// remember the sequence number after a write of this process, the clipboard may still be open
+fn record_own_write() {
+	if let Some(num) = clipboard_win::seq_num() {
+		LAST_OWN_WRITE.store(num.get() as u64, Ordering::SeqCst);
+	}
+}
+
+// the clipboard was last written by this process if nothing changed since our last write
+fn change_source() -> ChangeSource {
+	match clipboard_win::seq_num() {
+		Some(num) if num.get() as u64 == LAST_OWN_WRITE.load(Ordering::SeqCst) => {
+			ChangeSource::OwnWrite
+		}
+		_ => ChangeSource::ExternalChange,
+	}
+}
+
+// Block until a message arrives in the queue of this thread or the timeout elapses, so that
+// clipboard updates are picked up right away instead of at the next poll. Every watcher owns
+// its own monitor window on its own thread, which makes concurrent watchers independent.
+fn wait_for_message(timeout: Duration) {
+	unsafe {
+		MsgWaitForMultipleObjects(0, ptr::null(), 0, timeout.as_millis() as u32, QS_ALLINPUT);
+	}
+}
+
+// 延迟写入的格式及其数据生成函数，保存在拥有剪切板的窗口线程中
+struct LazyRenderer {
+	formats: Vec<(c_uint, ContentFormat)>,
+	provider: Box<dyn Fn(ContentFormat) -> Option<Vec<u8>> + Send>,
+	html_format: c_uint,
+	png_format: Option<c_uint>,
+}
+
+impl LazyRenderer {
+	/// place the data of `code` on the clipboard, the clipboard must be opened (it is during WM_RENDERFORMAT)
+	fn render(&self, code: c_uint) -> Result<()> {
+		let Some((_, format)) = self.formats.iter().find(|(c, _)| *c == code) else {
+			return Ok(());
+		};
+		let Some(bytes) = (self.provider)(format.clone()) else {
+			return Ok(());
+		};
+		let res = match content_from_bytes(format.clone(), bytes)? {
+			Some(ClipboardContent::Text(text)) => set_string_with(&text, options::NoClear),
+			Some(ClipboardContent::Html(html)) => {
+				set_without_clear(self.html_format, plain_html_to_cf_html(&html).as_bytes())
+			}
+			Some(ClipboardContent::Image(image)) if Some(code) == self.png_format => {
+				set_without_clear(code, image.to_png()?.get_bytes())
+			}
+			Some(ClipboardContent::Image(image)) => {
+				// CF_DIB is a bitmap without the 14 bytes BITMAPFILEHEADER
+				let bmp = image.to_bitmap()?;
+				set_without_clear(code, &bmp.get_bytes()[14..])
+			}
+			Some(ClipboardContent::Files(files))
+			| Some(ClipboardContent::FilesWithOperation(files, _)) => {
+				set_file_list_with(&files, options::NoClear)
+			}
+			Some(ClipboardContent::Rtf(rtf)) => set_without_clear(code, rtf.as_bytes()),
+			Some(ClipboardContent::Markdown(markdown)) => {
+				set_without_clear(code, markdown.as_bytes())
+			}
+			Some(ClipboardContent::Url(url)) => set_without_clear(code, &url_to_cf_url(&url)),
+			Some(ClipboardContent::Other(_, buffer)) => set_without_clear(code, &buffer),
+			None => return Ok(()),
+		};
+		res.map_err(|e| format!("render format {} error, code = {}", code, e).into())
+	}
+}
+
+thread_local! {
+	static LAZY_RENDERER: RefCell<Option<LazyRenderer>> = const { RefCell::new(None) };
+}
+
+// the executable file name without the extension of the process
+fn process_name(pid: u32) -> Option<String> {
+	unsafe {
+		let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+		if process.is_null() {
+			return None;
+		}
+		let mut buffer = [0u16; 1024];
+		let mut size = buffer.len() as u32;
+		let ok = QueryFullProcessImageNameW(process, 0, buffer.as_mut_ptr(), &mut size);
+		CloseHandle(process);
+		if ok == 0 {
+			return None;
 		}
+		let path = PathBuf::from(String::from_utf16_lossy(&buffer[..size as usize]));
+		path.file_stem()
+			.map(|name| name.to_string_lossy().into_owned())
 	}
 }
 
-impl Drop for WatcherShutdown {
-	fn drop(&mut self) {
-		let _ = self.stop_signal.send(());
+unsafe extern "system" fn lazy_window_proc(
+	hwnd: *mut c_void,
+	msg: u32,
+	wparam: usize,
+	lparam: isize,
+) -> isize {
+	match msg {
+		// a format that fails to render is left out, the window procedure has no caller to report it to
+		WM_RENDERFORMAT => {
+			LAZY_RENDERER.with(|r| {
+				if let Some(renderer) = &*r.borrow() {
+					let _ = renderer.render(wparam as c_uint);
+				}
+			});
+			0
+		}
+		// the owner is going away while still holding the clipboard, render everything now
+		WM_RENDERALLFORMATS => {
+			if raw::open_for(hwnd).is_ok() {
+				if GetClipboardOwner() == hwnd {
+					LAZY_RENDERER.with(|r| {
+						if let Some(renderer) = &*r.borrow() {
+							for (code, _) in &renderer.formats {
+								let _ = renderer.render(*code);
+							}
+						}
+					});
+				}
+				let _ = raw::close();
+			}
+			0
+		}
+		// another program took the clipboard, the data will never be requested again
+		WM_DESTROYCLIPBOARD => {
+			PostQuitMessage(0);
+			0
+		}
+		_ => DefWindowProcW(hwnd, msg, wparam, lparam),
+	}
+}
+
+unsafe fn create_lazy_window() -> Result<*mut c_void> {
+	static REGISTER_CLASS: Once = Once::new();
+	let class_name: Vec<u16> = "clipboard-rs-lazy-render\0".encode_utf16().collect();
+	let instance = GetModuleHandleW(ptr::null());
+	REGISTER_CLASS.call_once(|| {
+		let class = WndClassW {
+			style: 0,
+			wnd_proc: Some(lazy_window_proc),
+			cls_extra: 0,
+			wnd_extra: 0,
+			instance,
+			icon: ptr::null_mut(),
+			cursor: ptr::null_mut(),
+			background: ptr::null_mut(),
+			menu_name: ptr::null(),
+			class_name: class_name.as_ptr(),
+		};
+		RegisterClassW(&class);
+	});
+	let hwnd = CreateWindowExW(
+		0,
+		class_name.as_ptr(),
+		ptr::null(),
+		0,
+		0,
+		0,
+		0,
+		0,
+		HWND_MESSAGE as *mut c_void,
+		ptr::null_mut(),
+		instance,
+		ptr::null_mut(),
+	);
+	if hwnd.is_null() {
+		return Err("create lazy rendering window error".into());
+	}
+	Ok(hwnd)
+}
+
+// take the ownership of the clipboard with `hwnd` and announce the formats without data
+unsafe fn announce_lazy_formats(
+	hwnd: *mut c_void,
+	renderer: &LazyRenderer,
+	open_retries: u32,
+	retry_delay: Duration,
+) -> Result<()> {
+	let mut retries = 0;
+	while let Err(code) = raw::open_for(hwnd) {
+		if retries >= open_retries {
+			return Err(format!(
+				"Open clipboard error after {} attempts, code = {}",
+				retries + 1,
+				code
+			)
+			.into());
+		}
+		retries += 1;
+		thread::sleep(retry_delay);
+	}
+	let res = match raw::empty() {
+		Ok(()) => {
+			for (code, _) in &renderer.formats {
+				SetClipboardData(*code, ptr::null_mut());
+			}
+			Ok(())
+		}
+		Err(e) => Err(format!("Empty clipboard error, code = {}", e).into()),
+	};
+	let _ = raw::close();
+	res
+}
+
+fn create_monitor() -> Result<Monitor> {
+	Monitor::new().map_err(|e| format!("create monitor error, code = {}", e).into())
+}
+
+/// Tracks the consecutive failures of the clipboard monitor, so that a transient failure
+/// (e.g. the hidden monitor window being destroyed during a session switch) does not stop the watcher.
+struct MonitorRetry {
+	max_retries: u32,
+	retry_delay: Duration,
+	failures: u32,
+}
+
+impl Default for MonitorRetry {
+	fn default() -> Self {
+		MonitorRetry {
+			max_retries: 5,
+			retry_delay: Duration::from_millis(100),
+			failures: 0,
+		}
 	}
 }
 
-/// 将输入的 UTF-8 字符串转换为宽字符（UTF-16）字符串
+impl MonitorRetry {
+	fn reset(&mut self) {
+		self.failures = 0;
+	}
+
+	/// Recreate the monitor after `error` with exponential backoff, giving up once `max_retries`
+	/// consecutive failures are reached. The error returned then carries the last failure.
+	fn recover<M>(
+		&mut self,
+		mut error: Box<dyn std::error::Error + Send + Sync>,
+		mut create: impl FnMut() -> Result<M>,
+	) -> Result<M> {
+		loop {
+			if self.failures >= self.max_retries {
+				return Err(format!(
+					"clipboard monitor failed {} times in a row, giving up: {}",
+					self.failures, error
+				)
+				.into());
+			}
+			thread::sleep(self.retry_delay.saturating_mul(1 << self.failures.min(16)));
+			self.failures += 1;
+			match create() {
+				Ok(monitor) => return Ok(monitor),
+				Err(e) => error = e,
+			}
+		}
+	}
+}
+
+// 将输入的 UTF-8 字符串转换为宽字符（UTF-16）字符串
 // fn utf8_to_utf16(input: &str) -> Vec<u16> {
 // 	let mut vec: Vec<u16> = input.encode_utf16().collect();
 // 	vec.push(0);
@@ -560,51 +1481,176 @@ fn plain_html_to_cf_html(fragment: &str) -> String {
 	buffer
 }
 
+// UniformResourceLocator holds the URL as a NUL terminated string
+fn url_to_cf_url(url: &str) -> Vec<u8> {
+	let mut data = url.as_bytes().to_vec();
+	data.push(0);
+	data
+}
+
+// read the size from a BITMAPINFOHEADER (or BITMAPV4/V5HEADER) or the old BITMAPCOREHEADER
+// https://learn.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-bitmapv5header
+fn dib_dimensions(header: &[u8]) -> Result<(u32, u32)> {
+	let field = |range: std::ops::Range<usize>| header.get(range).ok_or("DIB header is too short");
+	let header_size = u32::from_le_bytes(field(0..4)?.try_into()?);
+	if header_size == 12 {
+		let width = u16::from_le_bytes(field(4..6)?.try_into()?);
+		let height = u16::from_le_bytes(field(6..8)?.try_into()?);
+		return Ok((width.into(), height.into()));
+	}
+	let width = i32::from_le_bytes(field(4..8)?.try_into()?);
+	// a negative height means a top-down bitmap
+	let height = i32::from_le_bytes(field(8..12)?.try_into()?);
+	Ok((width.unsigned_abs(), height.unsigned_abs()))
+}
+
 const SEP: char = ':';
 const START_HTML: &str = "StartHTML";
 const END_HTML: &str = "EndHTML";
+const START_FRAGMENT: &str = "StartFragment";
+const END_FRAGMENT: &str = "EndFragment";
+
+// the part of the CF_HTML data to extract
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HtmlRange {
+	// StartFragment..EndFragment, the content the user copied
+	Fragment,
+	// StartHTML..EndHTML, the whole document including the <html> and <body> wrapping
+	Document,
+}
 
-fn extract_html_from_clipboard_data(data: &str) -> Result<String> {
-	let mut start_idx = 0usize;
-	let mut end_idx = data.len();
+// https://learn.microsoft.com/en-us/windows/win32/dataxchg/html-clipboard-format
+fn extract_html_from_clipboard_data(data: &str, range: HtmlRange) -> Result<String> {
+	let mut start_html = None;
+	let mut end_html = None;
+	let mut start_fragment = None;
+	let mut end_fragment = None;
 	for line in data.lines() {
-		let mut split = line.split(SEP);
-		let key = match split.next() {
-			Some(key) => key,
+		//Reached HTML
+		if line.starts_with('<') {
+			break;
+		}
+		let (key, value) = match line.split_once(SEP) {
+			Some(pair) => pair,
 			None => break,
 		};
-		let value = match split.next() {
-			Some(value) => value,
-			//Reached HTML
-			None => break,
+		// offsets are byte counts, `-1` means the offset is absent (e.g. StartHTML when there is no context)
+		let offset = match value.trim().parse::<i64>() {
+			Ok(offset) => usize::try_from(offset).ok(),
+			//Should not really happen
+			Err(_) => continue,
 		};
 		match key {
-			START_HTML => match value.trim_start_matches('0').parse() {
-				Ok(value) => {
-					start_idx = value;
-					continue;
-				}
-				//Should not really happen
-				Err(_) => break,
-			},
-			END_HTML => match value.trim_start_matches('0').parse() {
-				Ok(value) => {
-					end_idx = value;
-					continue;
-				}
-				//Should not really happen
-				Err(_) => break,
-			},
+			START_HTML => start_html = offset,
+			END_HTML => end_html = offset,
+			START_FRAGMENT => start_fragment = offset,
+			END_FRAGMENT => end_fragment = offset,
 			_ => continue,
 		}
 	}
+	let fragment = start_fragment.zip(end_fragment);
+	let document = start_html.zip(end_html);
+	let (start_idx, end_idx) = match range {
+		HtmlRange::Fragment => fragment.or(document),
+		HtmlRange::Document => document.or(fragment),
+	}
+	.unwrap_or((0, data.len()));
 	//Make sure HTML writer didn't screw up offsets of fragment
-	let size = match end_idx.checked_sub(start_idx) {
-		Some(size) => size,
-		None => return Err("Invalid HTML offsets".into()),
-	};
-	if size > data.len() {
+	if start_idx > end_idx || end_idx > data.len() {
 		return Err("Invalid HTML offsets".into());
-	};
-	Ok(data[start_idx..end_idx].to_string())
+	}
+	match data.get(start_idx..end_idx) {
+		Some(html) => Ok(html.to_string()),
+		None => Err("Invalid HTML offsets".into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn retry(max_retries: u32) -> MonitorRetry {
+		MonitorRetry {
+			max_retries,
+			retry_delay: Duration::from_millis(1),
+			failures: 0,
+		}
+	}
+
+	#[test]
+	fn test_monitor_recovers_after_one_failure() {
+		let mut retry = retry(3);
+		let mut attempts = 0;
+		let monitor = retry.recover("fake watch error".into(), || {
+			attempts += 1;
+			if attempts == 1 {
+				Err("fake monitor error".into())
+			} else {
+				Ok(attempts)
+			}
+		});
+		assert_eq!(monitor.unwrap(), 2);
+		assert_eq!(retry.failures, 2);
+		retry.reset();
+		assert_eq!(retry.failures, 0);
+	}
+
+	#[test]
+	fn test_monitor_gives_up_after_max_retries() {
+		let mut retry = retry(3);
+		let mut attempts = 0;
+		let monitor: Result<()> = retry.recover("fake watch error".into(), || {
+			attempts += 1;
+			Err(format!("fake monitor error {}", attempts).into())
+		});
+		// the last failure is returned to the watcher instead of being printed
+		let error = monitor.unwrap_err().to_string();
+		assert!(error.contains("fake monitor error 3"), "{}", error);
+		assert_eq!(attempts, 3);
+	}
+
+	#[test]
+	fn test_extract_html_fragment_and_document() {
+		let data = plain_html_to_cf_html("<b>bold</b>");
+		let fragment = extract_html_from_clipboard_data(&data, HtmlRange::Fragment).unwrap();
+		assert_eq!(fragment, "<b>bold</b>");
+		let document = extract_html_from_clipboard_data(&data, HtmlRange::Document).unwrap();
+		assert!(document.starts_with("<html>"));
+		assert!(document.ends_with("</html>"));
+		assert!(document.contains("<b>bold</b>"));
+	}
+
+	#[test]
+	fn test_extract_html_without_context() {
+		let header = "Version:0.9\r\nStartHTML:-1\r\nEndHTML:-1\r\nStartFragment:0000000089\r\nEndFragment:0000000100\r\n";
+		assert_eq!(header.len(), 89);
+		let data = format!("{}<b>bold</b>", header);
+		let fragment = extract_html_from_clipboard_data(&data, HtmlRange::Fragment).unwrap();
+		assert_eq!(fragment, "<b>bold</b>");
+		// the fragment is the whole document when there is no context
+		let document = extract_html_from_clipboard_data(&data, HtmlRange::Document).unwrap();
+		assert_eq!(document, "<b>bold</b>");
+	}
+
+	#[test]
+	fn test_dib_dimensions() {
+		let mut header = 124u32.to_le_bytes().to_vec();
+		header.extend_from_slice(&1920i32.to_le_bytes());
+		header.extend_from_slice(&(-1080i32).to_le_bytes());
+		assert_eq!(dib_dimensions(&header).unwrap(), (1920, 1080));
+
+		let mut core = 12u32.to_le_bytes().to_vec();
+		core.extend_from_slice(&640u16.to_le_bytes());
+		core.extend_from_slice(&480u16.to_le_bytes());
+		assert_eq!(dib_dimensions(&core).unwrap(), (640, 480));
+
+		assert!(dib_dimensions(&header[..6]).is_err());
+	}
+
+	#[test]
+	fn test_extract_html_invalid_offsets() {
+		let data =
+			"Version:0.9\r\nStartFragment:0000000100\r\nEndFragment:0000000050\r\n<b>bold</b>";
+		assert!(extract_html_from_clipboard_data(data, HtmlRange::Fragment).is_err());
+	}
 }
@@ -1,21 +1,40 @@
+use crate::builder::DEFAULT_READ_TIMEOUT;
 use crate::{
-	common::{Result, RustImage},
-	ClipboardContent, ClipboardHandler, ContentFormat, RustImageData,
+	common::{
+		dispatch_to_handlers, image_dimensions_from_bytes, insert_named_handler, is_png,
+		notify_subscribers, take_handler_at, take_named_handler, ChangeSource, ClipboardSnapshot,
+		EventThrottle, FileOperation, FormatInfo, HandlerEntry, Result, RustImage, WatcherShutdown,
+		XSelection,
+	},
+	ClipboardContent, ClipboardContextBuilder, ClipboardError, ClipboardEvent, ClipboardHandler,
+	ContentFormat, RustImageData,
 };
 use crate::{Clipboard, ClipboardWatcher};
+use image::ImageFormat;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::os::fd::AsFd;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::{
-	sync::{Arc, RwLock},
+	borrow::Cow,
+	collections::HashMap,
+	io::Write,
+	sync::{
+		atomic::{AtomicU32, AtomicU64, Ordering},
+		Arc, Mutex, RwLock,
+	},
 	thread,
 	time::{Duration, Instant},
 };
 use x11rb::{
-	connection::Connection,
+	connection::{Connection, RequestConnection},
+	errors::{ConnectionError, ReplyError, ReplyOrIdError},
 	protocol::{
 		xfixes,
 		xproto::{
-			Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
-			SelectionNotifyEvent, SelectionRequestEvent, WindowClass, SELECTION_NOTIFY_EVENT,
+			Atom, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt as _,
+			CreateWindowAux, EventMask, GetPropertyReply, PropMode, Property, SelectionNotifyEvent,
+			SelectionRequestEvent, Timestamp, Window, WindowClass, SELECTION_NOTIFY_EVENT,
 		},
 		Event,
 	},
@@ -35,6 +54,11 @@ x11rb::atom_manager! {
 		INCR,
 		TIMESTAMP,
 		MULTIPLE,
+		ATOM_PAIR,
+		// appended to on our own window to get a server timestamp from the PropertyNotify event
+		TIMESTAMP_PROP: b"_CLIPBOARD_RS_TIMESTAMP",
+		// sent to our own window to stop the thread serving the selection
+		SHUTDOWN: b"_CLIPBOARD_RS_SHUTDOWN",
 
 		UTF8_STRING,
 		UTF8_MIME_0: b"text/plain;charset=utf-8",
@@ -50,27 +74,139 @@ x11rb::atom_manager! {
 		RTF: b"text/rtf",
 		RTF_1: b"text/richtext",
 		HTML: b"text/html",
+		HTML_UTF8: b"text/html;charset=utf-8",
+		MARKDOWN: b"text/markdown",
+		MARKDOWN_X: b"text/x-markdown",
+		// UTF-16 text with the URL and the title on separate lines, written by Firefox
+		MOZ_URL: b"text/x-moz-url",
 		PNG_MIME: b"image/png",
+		TIFF_MIME: b"image/tiff",
+		JPEG_MIME: b"image/jpeg",
+		BMP_MIME: b"image/bmp",
+		BMP_X_MIME: b"image/x-bmp",
+		BMP_MS_MIME: b"image/x-MS-bmp",
+		WEBP_MIME: b"image/webp",
 		FILE_LIST: b"text/uri-list",
 		GNOME_COPY_FILES: b"x-special/gnome-copied-files",
 		NAUTILUS_FILE_LIST: b"x-special/nautilus-clipboard",
+		KDE_CUT_SELECTION: b"application/x-kde-cutselection",
 	}
 }
 
-pub const DEFAULT_READ_TIMEOUT: u64 = 500;
+// 超过该大小的数据通过 INCR 协议分块发送
+const INCR_CHUNK_SIZE: usize = 256 * 1024;
+
+// TARGETS 缓存的有效期，选区所有者变化时缓存会立即失效
+const TARGETS_CACHE_TTL: Duration = Duration::from_millis(50);
+
+// 默认的等待连接可读的最长时间（毫秒），防止事件已被其他线程读入连接的队列时一直等待
+const DEFAULT_POLL_INTERVAL: u64 = 250;
+
+// 等待剪贴板管理器保存数据的超时
+const SAVE_TARGETS_TIMEOUT: Duration = Duration::from_secs(2);
+
+// 默认的取得所有权重试间隔（毫秒）
+const DEFAULT_OWNERSHIP_RETRY_DELAY: u64 = 20;
+
+// 默认的重新连接尝试次数
+const DEFAULT_RECONNECT_RETRIES: u32 = 3;
+
+// 默认的重新连接尝试间隔（毫秒）
+const DEFAULT_RECONNECT_DELAY: u64 = 200;
+
+// 默认的写入超时（毫秒）
+const DEFAULT_WRITE_TIMEOUT: u64 = 1000;
+
+// 本进程中用来持有选区的窗口，监听器据此判断变化是否来自本进程
+static OWN_WINDOWS: Mutex<Vec<Window>> = Mutex::new(Vec::new());
+
+// zh: 读取进度回调，参数为已接收的字节数和预计的总字节数（来自 INCR 属性，可能未知）
+// en: Read progress callback, called with the bytes received so far and the expected total (from the INCR property, may be unknown)
+pub type ReadProgressCallback = Arc<dyn Fn(usize, Option<usize>) + Send + Sync>;
 
 // zh: 用于创建 X11 剪贴板上下文的选项
 // en: Options for creating an X11 clipboard context
+#[non_exhaustive]
 pub struct ClipboardContextX11Options {
-	// zh: 剪贴板读取操作超时
-	// en: Timeout for clipboard read operations
+	// zh: 剪贴板读取操作的空闲超时，每次收到数据都会重新计时
+	// en: Idle timeout for clipboard read operations, the deadline is reset whenever data arrives
 	pub read_timeout: Option<Duration>,
+	// zh: 读取数据时的进度回调
+	// en: Progress callback invoked while reading data
+	pub progress_callback: Option<ReadProgressCallback>,
+	// zh: 禁用 TARGETS 缓存，每次 has/available_formats 都重新向选区所有者查询
+	// en: Disable the TARGETS cache, every has/available_formats call asks the selection owner again
+	pub disable_targets_cache: bool,
+	// zh: 要连接的 X display（例如 ":1" 或 SSH 转发的 "localhost:10.0"），None 表示使用 DISPLAY 环境变量
+	// en: The X display to connect to (e.g. ":1" or "localhost:10.0" forwarded over SSH), None means using the DISPLAY environment variable
+	pub display: Option<String>,
+	// zh: 写入时没能取得选区所有权（例如剪贴板管理器立即抢回）后的重试次数，默认不重试
+	// en: Number of retries when a write fails to take the selection ownership (e.g. a clipboard manager grabs it back right away), no retry by default
+	pub ownership_retries: u32,
+	// zh: 每次重试取得所有权前的等待时间
+	// en: Delay before every retry to take the ownership
+	pub ownership_retry_delay: Duration,
+	// zh: 与 X server 的连接断开后（例如 X server 重启或 SSH 隧道中断）尝试重新连接的次数，0 表示不重新连接
+	// en: Number of attempts to reconnect after the connection to the X server is lost (e.g. the X server restarted or the SSH tunnel dropped), 0 means never reconnecting
+	pub reconnect_retries: u32,
+	// zh: 每次重新连接尝试之间的等待时间
+	// en: Delay between the reconnection attempts
+	pub reconnect_delay: Duration,
+	// zh: 写入时等待 X server 的超时：取得所有权前等待服务器时间的最长时间（超时后使用 CurrentTime），超过后也不再重试取得所有权，None 表示一直等待
+	// en: Timeout of writes waiting for the X server: the longest wait for the server time before taking the ownership (CurrentTime is used after it), no ownership retry starts after it either, None means waiting forever
+	pub write_timeout: Option<Duration>,
+	// zh: 取得所有权后是否向 X server 确认，关闭后写入少两次往返，但不会发现所有权被立即抢走，也不会重试
+	// en: Whether to confirm with the X server that the ownership was taken, without it a write saves two round trips but does not notice the ownership being grabbed right away, nor retries
+	pub ownership_check: bool,
+	// zh: 读取时每次等待连接可读的最长时间，之后重新检查事件队列和超时
+	// en: The longest a read waits for the connection to become readable before checking the event queue and the timeout again
+	pub poll_interval: Duration,
+	// zh: 上下文被丢弃时是否先把写入的内容交给剪贴板管理器保存，以及最多等待多久，None 表示不交给剪贴板管理器，丢弃时不会阻塞
+	// en: Whether the content written is handed over to the clipboard manager before the context is dropped, and how long to wait for it at most, None means no handover, dropping does not block
+	pub persist_on_drop_timeout: Option<Duration>,
+}
+
+impl Default for ClipboardContextX11Options {
+	fn default() -> Self {
+		ClipboardContextX11Options {
+			read_timeout: Some(Duration::from_millis(DEFAULT_READ_TIMEOUT)),
+			progress_callback: None,
+			disable_targets_cache: false,
+			display: None,
+			ownership_retries: 0,
+			ownership_retry_delay: Duration::from_millis(DEFAULT_OWNERSHIP_RETRY_DELAY),
+			reconnect_retries: DEFAULT_RECONNECT_RETRIES,
+			reconnect_delay: Duration::from_millis(DEFAULT_RECONNECT_DELAY),
+			write_timeout: Some(Duration::from_millis(DEFAULT_WRITE_TIMEOUT)),
+			ownership_check: true,
+			poll_interval: Duration::from_millis(DEFAULT_POLL_INTERVAL),
+			persist_on_drop_timeout: None,
+		}
+	}
 }
 
 const FILE_PATH_PREFIX: &str = "file://";
 pub struct ClipboardContext {
-	inner: Arc<InnerContext>,
+	// 连接断开并重新连接后会被替换
+	inner: RwLock<Arc<InnerContext>>,
+	// 重新连接时用来重建上下文
+	selection: XSelection,
 	read_timeout: Option<Duration>,
+	progress_callback: Option<ReadProgressCallback>,
+	cache_targets: bool,
+	// 监听器需要连接到同一个 display
+	display: Option<String>,
+	ownership_retries: u32,
+	ownership_retry_delay: Duration,
+	reconnect_retries: u32,
+	reconnect_delay: Duration,
+	write_timeout: Option<Duration>,
+	ownership_check: bool,
+	// 重新连接时用来重建上下文
+	poll_interval: Duration,
+	persist_on_drop_timeout: Option<Duration>,
+	// 处理其他程序请求的线程，上下文被丢弃时结束
+	server_thread: Mutex<Option<thread::JoinHandle<()>>>,
 }
 
 struct ClipboardData {
@@ -78,19 +214,77 @@ struct ClipboardData {
 	data: Vec<u8>,
 }
 
+// 可以由已保存的 source 数据在请求时转换得到的目标，例如由 UTF8_STRING 转换的 STRING
+struct TargetAlias {
+	source: Atom,
+	target: Atom,
+	// 回复时使用的属性类型
+	reply_type: Atom,
+	convert: fn(&[u8]) -> Vec<u8>,
+}
+
+// 正在通过 INCR 协议发送给请求方的数据
+// https://tronche.com/gui/x/icccm/sec-2.html#s-2.7.2
+struct IncrTransfer {
+	requestor: Window,
+	property: Atom,
+	target: Atom,
+	data: Vec<u8>,
+	offset: usize,
+}
+
 struct InnerContext {
 	server: XServerContext,
 	server_for_write: XServerContext,
 	ignore_formats: Vec<Atom>,
 	// 此刻待写入的剪贴板内容
 	wait_write_data: RwLock<Vec<ClipboardData>>,
+	// 超过该大小的数据通过 INCR 协议分块发送
+	incr_chunk_size: usize,
+	incr_transfers: Mutex<Vec<IncrTransfer>>,
+	// 最近一次 XfixesSelectionNotify 事件的序列号
+	sequence_number: AtomicU64,
+	// 第一次读取序列号时才监听选区所有者的变化，保存监听的结果，失败（例如 X server 不支持 XFIXES）时不支持序列号
+	sequence_listener: Mutex<Option<std::result::Result<(), String>>>,
+	// 读写的选区，默认为 CLIPBOARD
+	selection: Atom,
+	// 等待剪贴板管理器完成 SAVE_TARGETS 的通知
+	save_targets_notify: Mutex<Option<Sender<bool>>>,
+	// 其他程序取得选区所有权时调用
+	ownership_lost_callback: Mutex<Option<OwnershipLostCallback>>,
+	// 最近一次读取的 TARGETS
+	targets_cache: Mutex<Option<TargetsCache>>,
+	// 服务线程收到 TIMESTAMP_PROP 的 PropertyNotify 后发送其中的服务器时间
+	timestamp_sender: Mutex<Sender<Timestamp>>,
+	timestamp_receiver: Mutex<Receiver<Timestamp>>,
+	// 取得选区所有权时的服务器时间，没有所有权时为 CURRENT_TIME
+	owner_timestamp: AtomicU32,
+	// 读取时每次等待连接可读的最长时间
+	poll_interval: Duration,
+}
+
+struct TargetsCache {
+	sequence_number: u64,
+	time: Instant,
+	targets: Vec<Atom>,
 }
 
+type OwnershipLostCallback = Box<dyn FnMut() + Send>;
+
 impl InnerContext {
-	pub fn new() -> Result<Self> {
-		let server = XServerContext::new()?;
-		let server_for_write = XServerContext::new()?;
+	pub fn new(selection: XSelection, options: &ClipboardContextX11Options) -> Result<Self> {
+		let server = XServerContext::new_with_options(options)?;
+		let server_for_write = XServerContext::new_with_options(options)?;
+		if let Ok(mut windows) = OWN_WINDOWS.lock() {
+			windows.push(server_for_write.win_id);
+		}
 		let wait_write_data = RwLock::new(Vec::new());
+		let selection = selection_atom(&server.atoms, selection);
+
+		// leave some room for the request header, and keep chunks reasonably small
+		// for requestors that do not support big requests
+		let incr_chunk_size =
+			(server_for_write.conn.maximum_request_bytes() / 4).min(INCR_CHUNK_SIZE);
 
 		let ignore_formats = vec![
 			server.atoms.TIMESTAMP,
@@ -99,14 +293,247 @@ impl InnerContext {
 			server.atoms.SAVE_TARGETS,
 		];
 
+		let (timestamp_sender, timestamp_receiver) = mpsc::channel();
+
 		Ok(Self {
 			server,
 			server_for_write,
 			ignore_formats,
 			wait_write_data,
+			incr_chunk_size,
+			incr_transfers: Mutex::new(Vec::new()),
+			sequence_number: AtomicU64::new(0),
+			sequence_listener: Mutex::new(None),
+			selection,
+			save_targets_notify: Mutex::new(None),
+			ownership_lost_callback: Mutex::new(None),
+			targets_cache: Mutex::new(None),
+			timestamp_sender: Mutex::new(timestamp_sender),
+			timestamp_receiver: Mutex::new(timestamp_receiver),
+			owner_timestamp: AtomicU32::new(CURRENT_TIME),
+			poll_interval: options.poll_interval,
 		})
 	}
 
+	// listen to the selection owner changes on the first call, so that the server thread keeps track of the sequence number,
+	// a server without XFIXES can still read and write, only the sequence number is unsupported
+	fn listen_owner_changes(&self) -> Result<()> {
+		let mut listener = self
+			.sequence_listener
+			.lock()
+			.map_err(|e| format!("lock sequence listener error: {:?}", e))?;
+		let result = listener.get_or_insert_with(|| {
+			select_owner_changes(&self.server_for_write, self.selection).map_err(|e| e.to_string())
+		});
+		match result {
+			Ok(()) => Ok(()),
+			Err(error) => Err(ClipboardError::PlatformError {
+				code: 0,
+				message: format!("failed to listen to selection owner changes: {}", error),
+			}
+			.into()),
+		}
+	}
+
+	// get the current server time, by appending nothing to a property of our window and waiting for its PropertyNotify
+	// https://tronche.com/gui/x/icccm/sec-2.html#s-2.1
+	fn server_timestamp(&self, timeout: Option<Duration>) -> Result<Timestamp> {
+		let ctx = &self.server_for_write;
+		// the lock makes concurrent callers wait for their own event
+		let receiver = self
+			.timestamp_receiver
+			.lock()
+			.map_err(|_| "Failed to lock timestamp receiver")?;
+		// drop timestamps left over by a caller that timed out
+		while receiver.try_recv().is_ok() {}
+		ctx.conn.change_property8(
+			PropMode::APPEND,
+			ctx.win_id,
+			ctx.atoms.TIMESTAMP_PROP,
+			AtomEnum::INTEGER,
+			&[],
+		)?;
+		ctx.conn.flush()?;
+		match timeout {
+			Some(timeout) => receiver
+				.recv_timeout(timeout)
+				.map_err(|_| ClipboardError::Timeout.into()),
+			None => receiver.recv().map_err(|_| ClipboardError::Timeout.into()),
+		}
+	}
+
+	fn invalidate_targets_cache(&self) {
+		if let Ok(mut cache) = self.targets_cache.lock() {
+			*cache = None;
+		}
+	}
+
+	// the data is too large to be sent in a single request, start an INCR transfer
+	fn start_incr_transfer(
+		&self,
+		requestor: Window,
+		property: Atom,
+		target: Atom,
+		data: &[u8],
+	) -> Result<()> {
+		let ctx = &self.server_for_write;
+		// we need to know when the requestor deletes the property to send the next chunk
+		ctx.conn.change_window_attributes(
+			requestor,
+			&ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+		)?;
+		ctx.conn.change_property32(
+			PropMode::REPLACE,
+			requestor,
+			property,
+			ctx.atoms.INCR,
+			&[data.len() as u32],
+		)?;
+		let mut transfers = self
+			.incr_transfers
+			.lock()
+			.map_err(|_| "Failed to lock incr transfers")?;
+		// a new request for the same property replaces the previous transfer
+		transfers.retain(|t| t.requestor != requestor || t.property != property);
+		transfers.push(IncrTransfer {
+			requestor,
+			property,
+			target,
+			data: data.to_vec(),
+			offset: 0,
+		});
+		Ok(())
+	}
+
+	// write the data of `target` to the property of the requestor, returns false if we do not have such data
+	fn send_target(
+		&self,
+		data_list: &[ClipboardData],
+		requestor: Window,
+		property: Atom,
+		target: Atom,
+	) -> Result<bool> {
+		if target == self.server_for_write.atoms.TIMESTAMP {
+			return self.send_timestamp(requestor, property);
+		}
+		let (reply_type, data) = match data_list.iter().find(|d| d.format == target) {
+			Some(data) => (target, Cow::Borrowed(data.data.as_slice())),
+			// the data we stored can be converted to the target
+			None => match find_target_alias(&self.server_for_write.atoms, data_list, target) {
+				Some((alias, data)) => (alias.reply_type, Cow::Owned((alias.convert)(&data.data))),
+				None => return Ok(false),
+			},
+		};
+		if data.len() > self.incr_chunk_size {
+			self.start_incr_transfer(requestor, property, reply_type, &data)?;
+		} else {
+			self.server_for_write.conn.change_property8(
+				PropMode::REPLACE,
+				requestor,
+				property,
+				reply_type,
+				&data,
+			)?;
+		}
+		Ok(true)
+	}
+
+	// tell the requestor when we acquired the selection, refused if we did not get a server timestamp
+	fn send_timestamp(&self, requestor: Window, property: Atom) -> Result<bool> {
+		let timestamp = self.owner_timestamp.load(Ordering::SeqCst);
+		if timestamp == CURRENT_TIME {
+			return Ok(false);
+		}
+		self.server_for_write.conn.change_property32(
+			PropMode::REPLACE,
+			requestor,
+			property,
+			AtomEnum::INTEGER,
+			&[timestamp],
+		)?;
+		Ok(true)
+	}
+
+	// the requestor asks for several targets at once, e.g. a clipboard manager saving our data
+	// https://tronche.com/gui/x/icccm/sec-2.html#s-2.6.2
+	fn send_multiple(
+		&self,
+		data_list: &[ClipboardData],
+		event: &SelectionRequestEvent,
+	) -> Result<bool> {
+		let ctx = &self.server_for_write;
+		let reply = ctx
+			.conn
+			.get_property(
+				false,
+				event.requestor,
+				event.property,
+				ctx.atoms.ATOM_PAIR,
+				0,
+				u32::MAX,
+			)?
+			.reply()?;
+		let mut pairs: Vec<Atom> = match reply.value32() {
+			Some(value) => value.collect(),
+			None => return Ok(false),
+		};
+		for pair in pairs.chunks_mut(2) {
+			if let [target, property] = pair {
+				if !self.send_target(data_list, event.requestor, *property, *target)? {
+					// tell the requestor which conversions failed
+					*target = x11rb::NONE;
+				}
+			}
+		}
+		ctx.conn.change_property32(
+			PropMode::REPLACE,
+			event.requestor,
+			event.property,
+			ctx.atoms.ATOM_PAIR,
+			&pairs,
+		)?;
+		Ok(true)
+	}
+
+	// the requestor has deleted the property, send the next chunk of an INCR transfer
+	fn continue_incr_transfer(&self, window: Window, property: Atom) -> Result<()> {
+		let ctx = &self.server_for_write;
+		let mut transfers = self
+			.incr_transfers
+			.lock()
+			.map_err(|_| "Failed to lock incr transfers")?;
+		let index = match transfers
+			.iter()
+			.position(|t| t.requestor == window && t.property == property)
+		{
+			Some(index) => index,
+			None => return Ok(()),
+		};
+		let transfer = &mut transfers[index];
+		let end = (transfer.offset + self.incr_chunk_size).min(transfer.data.len());
+		// a zero-length chunk marks the end of the transfer
+		ctx.conn.change_property8(
+			PropMode::REPLACE,
+			transfer.requestor,
+			transfer.property,
+			transfer.target,
+			&transfer.data[transfer.offset..end],
+		)?;
+		if transfer.offset == end {
+			let transfer = transfers.remove(index);
+			if !transfers.iter().any(|t| t.requestor == transfer.requestor) {
+				ctx.conn.change_window_attributes(
+					transfer.requestor,
+					&ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+				)?;
+			}
+		} else {
+			transfer.offset = end;
+		}
+		ctx.conn.flush()?;
+		Ok(())
+	}
+
 	pub fn handle_selection_request(&self, event: SelectionRequestEvent) -> Result<()> {
 		let success;
 		let ctx = &self.server_for_write;
@@ -118,12 +545,22 @@ impl InnerContext {
 				Ok(data_list) => {
 					let mut targets = Vec::with_capacity(10);
 					targets.push(atoms.TARGETS);
+					targets.push(atoms.TIMESTAMP);
 					targets.push(atoms.SAVE_TARGETS);
-					if data_list.len() > 0 {
+					targets.push(atoms.MULTIPLE);
+					if !data_list.is_empty() {
 						data_list.iter().for_each(|data| {
 							targets.push(data.format);
 						});
 					}
+					// the targets we can convert the stored data to
+					for alias in target_aliases(&atoms) {
+						if !targets.contains(&alias.target)
+							&& data_list.iter().any(|data| data.format == alias.source)
+						{
+							targets.push(alias.target);
+						}
+					}
 					ctx.conn.change_property32(
 						PropMode::REPLACE,
 						event.requestor,
@@ -139,18 +576,10 @@ impl InnerContext {
 			let reader = self.wait_write_data.read();
 			match reader {
 				Ok(data_list) => {
-					success = match data_list.iter().find(|d| d.format == event.target) {
-						Some(data) => {
-							ctx.conn.change_property8(
-								PropMode::REPLACE,
-								event.requestor,
-								event.property,
-								event.target,
-								&data.data,
-							)?;
-							true
-						}
-						None => false,
+					success = if event.target == atoms.MULTIPLE {
+						self.send_multiple(&data_list, &event)?
+					} else {
+						self.send_target(&data_list, event.requestor, event.property, event.target)?
 					};
 				}
 				Err(_) => return Err("Failed to read clipboard data".into()),
@@ -183,36 +612,35 @@ impl InnerContext {
 
 	pub fn process_event(
 		&self,
-		buff: &mut Vec<u8>,
-		selection: Atom,
+		sink: &mut dyn Write,
 		target: Atom,
 		property: Atom,
 		timeout: Option<Duration>,
 		sequence_number: u64,
+		progress: Option<&ReadProgressCallback>,
 	) -> Result<()> {
 		let mut is_incr = false;
-		let start_time = if timeout.is_some() {
-			Some(Instant::now())
-		} else {
-			None
-		};
+		let mut expected_total = None;
+		let mut received = 0;
+		// the timeout is an idle timeout, it restarts whenever data arrives
+		let mut last_activity = Instant::now();
 		let ctx = &self.server;
 		let atoms = ctx.atoms;
 		loop {
-			if timeout
-				.into_iter()
-				.zip(start_time)
-				.next()
-				.map(|(timeout, time)| (Instant::now() - time) >= timeout)
-				.unwrap_or(false)
-			{
-				return Err("Timeout while waiting for clipboard data".into());
-			}
+			let remaining = match timeout {
+				Some(timeout) => match timeout.checked_sub(last_activity.elapsed()) {
+					Some(remaining) if !remaining.is_zero() => Some(remaining),
+					_ => return Err(ClipboardError::Timeout.into()),
+				},
+				None => None,
+			};
 
 			let (event, seq) = match ctx.conn.poll_for_event_with_sequence()? {
 				Some(event) => event,
 				None => {
-					thread::park_timeout(Duration::from_millis(50));
+					// sleep until the server sends something, never past the deadline
+					let wait = remaining.map_or(self.poll_interval, |r| r.min(self.poll_interval));
+					wait_readable(ctx.conn.stream(), wait)?;
 					continue;
 				}
 			};
@@ -223,9 +651,15 @@ impl InnerContext {
 
 			match event {
 				Event::SelectionNotify(event) => {
-					if event.selection != selection {
+					if event.selection != self.selection {
 						continue;
 					};
+					// there is no owner, or the owner can not convert the selection to the target
+					if event.property == x11rb::NONE {
+						return Err(
+							"The selection owner refused to convert the clipboard data".into()
+						);
+					}
 
 					let target_type = {
 						if target == atoms.TARGETS {
@@ -235,31 +669,34 @@ impl InnerContext {
 						}
 					};
 
-					let reply = ctx
-						.conn
-						.get_property(
-							false,
-							event.requestor,
-							event.property,
-							target_type,
-							buff.len() as u32,
-							u32::MAX,
-						)?
-						.reply()?;
+					let reply =
+						ctx.get_full_property(false, event.requestor, event.property, target_type)?;
 
 					if reply.type_ == atoms.INCR {
 						if let Some(mut value) = reply.value32() {
 							if let Some(size) = value.next() {
-								buff.reserve(size as usize);
+								expected_total = Some(size as usize);
 							}
 						}
 						ctx.conn.delete_property(ctx.win_id, property)?.check()?;
 						is_incr = true;
+						last_activity = Instant::now();
+						if let Some(progress) = progress {
+							progress(received, expected_total);
+						}
 						continue;
-					} else if reply.type_ != target && reply.type_ != atoms.ATOM {
+					} else if reply.type_ != target
+						&& reply.type_ != atoms.ATOM
+						// the owner picks the encoding of TEXT and replies with its own type
+						&& target != atoms.TEXT
+					{
 						return Err("Clipboard data type mismatch".into());
 					}
-					buff.extend_from_slice(&reply.value);
+					sink.write_all(&reply.value)?;
+					received += reply.value.len();
+					if let Some(progress) = progress {
+						progress(received, Some(received));
+					}
 					break;
 				}
 
@@ -268,21 +705,7 @@ impl InnerContext {
 						continue;
 					};
 
-					let cookie =
-						ctx.conn
-							.get_property(false, ctx.win_id, property, AtomEnum::ATOM, 0, 0)?;
-
-					let length = cookie.reply()?.bytes_after;
-
-					let cookie = ctx.conn.get_property(
-						true,
-						ctx.win_id,
-						property,
-						AtomEnum::NONE,
-						0,
-						length,
-					)?;
-					let reply = cookie.reply()?;
+					let reply = ctx.get_full_property(true, ctx.win_id, property, AtomEnum::ANY)?;
 					if reply.type_ != target {
 						continue;
 					};
@@ -290,7 +713,13 @@ impl InnerContext {
 					let value = reply.value;
 
 					if !value.is_empty() {
-						buff.extend_from_slice(&value);
+						// every chunk goes to the sink right away, the whole transfer is never held in memory
+						sink.write_all(&value)?;
+						received += value.len();
+						last_activity = Instant::now();
+						if let Some(progress) = progress {
+							progress(received, expected_total);
+						}
 					} else {
 						break;
 					}
@@ -303,255 +732,865 @@ impl InnerContext {
 }
 
 impl ClipboardContext {
-	pub fn new() -> Result<Self> {
-		Self::new_with_options(ClipboardContextX11Options {
-			read_timeout: Some(Duration::from_millis(DEFAULT_READ_TIMEOUT)),
-		})
+	pub fn new_with_options(options: ClipboardContextX11Options) -> Result<Self> {
+		Self::from_options(XSelection::default(), options)
 	}
 
-	pub fn new_with_options(options: ClipboardContextX11Options) -> Result<Self> {
-		// build connection to X server
-		let ctx = InnerContext::new()?;
-		let ctx_arc = Arc::new(ctx);
-		let ctx_clone = ctx_arc.clone();
+	pub(crate) fn from_builder(builder: &ClipboardContextBuilder) -> Result<Self> {
+		Self::from_options(
+			builder.selection,
+			ClipboardContextX11Options {
+				read_timeout: builder.read_timeout,
+				..Default::default()
+			},
+		)
+	}
 
-		thread::spawn(move || {
-			let res = process_server_req(&ctx_clone);
-			if let Err(e) = res {
-				println!("process_server_req error: {:?}", e);
+	fn from_options(selection: XSelection, options: ClipboardContextX11Options) -> Result<Self> {
+		// x11rb's error is hard to understand when there is no display at all
+		let is_unset = |name| std::env::var_os(name).map_or(true, |value| value.is_empty());
+		if options.display.is_none() && is_unset("DISPLAY") && is_unset("WAYLAND_DISPLAY") {
+			return Err(ClipboardError::PlatformError {
+				code: 0,
+				message: "No display server available (DISPLAY and WAYLAND_DISPLAY are unset)"
+					.to_string(),
 			}
-		});
+			.into());
+		}
+		// build connection to X server
+		let ctx = Arc::new(InnerContext::new(selection, &options)?);
+		let server_thread = spawn_server_thread(ctx.clone());
 
 		Ok(Self {
-			inner: ctx_arc,
+			inner: RwLock::new(ctx),
+			selection,
 			read_timeout: options.read_timeout,
+			progress_callback: options.progress_callback,
+			cache_targets: !options.disable_targets_cache,
+			display: options.display,
+			ownership_retries: options.ownership_retries,
+			ownership_retry_delay: options.ownership_retry_delay,
+			reconnect_retries: options.reconnect_retries,
+			reconnect_delay: options.reconnect_delay,
+			write_timeout: options.write_timeout,
+			ownership_check: options.ownership_check,
+			poll_interval: options.poll_interval,
+			persist_on_drop_timeout: options.persist_on_drop_timeout,
+			server_thread: Mutex::new(Some(server_thread)),
 		})
 	}
 
+	// the current connections, replaced when they are re-established
+	fn inner(&self) -> Arc<InnerContext> {
+		match self.inner.read() {
+			Ok(inner) => inner.clone(),
+			Err(poisoned) => poisoned.into_inner().clone(),
+		}
+	}
+
+	// run the operation again on new connections if the X server went away while it ran
+	fn with_reconnect<R>(&self, mut op: impl FnMut() -> Result<R>) -> Result<R> {
+		let inner = self.inner();
+		match op() {
+			Err(e) if is_connection_error(&*e) => {
+				self.reconnect(&inner)?;
+				op()
+			}
+			res => res,
+		}
+	}
+
+	// re-establish both connections and the serving thread, `failed` is the context whose connection was lost
+	fn reconnect(&self, failed: &Arc<InnerContext>) -> Result<()> {
+		let mut inner = self
+			.inner
+			.write()
+			.map_err(|_| "Failed to lock clipboard context")?;
+		// another thread has already reconnected
+		if !Arc::ptr_eq(&inner, failed) {
+			return Ok(());
+		}
+		let options = ClipboardContextX11Options {
+			display: self.display.clone(),
+			poll_interval: self.poll_interval,
+			..Default::default()
+		};
+		for attempt in 0..self.reconnect_retries {
+			if attempt > 0 {
+				thread::sleep(self.reconnect_delay);
+			}
+			// the error of a single attempt is not kept, ConnectionLost is returned once all of them failed
+			let Ok(ctx) = InnerContext::new(self.selection, &options) else {
+				continue;
+			};
+			// the callback belongs to the context, not to the connection
+			if let (Ok(mut old), Ok(mut new)) = (
+				failed.ownership_lost_callback.lock(),
+				ctx.ownership_lost_callback.lock(),
+			) {
+				*new = old.take();
+			}
+			let ctx = Arc::new(ctx);
+			let server_thread = spawn_server_thread(ctx.clone());
+			*inner = ctx;
+			// the old thread stops on its own connection error, joining it could block if it has not noticed yet
+			if let Ok(mut handle) = self.server_thread.lock() {
+				*handle = Some(server_thread);
+			}
+			if let Ok(mut windows) = OWN_WINDOWS.lock() {
+				windows.retain(|window| *window != failed.server_for_write.win_id);
+			}
+			return Ok(());
+		}
+		Err(ClipboardError::ConnectionLost.into())
+	}
+
+	/// zh: 停止服务剪贴板请求的后台线程并等待它退出，在此之前会把写入的 CLIPBOARD 内容交给剪贴板管理器保存。丢弃上下文时也会自动执行，这里可以获得停止时的错误
+	/// en: Stop the background thread serving clipboard requests and wait for it to exit, the CLIPBOARD content written is handed over to the clipboard manager first. Dropping the context does the same, this returns the error of stopping
+	pub fn close(mut self) -> Result<()> {
+		self.shutdown()
+	}
+
+	// stop serving the selection, once only
+	fn shutdown(&mut self) -> Result<()> {
+		let running = match self.server_thread.get_mut() {
+			Ok(server_thread) => server_thread.is_some(),
+			Err(poisoned) => poisoned.into_inner().is_some(),
+		};
+		if !running {
+			return Ok(());
+		}
+		// our window goes away with the context, hand what we own over to the clipboard manager first if asked to
+		let inner = self.inner();
+		if let Some(timeout) = self.persist_on_drop_timeout {
+			if inner.selection == inner.server_for_write.atoms.CLIPBOARD {
+				let _ = self.persist(timeout);
+			}
+		}
+		// the connections are closed once the thread has released its reference to the context
+		self.stop_server_thread()
+	}
+
+	// wake the thread serving the selection with a message to our own window and wait for it to exit
+	fn stop_server_thread(&mut self) -> Result<()> {
+		let server_thread = match self.server_thread.get_mut() {
+			Ok(server_thread) => server_thread.take(),
+			Err(poisoned) => poisoned.into_inner().take(),
+		};
+		let Some(server_thread) = server_thread else {
+			return Ok(());
+		};
+		let ctx = &self.inner().server_for_write;
+		// with an empty event mask the event goes to the client that created the window, i.e. the serving thread
+		ctx.conn.send_event(
+			false,
+			ctx.win_id,
+			EventMask::NO_EVENT,
+			ClientMessageEvent::new(32, ctx.win_id, ctx.atoms.SHUTDOWN, [0u32; 5]),
+		)?;
+		ctx.conn.flush()?;
+		// a handler of the thread (e.g. the ownership lost callback) may drop the context
+		if server_thread.thread().id() != thread::current().id() {
+			server_thread
+				.join()
+				.map_err(|_| "The clipboard server thread panicked")?;
+		}
+		if let Ok(mut windows) = OWN_WINDOWS.lock() {
+			windows.retain(|window| *window != ctx.win_id);
+		}
+		Ok(())
+	}
+
+	/// zh: 设置失去剪贴板所有权时（其他程序写入了剪贴板）的回调，回调在后台线程中执行，再次设置会替换之前的回调
+	/// en: Set the callback invoked when the ownership of the clipboard is lost (another program wrote to the clipboard), the callback runs on a background thread, setting it again replaces the previous callback
+	pub fn set_ownership_lost_callback<F>(&self, callback: F) -> Result<()>
+	where
+		F: FnMut() + Send + 'static,
+	{
+		*self
+			.inner()
+			.ownership_lost_callback
+			.lock()
+			.map_err(|_| "Failed to lock ownership lost callback")? = Some(Box::new(callback));
+		Ok(())
+	}
+
+	/// zh: 将当前写入的剪贴板内容交给剪贴板管理器保存，使其在程序退出后依然可用，没有剪贴板管理器时返回错误，最多等待 2 秒。
+	/// 设置了 [`ClipboardContextX11Options::persist_on_drop_timeout`] 时上下文被丢弃时也会执行
+	/// en: Hand the clipboard content written by this context over to the clipboard manager, so that it is still available after the program exits, returns an error if there is no clipboard manager, waiting at most 2 seconds.
+	/// Also done when the context is dropped if [`ClipboardContextX11Options::persist_on_drop_timeout`] is set
+	pub fn persist_on_exit(&self) -> Result<()> {
+		self.persist(SAVE_TARGETS_TIMEOUT)
+	}
+
+	fn persist(&self, timeout: Duration) -> Result<()> {
+		let inner = self.inner();
+		let ctx = &inner.server_for_write;
+		let atoms = ctx.atoms;
+		if inner.selection != atoms.CLIPBOARD {
+			return Err("Only the CLIPBOARD selection can be persisted".into());
+		}
+		let owner = ctx
+			.conn
+			.get_selection_owner(atoms.CLIPBOARD)?
+			.reply()?
+			.owner;
+		if owner != ctx.win_id {
+			// we do not own the clipboard, there is nothing to persist
+			return Ok(());
+		}
+		let manager = ctx
+			.conn
+			.get_selection_owner(atoms.CLIPBOARD_MANAGER)?
+			.reply()?
+			.owner;
+		if manager == x11rb::NONE {
+			return Err("No clipboard manager is running".into());
+		}
+
+		// https://www.freedesktop.org/wiki/ClipboardManager/
+		let (tx, rx) = mpsc::channel();
+		*inner
+			.save_targets_notify
+			.lock()
+			.map_err(|_| "Failed to lock save targets notify")? = Some(tx);
+		ctx.conn
+			.convert_selection(
+				ctx.win_id,
+				atoms.CLIPBOARD_MANAGER,
+				atoms.SAVE_TARGETS,
+				atoms.PROPERTY,
+				// the time we acquired the selection, as the clipboard manager specification asks
+				inner.owner_timestamp.load(Ordering::SeqCst),
+			)?
+			.check()?;
+		ctx.conn.flush()?;
+
+		let res = rx.recv_timeout(timeout);
+		if let Ok(mut notify) = inner.save_targets_notify.lock() {
+			notify.take();
+		}
+		match res {
+			Ok(true) => Ok(()),
+			Ok(false) => Err("The clipboard manager refused to save the clipboard".into()),
+			Err(_) => Err("Timeout while waiting for the clipboard manager".into()),
+		}
+	}
+
+	// read the encoded bytes of the best image target offered
+	fn read_image_bytes(&self) -> Result<Vec<u8>> {
+		self.read_best_target(&ContentFormat::Image, "image")
+	}
+
+	// read the best target offered for the format, see `format_targets` for the priority,
+	// so that the getters read every target `has` accepts
+	fn read_best_target(&self, format: &ContentFormat, name: &str) -> Result<Vec<u8>> {
+		let ctx = &self.inner().server;
+		let candidates = format_targets(&ctx.atoms, format);
+		let targets = self.read_targets().unwrap_or_default();
+		let target = candidates
+			.iter()
+			.copied()
+			.find(|target| targets.contains(target))
+			// owners that do not answer TARGETS are still asked for the preferred target
+			.or_else(|| candidates.first().copied().filter(|_| targets.is_empty()));
+		let Some(target) = target else {
+			let offered: Vec<String> = ctx.get_atom_names(&targets).into_iter().flatten().collect();
+			return Err(format!("No {} data found, offered targets: {:?}", name, offered).into());
+		};
+		self.read(&target)
+	}
+
 	fn read(&self, format: &Atom) -> Result<Vec<u8>> {
-		let ctx = &self.inner.server;
+		let mut buff = Vec::new();
+		self.read_to(format, &mut buff)?;
+		Ok(buff)
+	}
+
+	fn read_to(&self, format: &Atom, sink: &mut dyn Write) -> Result<()> {
+		let inner = self.inner();
+		let ctx = &inner.server;
 		let atoms = ctx.atoms;
-		let clipboard = atoms.CLIPBOARD;
+		let clipboard = inner.selection;
 		let win_id = ctx.win_id;
 		let cookie =
 			ctx.conn
 				.convert_selection(win_id, clipboard, *format, atoms.PROPERTY, CURRENT_TIME)?;
 		let sequence_num = cookie.sequence_number();
 		cookie.check()?;
-		let mut buff = Vec::new();
 
-		self.inner.process_event(
-			&mut buff,
-			clipboard,
+		inner.process_event(
+			sink,
 			*format,
 			atoms.PROPERTY,
 			self.read_timeout,
 			sequence_num,
+			self.progress_callback.as_ref(),
 		)?;
 
 		ctx.conn.delete_property(win_id, atoms.PROPERTY)?.check()?;
 
-		Ok(buff)
+		Ok(())
 	}
 
-	fn write(&self, data: Vec<ClipboardData>) -> Result<()> {
-		let writer = self.inner.wait_write_data.write();
-		match writer {
-			Ok(mut writer) => {
-				writer.clear();
-				writer.extend(data);
+	// the targets offered by the owner of the selection, cached for a short time to save round-trips
+	fn read_targets(&self) -> Result<Vec<Atom>> {
+		if !self.cache_targets {
+			return self.read_targets_uncached();
+		}
+		let inner = self.inner();
+		let sequence_number = inner.sequence_number.load(Ordering::SeqCst);
+		if let Ok(cache) = inner.targets_cache.lock() {
+			if let Some(cache) = cache.as_ref() {
+				if cache.sequence_number == sequence_number
+					&& cache.time.elapsed() < TARGETS_CACHE_TTL
+				{
+					return Ok(cache.targets.clone());
+				}
 			}
-			Err(_) => return Err("Failed to write clipboard data".into()),
 		}
-		let ctx = &self.inner.server_for_write;
-		let atoms = ctx.atoms;
-
-		let win_id = ctx.win_id;
-		let clipboard = atoms.CLIPBOARD;
-		ctx.conn
-			.set_selection_owner(win_id, clipboard, CURRENT_TIME)?
-			.check()?;
+		let targets = self.read_targets_uncached()?;
+		if let Ok(mut cache) = inner.targets_cache.lock() {
+			*cache = Some(TargetsCache {
+				sequence_number,
+				time: Instant::now(),
+				targets: targets.clone(),
+			});
+		}
+		Ok(targets)
+	}
 
-		if ctx
+	// the selection without an owner is empty, there is nobody to ask for TARGETS
+	fn read_targets_uncached(&self) -> Result<Vec<Atom>> {
+		let inner = self.inner();
+		let ctx = &inner.server;
+		let owner = ctx
 			.conn
-			.get_selection_owner(clipboard)?
-			.reply()
-			.map(|reply| reply.owner == win_id)
-			.unwrap_or(false)
-		{
-			Ok(())
-		} else {
-			Err("Failed to take ownership of the clipboard".into())
+			.get_selection_owner(inner.selection)?
+			.reply()?
+			.owner;
+		if owner == x11rb::NONE {
+			return Ok(Vec::new());
 		}
+		let data = self.read(&ctx.atoms.TARGETS)?;
+		Ok(parse_atom_list(&data))
 	}
-}
 
-fn process_server_req(context: &InnerContext) -> Result<()> {
-	let atoms = context.server_for_write.atoms;
-	loop {
-		match context
-			.server_for_write
-			.conn
-			.wait_for_event()
-			.map_err(|e| format!("wait_for_event error: {:?}", e))?
-		{
-			Event::DestroyNotify(_) => {
-				// This window is being destroyed.
-				println!("Clipboard server window is being destroyed x_x");
-				break;
-			}
-			Event::SelectionClear(event) => {
-				// Someone else has new content in the clipboard, so it is
-				// notifying us that we should delete our data now.
-				println!("Somebody else owns the clipboard now");
-				if event.selection == atoms.CLIPBOARD {
-					// Clear the clipboard contents
-					context
-						.wait_write_data
-						.write()
-						.map(|mut writer| writer.clear())
-						.map_err(|e| format!("write clipboard data error: {:?}", e))?;
+	fn targets_contain(&self, formats: &[Atom], format: &ContentFormat) -> bool {
+		let ctx = &self.inner().server;
+		match format {
+			ContentFormat::Other(format_name) => {
+				let atom = ctx.get_atom(format_name.as_str());
+				match atom {
+					Ok(atom) => formats.contains(&atom),
+					Err(_) => false,
 				}
 			}
-			Event::SelectionRequest(event) => {
-				// Someone is requesting the clipboard content from us.
-				context
-					.handle_selection_request(event)
-					.map_err(|e| format!("handle_selection_request error: {:?}", e))?;
-			}
-			Event::SelectionNotify(event) => {
-				// We've requested the clipboard content and this is the answer.
-				// Considering that this thread is not responsible for reading
-				// clipboard contents, this must come from the clipboard manager
-				// signaling that the data was handed over successfully.
-				if event.selection != atoms.CLIPBOARD_MANAGER {
-					println!("Received a `SelectionNotify` from a selection other than the CLIPBOARD_MANAGER. This is unexpected in this thread.");
-					continue;
+			format => targets_match_format(formats, &format_targets(&ctx.atoms, format)),
+		}
+	}
+
+	// older applications (xterm, Motif) only offer STRING or TEXT, use the best text target the owner offers
+	fn read_text(&self) -> Result<String> {
+		let (target, data) = self.read_text_target()?;
+		Ok(String::from_utf8_lossy(&self.text_to_utf8(target, data)).to_string())
+	}
+
+	// the data of the best text target the owner offers, with the target it was read from
+	fn read_text_target(&self) -> Result<(Atom, Vec<u8>)> {
+		let atoms = self.inner().server.atoms;
+		let targets = self.read_targets().unwrap_or_default();
+		// the text targets offered in order of preference, owners that do not answer TARGETS are asked for all of them
+		let candidates: Vec<Atom> = format_targets(&atoms, &ContentFormat::Text)
+			.into_iter()
+			.filter(|target| targets.is_empty() || targets.contains(target))
+			.collect();
+		let mut last_error = None;
+		for target in candidates {
+			match self.read(&target) {
+				Ok(data) => return Ok((target, data)),
+				Err(e) => {
+					// an owner that does not answer would make every fallback wait as long
+					if let Some(ClipboardError::Timeout) = e.downcast_ref::<ClipboardError>() {
+						return Err(e);
+					}
+					last_error = Some(e);
 				}
 			}
-			_event => {
-				// May be useful for debugging but nothing else really.
-				// trace!("Received unwanted event: {:?}", event);
-			}
 		}
+		Err(last_error.unwrap_or_else(|| "No text data found".into()))
 	}
-	Ok(())
-}
 
-impl Clipboard for ClipboardContext {
-	//https://source.chromium.org/chromium/chromium/src/+/main:ui/base/x/x11_clipboard_helper.cc;l=224;drc=4cc063ac39c4a0d1f6011421b259a9715bb16de1;bpv=0;bpt=1
-	fn available_formats(&self) -> Result<Vec<String>> {
-		let ctx = &self.inner.server;
-		let atoms = ctx.atoms;
-		self.read(&atoms.TARGETS).map(|data| {
-			let mut formats = Vec::new();
-			// 解析原子标识符列表
-			let atom_list: Vec<Atom> = parse_atom_list(&data);
-			for atom in atom_list {
-				if self.inner.ignore_formats.contains(&atom) {
-					continue;
+	// transcode the text data of a target to UTF-8 according to its encoding, UTF-8 data is kept as is
+	fn text_to_utf8(&self, target: Atom, data: Vec<u8>) -> Vec<u8> {
+		let atoms = self.inner().server.atoms;
+		if target == atoms.STRING {
+			return latin1_to_string(&data).into_bytes();
+		}
+		if target == atoms.TEXT || target == atoms.TEXT_MIME_UNKNOWN {
+			// the encoding is unknown, fall back to Latin-1 when it is not UTF-8
+			return String::from_utf8(data)
+				.map(String::into_bytes)
+				.unwrap_or_else(|e| latin1_to_string(e.as_bytes()).into_bytes());
+		}
+		data
+	}
+
+	fn read_markdown(&self) -> Result<String> {
+		let atoms = self.inner().server.atoms;
+		let targets = self.read_targets()?;
+		let target = format_targets(&atoms, &ContentFormat::Markdown)
+			.into_iter()
+			.find(|target| targets.contains(target))
+			.ok_or("No markdown data found")?;
+		let data = self.read(&target)?;
+		Ok(String::from_utf8_lossy(&data).to_string())
+	}
+
+	fn read_url(&self) -> Result<String> {
+		let data = self.read(&self.inner().server.atoms.MOZ_URL)?;
+		parse_moz_url(&data).ok_or_else(|| "No url data found".into())
+	}
+
+	fn write(&self, data: Vec<ClipboardData>) -> Result<()> {
+		let inner = self.inner();
+		inner.invalidate_targets_cache();
+		let writer = inner.wait_write_data.write();
+		match writer {
+			Ok(mut writer) => {
+				writer.clear();
+				writer.extend(data);
+			}
+			Err(_) => return Err("Failed to write clipboard data".into()),
+		}
+		let ctx = &inner.server_for_write;
+		let win_id = ctx.win_id;
+		let clipboard = inner.selection;
+		let deadline = self.write_timeout.map(|timeout| Instant::now() + timeout);
+		for attempt in 0..=self.ownership_retries {
+			if attempt > 0 {
+				if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+					return Err(ClipboardError::Timeout.into());
 				}
-				let atom_name = ctx.get_atom_name(atom).unwrap_or("Unknown".to_string());
-				formats.push(atom_name);
+				thread::sleep(self.ownership_retry_delay);
 			}
-			formats
-		})
+			// ICCCM asks owners not to use CurrentTime, fall back to it only if the server time is not available
+			let timestamp = inner
+				.server_timestamp(self.write_timeout)
+				.unwrap_or(CURRENT_TIME);
+			let cookie = ctx.conn.set_selection_owner(win_id, clipboard, timestamp)?;
+			if !self.ownership_check {
+				cookie.ignore_error();
+				ctx.conn.flush()?;
+				inner.owner_timestamp.store(timestamp, Ordering::SeqCst);
+				return Ok(());
+			}
+			cookie.check()?;
+
+			if ctx
+				.conn
+				.get_selection_owner(clipboard)?
+				.reply()
+				.map(|reply| reply.owner == win_id)
+				.unwrap_or(false)
+			{
+				inner.owner_timestamp.store(timestamp, Ordering::SeqCst);
+				return Ok(());
+			}
+		}
+		Err(ClipboardError::OwnershipFailed.into())
 	}
 
-	fn has(&self, format: crate::ContentFormat) -> bool {
-		let ctx = &self.inner.server;
-		let atoms = ctx.atoms;
-		let atom_list = self.read(&atoms.TARGETS).map(|data| parse_atom_list(&data));
-		match atom_list {
-			Ok(formats) => match format {
-				ContentFormat::Text => formats.contains(&atoms.UTF8_STRING),
-				ContentFormat::Rtf => formats.contains(&atoms.RTF),
-				ContentFormat::Html => formats.contains(&atoms.HTML),
-				ContentFormat::Image => formats.contains(&atoms.PNG_MIME),
-				ContentFormat::Files => formats.contains(&atoms.FILE_LIST),
-				ContentFormat::Other(format_name) => {
-					let atom = ctx.get_atom(format_name.as_str());
-					match atom {
-						Ok(atom) => formats.contains(&atom),
-						Err(_) => false,
+	// the data written for the contents, in the atoms of the current connection
+	fn contents_to_data(&self, contents: &[ClipboardContent]) -> Result<Vec<ClipboardData>> {
+		let mut data = Vec::new();
+		let inner = self.inner();
+		let atoms = inner.server_for_write.atoms;
+		for content in contents {
+			match content {
+				ClipboardContent::Text(text) => {
+					data.push(ClipboardData {
+						format: atoms.UTF8_STRING,
+						data: text.as_bytes().to_vec(),
+					});
+				}
+				ClipboardContent::Rtf(rtf) => {
+					data.push(ClipboardData {
+						format: atoms.RTF,
+						data: rtf.as_bytes().to_vec(),
+					});
+				}
+				ClipboardContent::Html(html) => {
+					data.push(ClipboardData {
+						format: atoms.HTML,
+						data: html.as_bytes().to_vec(),
+					});
+				}
+				ClipboardContent::Image(image) => {
+					let image_png = image.to_png()?;
+					data.push(ClipboardData {
+						format: atoms.PNG_MIME,
+						data: image_png.get_bytes().to_vec(),
+					});
+				}
+				ClipboardContent::Files(files) => {
+					let data_arr =
+						file_uri_list_to_clipboard_data(files.clone(), FileOperation::Copy, atoms);
+					data.extend(data_arr);
+				}
+				ClipboardContent::FilesWithOperation(files, operation) => {
+					let data_arr =
+						file_uri_list_to_clipboard_data(files.clone(), *operation, atoms);
+					data.extend(data_arr);
+				}
+				ClipboardContent::Markdown(markdown) => {
+					data.push(ClipboardData {
+						format: atoms.MARKDOWN,
+						data: markdown.as_bytes().to_vec(),
+					});
+				}
+				ClipboardContent::Url(url) => {
+					data.push(ClipboardData {
+						format: atoms.MOZ_URL,
+						data: url.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+					});
+				}
+				ClipboardContent::Other(format_name, buffer) => {
+					let atom = inner.server_for_write.get_atom(format_name)?;
+					data.push(ClipboardData {
+						format: atom,
+						data: buffer.clone(),
+					});
+				}
+			}
+		}
+		Ok(data)
+	}
+}
+
+// wait until the X connection has data to read or the timeout expires
+fn wait_readable(stream: &impl AsFd, timeout: Duration) -> Result<()> {
+	let mut fds = [PollFd::new(stream, PollFlags::POLLIN)];
+	// round up, so that a sub-millisecond timeout does not turn into a busy loop
+	let timeout_ms = ((timeout.as_micros() + 999) / 1000).min(i32::MAX as u128) as i32;
+	match poll(&mut fds, timeout_ms) {
+		Ok(_) | Err(Errno::EINTR) => Ok(()),
+		Err(e) => Err(format!("poll error: {}", e).into()),
+	}
+}
+
+fn spawn_server_thread(context: Arc<InnerContext>) -> thread::JoinHandle<()> {
+	thread::spawn(move || {
+		let res = process_server_req(&context);
+		if let Err(e) = res {
+			println!("process_server_req error: {:?}", e);
+		}
+	})
+}
+
+// the X server went away (e.g. it was restarted), the connection has to be re-established
+fn is_connection_error(error: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+	error.downcast_ref::<ConnectionError>().is_some()
+		|| matches!(
+			error.downcast_ref::<ReplyError>(),
+			Some(ReplyError::ConnectionError(_))
+		) || matches!(
+		error.downcast_ref::<ReplyOrIdError>(),
+		Some(ReplyOrIdError::ConnectionError(_))
+	)
+}
+
+fn process_server_req(context: &InnerContext) -> Result<()> {
+	let atoms = context.server_for_write.atoms;
+	loop {
+		let (event, sequence) = context
+			.server_for_write
+			.conn
+			.wait_for_event_with_sequence()
+			.map_err(|e| format!("wait_for_event error: {:?}", e))?;
+		match event {
+			Event::DestroyNotify(_) => {
+				// This window is being destroyed.
+				println!("Clipboard server window is being destroyed x_x");
+				break;
+			}
+			// The context is being dropped.
+			Event::ClientMessage(event)
+				if event.window == context.server_for_write.win_id
+					&& event.type_ == atoms.SHUTDOWN =>
+			{
+				break;
+			}
+			Event::SelectionClear(event) => {
+				// Someone else has new content in the clipboard, so it is
+				// notifying us that we should delete our data now.
+				println!("Somebody else owns the clipboard now");
+				if event.selection == context.selection {
+					context
+						.owner_timestamp
+						.store(CURRENT_TIME, Ordering::SeqCst);
+					// Clear the clipboard contents
+					context
+						.wait_write_data
+						.write()
+						.map(|mut writer| writer.clear())
+						.map_err(|e| format!("write clipboard data error: {:?}", e))?;
+					if let Ok(mut callback) = context.ownership_lost_callback.lock() {
+						if let Some(callback) = callback.as_mut() {
+							callback();
+						}
 					}
 				}
-			},
+			}
+			// The selection owner has changed, record the sequence number of this event
+			Event::XfixesSelectionNotify(event) if event.selection == context.selection => {
+				context.sequence_number.store(sequence, Ordering::SeqCst);
+				context.invalidate_targets_cache();
+			}
+			// The server time we asked for in `server_timestamp`.
+			Event::PropertyNotify(event)
+				if event.window == context.server_for_write.win_id
+					&& event.atom == atoms.TIMESTAMP_PROP =>
+			{
+				if let Ok(sender) = context.timestamp_sender.lock() {
+					let _ = sender.send(event.time);
+				}
+			}
+			Event::PropertyNotify(event) if event.state == Property::DELETE => {
+				// The requestor of an INCR transfer is ready for the next chunk.
+				context
+					.continue_incr_transfer(event.window, event.atom)
+					.map_err(|e| format!("continue_incr_transfer error: {:?}", e))?;
+			}
+			Event::SelectionRequest(event) => {
+				// Someone is requesting the clipboard content from us.
+				context
+					.handle_selection_request(event)
+					.map_err(|e| format!("handle_selection_request error: {:?}", e))?;
+			}
+			// We've requested the clipboard manager to save our data and this is the answer.
+			Event::SelectionNotify(event) if event.selection == atoms.CLIPBOARD_MANAGER => {
+				if let Ok(mut notify) = context.save_targets_notify.lock() {
+					if let Some(notify) = notify.take() {
+						let _ = notify.send(event.property != x11rb::NONE);
+					}
+				}
+			}
+			// Considering that this thread is not responsible for reading
+			// clipboard contents, any other answer is unexpected.
+			Event::SelectionNotify(_) => {
+				println!("Received a `SelectionNotify` from a selection other than the CLIPBOARD_MANAGER. This is unexpected in this thread.");
+				continue;
+			}
+			_event => {
+				// May be useful for debugging but nothing else really.
+				// trace!("Received unwanted event: {:?}", event);
+			}
+		}
+	}
+	Ok(())
+}
+
+impl Drop for ClipboardContext {
+	fn drop(&mut self) {
+		// errors cannot be reported from drop, close returns them
+		let _ = self.shutdown();
+	}
+}
+
+impl Clipboard for ClipboardContext {
+	//https://source.chromium.org/chromium/chromium/src/+/main:ui/base/x/x11_clipboard_helper.cc;l=224;drc=4cc063ac39c4a0d1f6011421b259a9715bb16de1;bpv=0;bpt=1
+	fn available_formats(&self) -> Result<Vec<String>> {
+		self.with_reconnect(|| {
+			let inner = self.inner();
+			let atom_list: Vec<Atom> = self
+				.read_targets()?
+				.into_iter()
+				.filter(|atom| !inner.ignore_formats.contains(atom))
+				.collect();
+			Ok(inner
+				.server
+				.get_atom_names(&atom_list)
+				.into_iter()
+				.map(|name| name.unwrap_or("Unknown".to_string()))
+				.collect())
+		})
+	}
+
+	fn available_formats_detailed(&self) -> Result<Vec<FormatInfo>> {
+		self.with_reconnect(|| {
+			let inner = self.inner();
+			let atom_list: Vec<Atom> = self
+				.read_targets()?
+				.into_iter()
+				.filter(|atom| !inner.ignore_formats.contains(atom))
+				.collect();
+			let names = inner.server.get_atom_names(&atom_list);
+			Ok(atom_list
+				.into_iter()
+				.zip(names)
+				.map(|(atom, name)| FormatInfo { name, id: atom })
+				.collect())
+		})
+	}
+
+	fn has(&self, format: crate::ContentFormat) -> bool {
+		match self.with_reconnect(|| self.read_targets()) {
+			Ok(targets) => self.targets_contain(&targets, &format),
 			Err(_) => false,
 		}
 	}
 
+	// TARGETS is read once for all the formats
+	fn has_any(&self, formats: &[ContentFormat]) -> bool {
+		match self.with_reconnect(|| self.read_targets()) {
+			Ok(targets) => formats
+				.iter()
+				.any(|format| self.targets_contain(&targets, format)),
+			Err(_) => false,
+		}
+	}
+
+	fn has_all(&self, formats: &[ContentFormat]) -> bool {
+		match self.with_reconnect(|| self.read_targets()) {
+			Ok(targets) => formats
+				.iter()
+				.all(|format| self.targets_contain(&targets, format)),
+			Err(_) => false,
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		match self.with_reconnect(|| self.read_targets()) {
+			Ok(targets) => {
+				let inner = self.inner();
+				targets
+					.iter()
+					.all(|atom| inner.ignore_formats.contains(atom))
+			}
+			// no selection owner or it refuses to answer TARGETS
+			Err(_) => true,
+		}
+	}
+
+	// give up the selection instead of owning an empty one, other programs then see no owner at all
 	fn clear(&self) -> Result<()> {
-		self.write(vec![])
+		self.with_reconnect(|| {
+			let inner = self.inner();
+			inner.invalidate_targets_cache();
+			match inner.wait_write_data.write() {
+				Ok(mut writer) => writer.clear(),
+				Err(_) => return Err("Failed to write clipboard data".into()),
+			}
+			let timestamp = inner
+				.server_timestamp(self.write_timeout)
+				.unwrap_or(CURRENT_TIME);
+			// the selection is taken from its owner even if it is another program
+			inner
+				.server_for_write
+				.conn
+				.set_selection_owner(x11rb::NONE, inner.selection, timestamp)?
+				.check()?;
+			inner.owner_timestamp.store(CURRENT_TIME, Ordering::SeqCst);
+			Ok(())
+		})
 	}
 
 	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
-		let atom = self.inner.server.get_atom(format);
-		match atom {
-			Ok(atom) => self.read(&atom),
+		self.with_reconnect(|| {
+			let atom = self.inner().server.get_atom(format);
+			match atom {
+				Ok(atom) => self.read(&atom),
+				Err(_) => Err("Invalid format".into()),
+			}
+		})
+	}
+
+	// not retried, the sink may already have received part of the data
+	fn read_streaming(&self, format: &str, sink: &mut dyn Write) -> Result<()> {
+		let inner = self.inner();
+		let res = match inner.server.get_atom(format) {
+			Ok(atom) => self.read_to(&atom, sink),
 			Err(_) => Err("Invalid format".into()),
+		};
+		match res {
+			Err(e) if is_connection_error(&*e) => {
+				self.reconnect(&inner)?;
+				Err(e)
+			}
+			res => res,
 		}
 	}
 
 	fn get_text(&self) -> Result<String> {
-		let atoms = self.inner.server.atoms;
-		let text_data = self.read(&atoms.UTF8_STRING);
-		text_data.map_or_else(
-			|_| Ok("".to_string()),
-			|data| Ok(String::from_utf8_lossy(&data).to_string()),
-		)
+		self.with_reconnect(|| self.read_text())
 	}
 
 	fn get_rich_text(&self) -> Result<String> {
-		let atoms = self.inner.server.atoms;
-		let rtf_data = self.read(&atoms.RTF);
-		rtf_data.map_or_else(
-			|_| Ok("".to_string()),
-			|data| Ok(String::from_utf8_lossy(&data).to_string()),
-		)
+		let rtf_data = self.get_rich_text_bytes()?;
+		Ok(String::from_utf8_lossy(&rtf_data).to_string())
 	}
 
 	fn get_html(&self) -> Result<String> {
-		let atoms = self.inner.server.atoms;
-		let html_data = self.read(&atoms.HTML);
-		html_data.map_or_else(
-			|_| Ok("".to_string()),
-			|data| Ok(String::from_utf8_lossy(&data).to_string()),
-		)
+		let html_data = self.get_html_bytes()?;
+		Ok(String::from_utf8_lossy(&html_data).to_string())
+	}
+
+	fn get_text_bytes(&self) -> Result<Vec<u8>> {
+		self.with_reconnect(|| {
+			let (target, data) = self.read_text_target()?;
+			Ok(self.text_to_utf8(target, data))
+		})
+	}
+
+	fn get_rich_text_bytes(&self) -> Result<Vec<u8>> {
+		self.with_reconnect(|| self.read_best_target(&ContentFormat::Rtf, "rich text"))
+	}
+
+	fn get_html_bytes(&self) -> Result<Vec<u8>> {
+		self.with_reconnect(|| self.read_best_target(&ContentFormat::Html, "html"))
 	}
 
 	fn get_image(&self) -> Result<crate::RustImageData> {
-		let atoms = self.inner.server.atoms;
-		let image_bytes = self.read(&atoms.PNG_MIME);
-		match image_bytes {
-			Ok(bytes) => {
-				let image = RustImageData::from_bytes(&bytes);
-				match image {
-					Ok(image) => Ok(image),
-					Err(_) => Err("Invalid image data".into()),
-				}
-			}
-			Err(_) => Err("No image data found".into()),
-		}
+		let bytes = self.with_reconnect(|| self.read_image_bytes())?;
+		RustImageData::from_bytes(&bytes).map_err(|e| format!("Invalid image data: {}", e).into())
+	}
+
+	fn image_dimensions(&self) -> Result<(u32, u32, ImageFormat)> {
+		let bytes = self.with_reconnect(|| self.read_image_bytes())?;
+		image_dimensions_from_bytes(&bytes).map_err(|e| format!("Invalid image data: {}", e).into())
 	}
 
 	fn get_files(&self) -> Result<Vec<String>> {
-		let atoms = self.inner.server.atoms;
-		let file_list_data = self.read(&atoms.FILE_LIST);
-		file_list_data.map_or_else(
-			|_| Ok(vec![]),
-			|data| {
-				let file_list_str = String::from_utf8_lossy(&data).to_string();
-				let mut list = Vec::new();
-				for line in file_list_str.lines() {
-					if !line.starts_with(FILE_PATH_PREFIX) {
-						continue;
-					}
-					list.push(line.to_string())
+		self.with_reconnect(|| {
+			let atoms = self.inner().server.atoms;
+			let file_list_data = self.read(&atoms.FILE_LIST)?;
+			Ok(parse_uri_list(&String::from_utf8_lossy(&file_list_data)))
+		})
+	}
+
+	fn get_files_with_operation(&self) -> Result<(FileOperation, Vec<String>)> {
+		let atoms = self.inner().server.atoms;
+		let targets = self.read_targets().unwrap_or_default();
+		// GNOME file managers put the operation on the first line
+		if targets.contains(&atoms.GNOME_COPY_FILES) {
+			if let Ok(data) = self.read(&atoms.GNOME_COPY_FILES) {
+				if let Some(res) = parse_gnome_copied_files(&String::from_utf8_lossy(&data)) {
+					return Ok(res);
 				}
-				Ok(list)
-			},
-		)
+			}
+		}
+		let files = self.get_files()?;
+		// KDE marks a cut with "1" in a separate target
+		let is_cut = targets.contains(&atoms.KDE_CUT_SELECTION)
+			&& self
+				.read(&atoms.KDE_CUT_SELECTION)
+				.map_or(false, |data| data.starts_with(b"1"));
+		let operation = match is_cut {
+			true => FileOperation::Cut,
+			false => FileOperation::Copy,
+		};
+		Ok((operation, files))
 	}
 
 	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
@@ -578,6 +1617,15 @@ impl Clipboard for ClipboardContext {
 					Ok(files) => contents.push(ClipboardContent::Files(files)),
 					Err(_) => continue,
 				},
+				ContentFormat::Markdown => match self.with_reconnect(|| self.read_markdown()) {
+					Ok(markdown) => contents.push(ClipboardContent::Markdown(markdown)),
+					Err(_) => continue,
+				},
+				ContentFormat::Url => match self.with_reconnect(|| self.read_url()) {
+					Ok(url) => contents.push(ClipboardContent::Url(url)),
+					Err(_) => continue,
+				},
+				ContentFormat::Metafile => continue,
 				ContentFormat::Other(format_name) => match self.get_buffer(format_name) {
 					Ok(buffer) => {
 						contents.push(ClipboardContent::Other(format_name.clone(), buffer))
@@ -590,158 +1638,249 @@ impl Clipboard for ClipboardContext {
 	}
 
 	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
-		let atom = self.inner.server_for_write.get_atom(format)?;
-		let data = ClipboardData {
-			format: atom,
-			data: buffer,
-		};
-		self.write(vec![data])
+		self.with_reconnect(|| {
+			let atom = self.inner().server_for_write.get_atom(format)?;
+			let data = ClipboardData {
+				format: atom,
+				data: buffer.clone(),
+			};
+			self.write(vec![data])
+		})
 	}
 
 	fn set_text(&self, text: String) -> Result<()> {
-		let atoms = self.inner.server_for_write.atoms;
-		let text_bytes = text.as_bytes().to_vec();
+		self.with_reconnect(|| {
+			let atoms = self.inner().server_for_write.atoms;
+			let text_bytes = text.as_bytes().to_vec();
 
-		let data = ClipboardData {
-			format: atoms.UTF8_STRING,
-			data: text_bytes,
-		};
-		self.write(vec![data])
+			let data = ClipboardData {
+				format: atoms.UTF8_STRING,
+				data: text_bytes,
+			};
+			self.write(vec![data])
+		})
 	}
 
 	fn set_rich_text(&self, text: String) -> Result<()> {
-		let atoms = self.inner.server_for_write.atoms;
-		let text_bytes = text.as_bytes().to_vec();
+		self.with_reconnect(|| {
+			let atoms = self.inner().server_for_write.atoms;
+			let text_bytes = text.as_bytes().to_vec();
 
-		let data = ClipboardData {
-			format: atoms.RTF,
-			data: text_bytes,
-		};
-		self.write(vec![data])
+			let data = ClipboardData {
+				format: atoms.RTF,
+				data: text_bytes,
+			};
+			self.write(vec![data])
+		})
 	}
 
 	fn set_html(&self, html: String) -> Result<()> {
-		let atoms = self.inner.server_for_write.atoms;
-		let html_bytes = html.as_bytes().to_vec();
+		self.with_reconnect(|| {
+			let atoms = self.inner().server_for_write.atoms;
+			let html_bytes = html.as_bytes().to_vec();
 
-		let data = ClipboardData {
-			format: atoms.HTML,
-			data: html_bytes,
-		};
-		self.write(vec![data])
+			let data = ClipboardData {
+				format: atoms.HTML,
+				data: html_bytes,
+			};
+			self.write(vec![data])
+		})
 	}
 
 	fn set_image(&self, image: RustImageData) -> Result<()> {
-		let atoms = self.inner.server_for_write.atoms;
 		let image_png = image.to_png()?;
-		let data = ClipboardData {
-			format: atoms.PNG_MIME,
-			data: image_png.get_bytes().to_vec(),
-		};
-		self.write(vec![data])
+		self.with_reconnect(|| {
+			let atoms = self.inner().server_for_write.atoms;
+			let data = ClipboardData {
+				format: atoms.PNG_MIME,
+				data: image_png.get_bytes().to_vec(),
+			};
+			self.write(vec![data])
+		})
+	}
+
+	fn set_image_from_path(&self, path: &str) -> Result<()> {
+		let bytes = std::fs::read(path)?;
+		if !is_png(&bytes) {
+			return self.set_image(RustImageData::from_bytes(&bytes)?);
+		}
+		self.with_reconnect(|| {
+			let data = ClipboardData {
+				format: self.inner().server_for_write.atoms.PNG_MIME,
+				data: bytes.clone(),
+			};
+			self.write(vec![data])
+		})
 	}
 
 	fn set_files(&self, files: Vec<String>) -> Result<()> {
-		let atoms = self.inner.server_for_write.atoms;
-		let data = file_uri_list_to_clipboard_data(files, atoms);
-		self.write(data)
+		self.with_reconnect(|| {
+			let atoms = self.inner().server_for_write.atoms;
+			let data = file_uri_list_to_clipboard_data(files.clone(), FileOperation::Copy, atoms);
+			self.write(data)
+		})
 	}
 
 	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
-		let mut data = Vec::new();
-		let atoms = self.inner.server_for_write.atoms;
-		for content in contents {
-			match content {
-				ClipboardContent::Text(text) => {
-					data.push(ClipboardData {
-						format: atoms.UTF8_STRING,
-						data: text.as_bytes().to_vec(),
-					});
-				}
-				ClipboardContent::Rtf(rtf) => {
-					data.push(ClipboardData {
-						format: atoms.RTF,
-						data: rtf.as_bytes().to_vec(),
-					});
-				}
-				ClipboardContent::Html(html) => {
-					data.push(ClipboardData {
-						format: atoms.HTML,
-						data: html.as_bytes().to_vec(),
-					});
-				}
-				ClipboardContent::Image(image) => {
-					let image_png = image.to_png()?;
-					data.push(ClipboardData {
-						format: atoms.PNG_MIME,
-						data: image_png.get_bytes().to_vec(),
-					});
-				}
-				ClipboardContent::Files(files) => {
-					let data_arr = file_uri_list_to_clipboard_data(files, atoms);
-					data.extend(data_arr);
-				}
-				ClipboardContent::Other(format_name, buffer) => {
-					let atom = self.inner.server_for_write.get_atom(&format_name)?;
-					data.push(ClipboardData {
-						format: atom,
-						data: buffer,
-					});
-				}
+		self.with_reconnect(|| {
+			let data = self.contents_to_data(&contents)?;
+			self.write(data)
+		})
+	}
+
+	fn snapshot(&self) -> Result<ClipboardSnapshot> {
+		let mut item = Vec::new();
+		for info in self.available_formats_detailed()? {
+			// the owner may refuse to convert some of the targets it announces
+			if let Ok(data) = self.read(&info.id) {
+				item.push((info, data));
 			}
 		}
+		Ok(ClipboardSnapshot { items: vec![item] })
+	}
+
+	fn restore(&self, snapshot: ClipboardSnapshot) -> Result<()> {
+		// atoms are shared by all connections to the same display
+		let data = snapshot
+			.items
+			.into_iter()
+			.flatten()
+			.map(|(info, data)| ClipboardData {
+				format: info.id,
+				data,
+			})
+			.collect();
 		self.write(data)
 	}
+
+	fn get_sequence_number(&self) -> Result<u64> {
+		let inner = self.inner();
+		inner.listen_owner_changes()?;
+		Ok(inner.sequence_number.load(Ordering::SeqCst))
+	}
 }
 
 pub struct ClipboardWatcherContext<T: ClipboardHandler> {
-	handlers: Vec<T>,
+	handlers: Vec<HandlerEntry<T>>,
 	stop_signal: Sender<()>,
 	stop_receiver: Receiver<()>,
+	// start_watch 退出后发送，WatcherShutdown::wait_for_completion 等待它
+	done_signal: Sender<()>,
+	done_receiver: Arc<Mutex<Receiver<()>>>,
+	running: bool,
+	// 旧的行为：WatcherShutdown 被丢弃时停止监听
+	stop_on_drop: bool,
+	throttle: EventThrottle,
+	subscribers: Vec<Sender<ClipboardEvent>>,
+	// 传给处理器用来读取剪切板内容
+	clipboard: ClipboardContext,
+	// 监听的选区，默认只有 CLIPBOARD
+	selections: Vec<XSelection>,
 }
 
 unsafe impl<T: ClipboardHandler> Send for ClipboardWatcherContext<T> {}
 
 impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
-	pub fn new() -> Result<Self> {
+	/// zh: 使用指定的剪切板上下文创建监听器，处理器会通过它读取剪切板内容
+	/// en: Create a watcher with the given clipboard context, handlers read the clipboard through it
+	pub fn new_with_clipboard(clipboard: ClipboardContext) -> Result<Self> {
 		let (tx, rx) = mpsc::channel();
+		let (done_tx, done_rx) = mpsc::channel();
 		Ok(Self {
 			handlers: Vec::new(),
 			stop_signal: tx,
 			stop_receiver: rx,
+			done_signal: done_tx,
+			done_receiver: Arc::new(Mutex::new(done_rx)),
+			running: false,
+			stop_on_drop: false,
+			throttle: EventThrottle::default(),
+			subscribers: Vec::new(),
+			clipboard,
+			selections: vec![XSelection::Clipboard],
 		})
 	}
+
+	/// zh: 设置监听的选区，默认只监听 CLIPBOARD，变化的选区可以通过 [`ClipboardEvent::selection`] 获得，需要在开始监听前调用
+	/// en: Set the selections to watch, only CLIPBOARD is watched by default, the selection that changed is reported in [`ClipboardEvent::selection`], must be called before the watch starts
+	pub fn watch_selections(&mut self, selections: &[XSelection]) -> &mut Self {
+		self.selections = selections.to_vec();
+		self
+	}
+
+	/// zh: 设置每秒最多通知处理器的次数，超出的变化会被合并，但一连串变化中的最后一次总会被通知，小于等于 0 表示不限制
+	/// en: Set the maximum number of events per second dispatched to handlers, changes beyond the rate are coalesced but the last change of a burst is always delivered, a value <= 0 means no limit
+	pub fn set_max_event_rate(&mut self, max_events_per_second: f32) -> &mut Self {
+		self.throttle.set_max_event_rate(max_events_per_second);
+		self
+	}
+
+	/// zh: 设置丢弃 [`WatcherShutdown`] 时是否停止监听（旧版本的行为），默认只有调用 [`WatcherShutdown::stop`] 才会停止，只影响之后获得的停止通道
+	/// en: Set whether dropping a [`WatcherShutdown`] stops the watcher (the behavior of older versions), by default only [`WatcherShutdown::stop`] stops it, only affects shutdown channels obtained afterwards
+	pub fn set_stop_on_drop(&mut self, stop_on_drop: bool) -> &mut Self {
+		self.stop_on_drop = stop_on_drop;
+		self
+	}
+
+	/// zh: 订阅剪切板变化事件，返回一个接收变化事件的通道，每次调用都会创建一个新的独立通道
+	/// en: Subscribe to clipboard change events, returns a receiver of the change events, every call creates a new independent channel
+	pub fn subscribe(&mut self) -> Receiver<ClipboardEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.push(tx);
+		rx
+	}
 }
 
 impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
 	fn add_handler(&mut self, f: T) -> &mut Self {
-		self.handlers.push(f);
+		self.handlers.push(HandlerEntry::new(f, None));
 		self
 	}
 
-	fn start_watch(&mut self) {
-		let watch_server = XServerContext::new().expect("Failed to create X server context");
-		let screen = watch_server
-			.conn
-			.setup()
-			.roots
-			.get(watch_server._screen)
-			.expect("Failed to get screen");
-
-		xfixes::query_version(&watch_server.conn, 5, 0)
-			.expect("Failed to query version xfixes is not available");
-		let cookie = xfixes::select_selection_input(
-			&watch_server.conn,
-			screen.root,
-			watch_server.atoms.CLIPBOARD,
-			xfixes::SelectionEventMask::SET_SELECTION_OWNER
-				| xfixes::SelectionEventMask::SELECTION_CLIENT_CLOSE
-				| xfixes::SelectionEventMask::SELECTION_WINDOW_DESTROY,
-		)
-		.expect("Failed to select selection input");
+	fn add_filtered_handler(&mut self, handler: T, formats: Vec<ContentFormat>) -> &mut Self {
+		self.handlers
+			.push(HandlerEntry::new(handler, Some(formats)));
+		self
+	}
+
+	fn add_named_handler(&mut self, name: &str, handler: T) -> &mut Self {
+		insert_named_handler(&mut self.handlers, name, handler);
+		self
+	}
+
+	fn remove_handler(&mut self, index: usize) -> Option<T> {
+		take_handler_at(&mut self.handlers, index)
+	}
 
-		cookie.check().unwrap();
+	fn remove_named_handler(&mut self, name: &str) -> Option<T> {
+		take_named_handler(&mut self.handlers, name)
+	}
 
+	fn start_watch(&mut self) {
+		if self.running {
+			println!("already start watch!");
+			return;
+		}
+		if self.handlers.is_empty() && self.subscribers.is_empty() {
+			println!("no handler, no need to start watch!");
+			return;
+		}
+		self.running = true;
+		// drain the stop signals left over from a previous session (e.g. `WatcherShutdown::stop` called while the watcher was not running),
+		// so that the watcher can be restarted after it has been stopped
+		while self.stop_receiver.try_recv().is_ok() {}
+		let (mut watch_server, mut selections) = match self.connect_watch_server() {
+			Ok(res) => res,
+			Err(e) => {
+				self.report_watch_error(e);
+				self.running = false;
+				let _ = self.done_signal.send(());
+				return;
+			}
+		};
+
+		// the latest change of every selection, so that a burst on one selection does not hide the others
+		let mut last_events: Vec<ClipboardEvent> = Vec::new();
 		loop {
 			if self
 				.stop_receiver
@@ -750,39 +1889,130 @@ impl<T: ClipboardHandler> ClipboardWatcher<T> for ClipboardWatcherContext<T> {
 			{
 				break;
 			}
-			let event = match watch_server
-				.conn
-				.poll_for_event()
-				.expect("Failed to poll for event")
-			{
-				Some(event) => event,
-				None => {
-					continue;
-				}
+			let events = match poll_events(&watch_server.conn) {
+				Ok(events) => events,
+				// the X server went away, watch the selections again on a new connection
+				Err(_) => match self.reconnect_watch_server() {
+					Ok(res) => {
+						(watch_server, selections) = res;
+						continue;
+					}
+					Err(e) => {
+						self.report_watch_error(e);
+						break;
+					}
+				},
 			};
-			if let Event::XfixesSelectionNotify(_) = event {
-				self.handlers
-					.iter_mut()
-					.for_each(|handler| handler.on_clipboard_change());
+			let mut changed = false;
+			for event in events {
+				if let Event::XfixesSelectionNotify(event) = event {
+					let Some((_, selection)) =
+						selections.iter().find(|(atom, _)| *atom == event.selection)
+					else {
+						continue;
+					};
+					changed = true;
+					let mut clipboard_event = ClipboardEvent::new(Some(event.timestamp as u64));
+					clipboard_event.selection = *selection;
+					let own = OWN_WINDOWS
+						.lock()
+						.map_or(false, |windows| windows.contains(&event.owner));
+					if own {
+						clipboard_event.source = ChangeSource::OwnWrite;
+					}
+					last_events.retain(|e| e.selection != *selection);
+					last_events.push(clipboard_event);
+				}
+			}
+			if self.throttle.should_dispatch(changed, Instant::now()) {
+				for event in last_events.drain(..) {
+					// TARGETS is read at most once per event, whatever the number of filtered handlers
+					let mut targets = None;
+					dispatch_to_handlers(&mut self.handlers, &self.clipboard, &event, |format| {
+						targets
+							.get_or_insert_with(|| self.clipboard.read_targets().ok())
+							.as_ref()
+							.map_or(false, |targets| {
+								self.clipboard.targets_contain(targets, format)
+							})
+					});
+					notify_subscribers(&mut self.subscribers, &event);
+				}
 			}
 		}
+		self.running = false;
+		let _ = self.done_signal.send(());
 	}
 
 	fn get_shutdown_channel(&self) -> WatcherShutdown {
-		WatcherShutdown {
-			sender: self.stop_signal.clone(),
-		}
+		WatcherShutdown::new(
+			self.stop_signal.clone(),
+			self.done_receiver.clone(),
+			self.stop_on_drop,
+		)
 	}
 }
 
-pub struct WatcherShutdown {
-	sender: Sender<()>,
+impl<T: ClipboardHandler> ClipboardWatcherContext<T> {
+	// open the connection of the watcher and ask for the owner changes of the watched selections
+	fn connect_watch_server(&self) -> Result<(XServerContext, Vec<(Atom, XSelection)>)> {
+		let watch_server = XServerContext::new_with_options(&ClipboardContextX11Options {
+			display: self.clipboard.display.clone(),
+			..Default::default()
+		})?;
+		let screen = watch_server
+			.conn
+			.setup()
+			.roots
+			.get(watch_server._screen)
+			.ok_or("Failed to get screen")?;
+
+		xfixes::query_version(&watch_server.conn, 5, 0)?.reply()?;
+		let selections: Vec<(Atom, XSelection)> = self
+			.selections
+			.iter()
+			.map(|selection| (selection_atom(&watch_server.atoms, *selection), *selection))
+			.collect();
+		for (atom, _) in &selections {
+			xfixes::select_selection_input(
+				&watch_server.conn,
+				screen.root,
+				*atom,
+				xfixes::SelectionEventMask::SET_SELECTION_OWNER
+					| xfixes::SelectionEventMask::SELECTION_CLIENT_CLOSE
+					| xfixes::SelectionEventMask::SELECTION_WINDOW_DESTROY,
+			)?
+			.check()?;
+		}
+		Ok((watch_server, selections))
+	}
+
+	fn reconnect_watch_server(&self) -> Result<(XServerContext, Vec<(Atom, XSelection)>)> {
+		for attempt in 0..self.clipboard.reconnect_retries {
+			if attempt > 0 {
+				thread::sleep(self.clipboard.reconnect_delay);
+			}
+			if let Ok(res) = self.connect_watch_server() {
+				return Ok(res);
+			}
+		}
+		Err(ClipboardError::ConnectionLost.into())
+	}
+
+	fn report_watch_error(&mut self, error: Box<dyn std::error::Error + Send + Sync>) {
+		self.handlers.iter_mut().for_each(|entry| {
+			entry.handler.on_watch_error(&*error);
+		});
+	}
 }
 
-impl Drop for WatcherShutdown {
-	fn drop(&mut self) {
-		let _ = self.sender.send(());
+// take all the events already received, fails if the connection is lost
+fn poll_events(conn: &RustConnection) -> std::result::Result<Vec<Event>, ConnectionError> {
+	let mut events = Vec::new();
+	while let Some(event) = conn.poll_for_event()? {
+		events.push(event);
 	}
+	Ok(events)
 }
 
 struct XServerContext {
@@ -790,11 +2020,13 @@ struct XServerContext {
 	win_id: u32,
 	_screen: usize,
 	atoms: Atoms,
+	// 原子的名称在连接期间不会改变，缓存起来避免重复查询
+	atom_names: Mutex<HashMap<Atom, String>>,
 }
 
 impl XServerContext {
-	fn new() -> Result<Self> {
-		let (conn, screen) = x11rb::connect(None)?;
+	fn new_with_options(options: &ClipboardContextX11Options) -> Result<Self> {
+		let (conn, screen) = x11rb::connect(options.display.as_deref())?;
 		let win_id = conn.generate_id()?;
 		{
 			let screen = conn.setup().roots.get(screen).unwrap();
@@ -820,20 +2052,222 @@ impl XServerContext {
 			win_id,
 			_screen: screen,
 			atoms,
+			atom_names: Mutex::new(HashMap::new()),
 		})
 	}
 
+	// GetProperty replies are limited in size, read the property in chunks until nothing is left.
+	// the offset of GetProperty is in 32-bit units, not in bytes
+	fn get_full_property(
+		&self,
+		delete: bool,
+		window: Window,
+		property: Atom,
+		property_type: impl Into<Atom>,
+	) -> Result<GetPropertyReply> {
+		let property_type = property_type.into();
+		let chunk_length = (self.conn.maximum_request_bytes() / 4) as u32;
+		// the server only deletes the property once bytes_after is zero
+		let mut reply = self
+			.conn
+			.get_property(delete, window, property, property_type, 0, chunk_length)?
+			.reply()?;
+		while reply.bytes_after > 0 {
+			let next = self
+				.conn
+				.get_property(
+					delete,
+					window,
+					property,
+					property_type,
+					reply.value.len() as u32 / 4,
+					chunk_length,
+				)?
+				.reply()?;
+			if next.type_ != reply.type_ || next.format != reply.format {
+				return Err("The clipboard property changed while reading it".into());
+			}
+			reply.value.extend_from_slice(&next.value);
+			reply.value_len += next.value_len;
+			reply.bytes_after = next.bytes_after;
+		}
+		Ok(reply)
+	}
+
 	fn get_atom(&self, format: &str) -> Result<Atom> {
 		let cookie = self.conn.intern_atom(false, format.as_bytes())?;
 		Ok(cookie.reply()?.atom)
 	}
 
-	fn get_atom_name(&self, atom: Atom) -> Result<String> {
-		let cookie = self.conn.get_atom_name(atom)?;
-		Ok(String::from_utf8_lossy(&cookie.reply()?.name).to_string())
+	// look up the names of several atoms with a single round trip, names that fail to resolve are None
+	fn get_atom_names(&self, atoms: &[Atom]) -> Vec<Option<String>> {
+		let mut cache = match self.atom_names.lock() {
+			Ok(cache) => cache,
+			Err(poisoned) => poisoned.into_inner(),
+		};
+		// send all the requests first, then wait for the replies
+		let cookies: Vec<_> = atoms
+			.iter()
+			.map(|atom| match cache.contains_key(atom) {
+				true => None,
+				false => self.conn.get_atom_name(*atom).ok(),
+			})
+			.collect();
+		atoms
+			.iter()
+			.zip(cookies)
+			.map(|(atom, cookie)| {
+				if let Some(cookie) = cookie {
+					let name = String::from_utf8_lossy(&cookie.reply().ok()?.name).to_string();
+					cache.insert(*atom, name);
+				}
+				cache.get(atom).cloned()
+			})
+			.collect()
+	}
+}
+
+// the atom of the selection to read and write
+fn selection_atom(atoms: &Atoms, selection: XSelection) -> Atom {
+	match selection {
+		XSelection::Clipboard => atoms.CLIPBOARD,
+		XSelection::Primary => AtomEnum::PRIMARY.into(),
+		XSelection::Secondary => AtomEnum::SECONDARY.into(),
 	}
 }
 
+// ask XFIXES for the owner changes of the selection, they arrive as XfixesSelectionNotify events on the connection
+fn select_owner_changes(ctx: &XServerContext, selection: Atom) -> Result<()> {
+	let screen = ctx
+		.conn
+		.setup()
+		.roots
+		.get(ctx._screen)
+		.ok_or("Failed to get screen")?;
+	xfixes::query_version(&ctx.conn, 5, 0)?.reply()?;
+	xfixes::select_selection_input(
+		&ctx.conn,
+		screen.root,
+		selection,
+		xfixes::SelectionEventMask::SET_SELECTION_OWNER
+			| xfixes::SelectionEventMask::SELECTION_CLIENT_CLOSE
+			| xfixes::SelectionEventMask::SELECTION_WINDOW_DESTROY,
+	)?
+	.check()?;
+	Ok(())
+}
+
+// the targets providing each format in order of preference, `Other` formats need to be interned first
+fn format_targets(atoms: &Atoms, format: &ContentFormat) -> Vec<Atom> {
+	match format {
+		ContentFormat::Text => vec![
+			atoms.UTF8_STRING,
+			atoms.UTF8_MIME_0,
+			atoms.UTF8_MIME_1,
+			atoms.STRING,
+			atoms.TEXT,
+			atoms.TEXT_MIME_UNKNOWN,
+		],
+		ContentFormat::Rtf => vec![atoms.RTF, atoms.RTF_1],
+		ContentFormat::Html => vec![atoms.HTML, atoms.HTML_UTF8],
+		// png > tiff > jpeg > bmp > webp, only the targets `image` is built to decode
+		ContentFormat::Image => vec![
+			atoms.PNG_MIME,
+			atoms.TIFF_MIME,
+			atoms.JPEG_MIME,
+			atoms.BMP_MIME,
+			atoms.BMP_X_MIME,
+			atoms.BMP_MS_MIME,
+			#[cfg(feature = "webp")]
+			atoms.WEBP_MIME,
+		],
+		ContentFormat::Files => vec![atoms.FILE_LIST],
+		ContentFormat::Markdown => vec![atoms.MARKDOWN, atoms.MARKDOWN_X],
+		ContentFormat::Url => vec![atoms.MOZ_URL],
+		ContentFormat::Metafile | ContentFormat::Other(_) => vec![],
+	}
+}
+
+// whether the TARGETS of the owner provide a format, given the targets providing it from format_targets
+fn targets_match_format(targets: &[Atom], format_targets: &[Atom]) -> bool {
+	format_targets.iter().any(|target| targets.contains(target))
+}
+
+// every byte of ISO Latin-1 is the code point of the same value
+fn latin1_to_string(data: &[u8]) -> String {
+	data.iter().map(|&b| b as char).collect()
+}
+
+// characters outside of ISO Latin-1 are replaced with '?'
+fn utf8_to_latin1(data: &[u8]) -> Vec<u8> {
+	String::from_utf8_lossy(data)
+		.chars()
+		.map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+		.collect()
+}
+
+// the targets served from the data of another target, so that requestors asking for older or
+// more specific names (STRING for xterm and Java, text/html;charset=utf-8) still get our data
+fn target_aliases(atoms: &Atoms) -> [TargetAlias; 6] {
+	[
+		// GTK applications offer the MIME names of UTF-8 text as well
+		TargetAlias {
+			source: atoms.UTF8_STRING,
+			target: atoms.UTF8_MIME_0,
+			reply_type: atoms.UTF8_MIME_0,
+			convert: <[u8]>::to_vec,
+		},
+		TargetAlias {
+			source: atoms.UTF8_STRING,
+			target: atoms.UTF8_MIME_1,
+			reply_type: atoms.UTF8_MIME_1,
+			convert: <[u8]>::to_vec,
+		},
+		TargetAlias {
+			source: atoms.UTF8_STRING,
+			target: atoms.STRING,
+			reply_type: atoms.STRING,
+			convert: utf8_to_latin1,
+		},
+		// the owner picks the encoding of TEXT and tells it with the type of the reply
+		TargetAlias {
+			source: atoms.UTF8_STRING,
+			target: atoms.TEXT,
+			reply_type: atoms.UTF8_STRING,
+			convert: <[u8]>::to_vec,
+		},
+		TargetAlias {
+			source: atoms.UTF8_STRING,
+			target: atoms.TEXT_MIME_UNKNOWN,
+			reply_type: atoms.TEXT_MIME_UNKNOWN,
+			convert: <[u8]>::to_vec,
+		},
+		TargetAlias {
+			source: atoms.HTML,
+			target: atoms.HTML_UTF8,
+			reply_type: atoms.HTML_UTF8,
+			convert: <[u8]>::to_vec,
+		},
+	]
+}
+
+// the alias of `target` and the stored data it is converted from
+fn find_target_alias<'a>(
+	atoms: &Atoms,
+	data_list: &'a [ClipboardData],
+	target: Atom,
+) -> Option<(TargetAlias, &'a ClipboardData)> {
+	target_aliases(atoms)
+		.into_iter()
+		.filter(|alias| alias.target == target)
+		.find_map(|alias| {
+			data_list
+				.iter()
+				.find(|data| data.format == alias.source)
+				.map(|data| (alias, data))
+		})
+}
+
 // 解析原子标识符列表
 fn parse_atom_list(data: &[u8]) -> Vec<Atom> {
 	data.chunks(4)
@@ -845,7 +2279,51 @@ fn parse_atom_list(data: &[u8]) -> Vec<Atom> {
 		.collect()
 }
 
-fn file_uri_list_to_clipboard_data(file_list: Vec<String>, atoms: Atoms) -> Vec<ClipboardData> {
+// the URL of text/x-moz-url, "url\ntitle" in UTF-16 (UTF-8 from some programs), None if it is empty
+pub(super) fn parse_moz_url(data: &[u8]) -> Option<String> {
+	let text = if data.len() % 2 == 0 && data.contains(&0) {
+		let units: Vec<u16> = data
+			.chunks_exact(2)
+			.map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+			.collect();
+		String::from_utf16_lossy(&units)
+	} else {
+		String::from_utf8_lossy(data).into_owned()
+	};
+	let url = text
+		.trim_start_matches('\u{feff}')
+		.split(['\n', '\t', '\0'])
+		.next()?
+		.trim();
+	(!url.is_empty()).then(|| url.to_owned())
+}
+
+// text/uri-list, lines end with \r\n and lines starting with # are comments
+// https://www.rfc-editor.org/rfc/rfc2483#section-5
+pub(super) fn parse_uri_list(data: &str) -> Vec<String> {
+	data.lines()
+		.map(str::trim)
+		.filter(|line| !line.starts_with('#') && line.starts_with(FILE_PATH_PREFIX))
+		.map(str::to_owned)
+		.collect()
+}
+
+// x-special/gnome-copied-files: the operation on the first line, then one URI per line
+fn parse_gnome_copied_files(data: &str) -> Option<(FileOperation, Vec<String>)> {
+	let (operation, uris) = data.split_once('\n')?;
+	let operation = match operation.trim() {
+		"copy" => FileOperation::Copy,
+		"cut" => FileOperation::Cut,
+		_ => return None,
+	};
+	Some((operation, parse_uri_list(uris)))
+}
+
+fn file_uri_list_to_clipboard_data(
+	file_list: Vec<String>,
+	operation: FileOperation,
+	atoms: Atoms,
+) -> Vec<ClipboardData> {
 	let uri_list: Vec<String> = file_list
 		.iter()
 		.map(|f| {
@@ -871,9 +2349,13 @@ fn file_uri_list_to_clipboard_data(file_list: Vec<String>, atoms: Atoms) -> Vec<
 	let data_text_plain = uri_str_list.join("\r\n");
 	let data_text_utf8 = uri_str_list.join("\n");
 	let data_text_uri_list = uri_list.join("\r\n");
-	let data_gnome_copied_files = ["copy\n", uri_list.join("\n").as_str()].concat();
+	let operation_line = match operation {
+		FileOperation::Copy => "copy\n",
+		FileOperation::Cut => "cut\n",
+	};
+	let data_gnome_copied_files = [operation_line, uri_list.join("\n").as_str()].concat();
 
-	vec![
+	let mut data = vec![
 		ClipboardData {
 			format: atoms.TEXT_MIME_UNKNOWN,
 			data: data_text_plain.as_bytes().to_vec(),
@@ -906,5 +2388,118 @@ fn file_uri_list_to_clipboard_data(file_list: Vec<String>, atoms: Atoms) -> Vec<
 			format: atoms.NAUTILUS_FILE_LIST,
 			data: data_gnome_copied_files.as_bytes().to_vec(),
 		},
-	]
+	];
+	// KDE marks a cut in a separate target
+	if operation == FileOperation::Cut {
+		data.push(ClipboardData {
+			format: atoms.KDE_CUT_SELECTION,
+			data: b"1".to_vec(),
+		});
+	}
+	data
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_uri_list() {
+		let data =
+			"# a comment\r\nfile:///tmp/a.txt\r\nfile:///tmp/b%20c.txt\r\n\r\nhttp://example.com\n";
+		assert_eq!(
+			parse_uri_list(data),
+			vec!["file:///tmp/a.txt", "file:///tmp/b%20c.txt"]
+		);
+	}
+
+	#[test]
+	fn test_parse_gnome_copied_files() {
+		assert_eq!(
+			parse_gnome_copied_files("cut\nfile:///tmp/a.txt\nfile:///tmp/b.txt"),
+			Some((
+				FileOperation::Cut,
+				vec![
+					"file:///tmp/a.txt".to_string(),
+					"file:///tmp/b.txt".to_string()
+				]
+			))
+		);
+		assert_eq!(
+			parse_gnome_copied_files("copy\nfile:///tmp/a.txt").map(|(op, _)| op),
+			Some(FileOperation::Copy)
+		);
+		assert_eq!(parse_gnome_copied_files("move\nfile:///tmp/a.txt"), None);
+	}
+
+	#[test]
+	fn test_parse_moz_url() {
+		let utf16: Vec<u8> = "https://example.com/\nExample"
+			.encode_utf16()
+			.flat_map(u16::to_le_bytes)
+			.collect();
+		assert_eq!(
+			parse_moz_url(&utf16),
+			Some("https://example.com/".to_string())
+		);
+		assert_eq!(
+			parse_moz_url(b"https://example.com/a\tA"),
+			Some("https://example.com/a".to_string())
+		);
+		assert_eq!(parse_moz_url(b""), None);
+	}
+
+	#[test]
+	fn test_utf8_to_latin1() {
+		assert_eq!(utf8_to_latin1("café".as_bytes()), b"caf\xe9");
+		assert_eq!(utf8_to_latin1("a ✓ b".as_bytes()), b"a ? b");
+	}
+
+	#[test]
+	fn test_is_connection_error() {
+		let lost: Box<dyn std::error::Error + Send + Sync> = ConnectionError::UnknownError.into();
+		assert!(is_connection_error(&*lost));
+		let lost: Box<dyn std::error::Error + Send + Sync> =
+			ReplyError::ConnectionError(ConnectionError::UnknownError).into();
+		assert!(is_connection_error(&*lost));
+		let timeout: Box<dyn std::error::Error + Send + Sync> = ClipboardError::Timeout.into();
+		assert!(!is_connection_error(&*timeout));
+	}
+
+	#[test]
+	fn test_targets_match_text_aliases() {
+		// plain values standing in for TARGETS, UTF8_STRING, STRING, RTF and RTF_1
+		let (targets_atom, utf8_string, string, rtf, rtf_1) = (1, 2, 3, 4, 5);
+		let text = [utf8_string, string];
+		let rich_text = [rtf, rtf_1];
+
+		// a legacy owner that only offers STRING
+		let data: Vec<u8> = [targets_atom, string]
+			.iter()
+			.flat_map(|atom: &Atom| atom.to_ne_bytes())
+			.collect();
+		let targets = parse_atom_list(&data);
+
+		assert!(targets_match_format(&targets, &text));
+		assert!(!targets_match_format(&targets, &rich_text));
+
+		let targets = [rtf_1];
+		assert!(targets_match_format(&targets, &rich_text));
+		assert!(!targets_match_format(&targets, &text));
+	}
+
+	#[test]
+	fn test_targets_match_image_types() {
+		// plain values standing in for TARGETS, UTF8_STRING and the image MIME types
+		let (targets_atom, utf8_string) = (1, 2);
+		let (png, tiff, jpeg, bmp) = (3, 4, 5, 6);
+		let image = [png, tiff, jpeg, bmp];
+
+		// e.g. GIMP offers several image types but no PNG
+		let targets = [targets_atom, bmp, jpeg, tiff];
+		assert!(targets_match_format(&targets, &image));
+
+		let targets = [targets_atom, utf8_string];
+		assert!(!targets_match_format(&targets, &image));
+	}
 }
@@ -0,0 +1,19 @@
+use clipboard_rs::{Clipboard, ClipboardContext};
+
+#[test]
+fn test_append_text() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	ctx.clear().unwrap();
+	ctx.append_text("first").unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "first");
+
+	ctx.append_text("second").unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "first\nsecond");
+
+	ctx.append_text_with_separator("third", Some(", ")).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "first\nsecond, third");
+
+	ctx.append_text_with_separator("!", None).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "first\nsecond, third!");
+}
@@ -0,0 +1,26 @@
+#![cfg(target_os = "linux")]
+
+// the environment is shared by the whole test binary, so this test lives in its own file
+use clipboard_rs::{Clipboard, ClipboardContext, ClipboardContextBuilder, LinuxBackend};
+
+#[test]
+fn test_backend_from_env() {
+	std::env::set_var("CLIPBOARD_RS_BACKEND", "memory");
+	let ctx = ClipboardContext::new().unwrap();
+	assert_eq!(ctx.backend(), LinuxBackend::Memory);
+	ctx.set_text("memory".to_string()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "memory");
+
+	// the builder wins over the environment
+	std::env::set_var("DISPLAY", "");
+	let err = ClipboardContextBuilder::default()
+		.force_backend(LinuxBackend::X11)
+		.build()
+		.err()
+		.expect("expected an error");
+	assert!(err.to_string().contains("X11 backend: "), "{}", err);
+
+	std::env::set_var("CLIPBOARD_RS_BACKEND", "gtk");
+	let err = ClipboardContext::new().err().expect("expected an error");
+	assert!(err.to_string().contains("CLIPBOARD_RS_BACKEND"), "{}", err);
+}
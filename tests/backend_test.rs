@@ -0,0 +1,52 @@
+#![cfg(target_os = "linux")]
+
+// the environment is shared by the whole test binary, so this test lives in its own file
+use clipboard_rs::{ClipboardContext, ClipboardContextBuilder, ClipboardError, LinuxBackend};
+
+fn platform_error_message(err: Box<dyn std::error::Error + Send + Sync>) -> String {
+	match err.downcast_ref::<ClipboardError>() {
+		Some(ClipboardError::PlatformError { message, .. }) => message.clone(),
+		other => panic!("unexpected error: {:?}", other),
+	}
+}
+
+#[test]
+fn test_backend_errors() {
+	// a Wayland session without Xwayland and without a reachable compositor
+	std::env::remove_var("CLIPBOARD_RS_BACKEND");
+	std::env::set_var("DISPLAY", "");
+	std::env::set_var("WAYLAND_DISPLAY", "clipboard-rs-missing-compositor");
+
+	let err = ClipboardContext::new().err().expect("expected an error");
+	let message = platform_error_message(err);
+	// with the osc52 feature the terminal is tried instead, there is none in the tests either
+	assert!(
+		message.contains("Wayland") || message.starts_with("OSC 52 backend: "),
+		"{}",
+		message
+	);
+
+	let err = ClipboardContextBuilder::default()
+		.force_backend(LinuxBackend::Wayland)
+		.build()
+		.err()
+		.expect("expected an error");
+	let message = platform_error_message(err);
+	assert!(message.starts_with("Wayland backend: "), "{}", message);
+
+	let err = ClipboardContextBuilder::default()
+		.force_backend(LinuxBackend::X11)
+		.build()
+		.err()
+		.expect("expected an error");
+	let message = platform_error_message(err);
+	assert!(message.starts_with("X11 backend: "), "{}", message);
+
+	let err = ClipboardContextBuilder::default()
+		.force_backend(LinuxBackend::Osc52)
+		.build()
+		.err()
+		.expect("expected an error");
+	let message = platform_error_message(err);
+	assert!(message.starts_with("OSC 52 backend: "), "{}", message);
+}
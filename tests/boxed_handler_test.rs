@@ -0,0 +1,45 @@
+mod common;
+
+use clipboard_rs::{
+	ChangeSource, Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher,
+	ClipboardWatcherContext,
+};
+use common::{recv_distinct, while_watching};
+use std::sync::mpsc::{self, Sender};
+
+struct Logger {
+	sender: Sender<&'static str>,
+}
+
+impl ClipboardHandler for Logger {
+	fn on_clipboard_change(&mut self) {
+		let _ = self.sender.send("logger");
+	}
+}
+
+struct SourceTracker {
+	sender: Sender<&'static str>,
+}
+
+impl ClipboardHandler for SourceTracker {
+	fn on_clipboard_change_with_source(&mut self, _source: ChangeSource) {
+		let _ = self.sender.send("tracker");
+	}
+}
+
+#[test]
+fn test_handlers_of_different_types() {
+	let ctx = ClipboardContext::new().unwrap();
+	let (tx, rx) = mpsc::channel();
+
+	let mut watcher: ClipboardWatcherContext<Box<dyn ClipboardHandler + Send>> =
+		ClipboardWatcherContext::new().unwrap();
+	watcher
+		.add_handler(Box::new(Logger { sender: tx.clone() }))
+		.add_handler(Box::new(SourceTracker { sender: tx }));
+
+	while_watching(vec![watcher], || {
+		ctx.set_text("seen by both handlers".to_string()).unwrap();
+		assert_eq!(recv_distinct(&rx, 2), vec!["logger", "tracker"]);
+	});
+}
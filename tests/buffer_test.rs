@@ -0,0 +1,73 @@
+use clipboard_rs::{Clipboard, ClipboardContext, ContentFormat};
+
+#[test]
+fn test_set_buffers() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let app_format = "application/x-clipboard-rs-test";
+	let fallback_format = "text/x-clipboard-rs-test";
+
+	ctx.set_buffers(vec![
+		(app_format.to_string(), b"app data".to_vec()),
+		(fallback_format.to_string(), b"fallback data".to_vec()),
+	])
+	.unwrap();
+
+	assert!(ctx.has(ContentFormat::Other(app_format.to_string())));
+	assert!(ctx.has(ContentFormat::Other(fallback_format.to_string())));
+	assert_eq!(ctx.get_buffer(app_format).unwrap(), b"app data");
+	assert_eq!(ctx.get_buffer(fallback_format).unwrap(), b"fallback data");
+
+	// no metafile was written, and it is unsupported outside of Windows anyway
+	assert!(!ctx.has(ContentFormat::Metafile));
+	assert!(ctx.get(&[ContentFormat::Metafile]).unwrap().is_empty());
+}
+
+#[test]
+fn test_available_formats_detailed() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let format = "application/x-clipboard-rs-detailed";
+	ctx.set_buffer(format, b"detailed".to_vec()).unwrap();
+
+	let formats = ctx.available_formats_detailed().unwrap();
+	assert!(formats
+		.iter()
+		.any(|info| info.name.as_deref() == Some(format)));
+	assert_eq!(formats.len(), ctx.available_formats().unwrap().len());
+}
+
+#[test]
+fn test_read_streaming() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let format = "application/x-clipboard-rs-streaming";
+	let data: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+	ctx.set_buffer(format, data.clone()).unwrap();
+
+	let mut sink = Vec::new();
+	ctx.read_streaming(format, &mut sink).unwrap();
+	assert!(sink == data);
+}
+
+#[test]
+fn test_set_with_lazy() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let format = "application/x-clipboard-rs-lazy";
+	ctx.set_with_lazy(
+		vec![
+			ContentFormat::Text,
+			ContentFormat::Other(format.to_string()),
+		],
+		move |requested| match requested {
+			ContentFormat::Text => Some(b"lazy text".to_vec()),
+			ContentFormat::Other(_) => Some(b"lazy data".to_vec()),
+			_ => None,
+		},
+	)
+	.unwrap();
+
+	assert_eq!(ctx.get_text().unwrap(), "lazy text");
+	assert_eq!(ctx.get_buffer(format).unwrap(), b"lazy data");
+}
@@ -0,0 +1,17 @@
+use clipboard_rs::{Clipboard, ClipboardContextBuilder, XSelection};
+use std::time::Duration;
+
+#[test]
+fn test_builder() {
+	let ctx = ClipboardContextBuilder::default()
+		.read_timeout(Some(Duration::from_secs(1)))
+		.clipboard_open_attempts(5)
+		.with_retry_delay(Duration::from_millis(10))
+		.with_selection(XSelection::Clipboard)
+		.build()
+		.unwrap();
+
+	let text = "clipboard-rs builder".to_string();
+	ctx.set_text(text.clone()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), text);
+}
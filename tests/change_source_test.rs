@@ -0,0 +1,32 @@
+mod common;
+
+use clipboard_rs::{
+	ChangeSource, Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher,
+	ClipboardWatcherContext,
+};
+use common::{recv_event, while_watching};
+use std::sync::mpsc::{self, Sender};
+
+struct SourceRecorder {
+	sources: Sender<ChangeSource>,
+}
+
+impl ClipboardHandler for SourceRecorder {
+	fn on_clipboard_change_with_source(&mut self, source: ChangeSource) {
+		let _ = self.sources.send(source);
+	}
+}
+
+#[test]
+fn test_own_write_is_tagged() {
+	let ctx = ClipboardContext::new().unwrap();
+	let (tx, rx) = mpsc::channel();
+
+	let mut watcher = ClipboardWatcherContext::new().unwrap();
+	watcher.add_handler(SourceRecorder { sources: tx });
+
+	while_watching(vec![watcher], || {
+		ctx.set_text("written by this process".to_string()).unwrap();
+		assert_eq!(recv_event(&rx), ChangeSource::OwnWrite);
+	});
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use clipboard_rs::common::RustImage;
+use clipboard_rs::{Clipboard, ClipboardContent, RustImageData};
+use common::MockClipboard;
+
+#[test]
+fn test_clone_to() {
+	let source = MockClipboard::new();
+	let target = MockClipboard::new();
+
+	let format = "application/x-clipboard-rs-clone";
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let size = image.get_size();
+	source
+		.set(vec![
+			ClipboardContent::Text("cloned text".to_string()),
+			ClipboardContent::Html("<b>cloned</b>".to_string()),
+			ClipboardContent::Image(image),
+			ClipboardContent::Other(format.to_string(), vec![1, 2, 3]),
+		])
+		.unwrap();
+	target.set_text("old content".to_string()).unwrap();
+
+	source.clone_to(&target).unwrap();
+
+	assert_eq!(target.get_text().unwrap(), "cloned text");
+	assert_eq!(target.get_html().unwrap(), "<b>cloned</b>");
+	assert_eq!(target.get_image().unwrap().get_size(), size);
+	assert_eq!(target.get_buffer(format).unwrap(), vec![1, 2, 3]);
+	assert_eq!(
+		target.available_formats().unwrap().len(),
+		source.available_formats().unwrap().len()
+	);
+}
+
+#[test]
+fn test_clone_empty_clears_target() {
+	let source = MockClipboard::new();
+	let target = MockClipboard::new();
+	target.set_text("old content".to_string()).unwrap();
+
+	source.clone_to(&target).unwrap();
+
+	assert!(target.is_empty());
+}
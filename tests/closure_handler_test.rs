@@ -0,0 +1,23 @@
+mod common;
+
+use clipboard_rs::{Clipboard, ClipboardContext, ClipboardWatcher, ClipboardWatcherContext};
+use common::{recv_event, while_watching};
+use std::sync::mpsc;
+
+#[test]
+fn test_closure_handler() {
+	let ctx = ClipboardContext::new().unwrap();
+	let (tx, rx) = mpsc::channel();
+
+	let mut watcher = ClipboardWatcherContext::new().unwrap();
+	let mut changes = 0;
+	watcher.add_handler(move || {
+		changes += 1;
+		let _ = tx.send(changes);
+	});
+
+	while_watching(vec![watcher], || {
+		ctx.set_text("seen by the closure".to_string()).unwrap();
+		assert_eq!(recv_event(&rx), 1);
+	});
+}
@@ -0,0 +1,226 @@
+// every test file includes this module but uses only some of it
+#![allow(dead_code)]
+
+use clipboard_rs::common::RustImage;
+use clipboard_rs::{
+	Clipboard, ClipboardContent, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
+	ContentFormat, Result, RustImageData, WatcherShutdown,
+};
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const TEXT: &str = "text/plain";
+const RTF: &str = "text/rtf";
+const HTML: &str = "text/html";
+const PNG: &str = "image/png";
+const FILE_LIST: &str = "text/uri-list";
+const MARKDOWN: &str = "text/markdown";
+const URL: &str = "text/x-moz-url";
+
+/// An in-memory clipboard owned by the test, independent of any other instance
+#[derive(Default)]
+pub struct MockClipboard {
+	store: Mutex<Vec<(String, Vec<u8>)>>,
+}
+
+impl MockClipboard {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn read(&self, format: &str) -> Result<Vec<u8>> {
+		self.store
+			.lock()
+			.unwrap()
+			.iter()
+			.find(|(name, _)| name == format)
+			.map(|(_, data)| data.clone())
+			.ok_or_else(|| format!("no data of format {}", format).into())
+	}
+
+	fn read_string(&self, format: &str) -> Result<String> {
+		Ok(String::from_utf8(self.read(format)?)?)
+	}
+}
+
+fn format_name(format: &ContentFormat) -> Option<&str> {
+	match format {
+		ContentFormat::Text => Some(TEXT),
+		ContentFormat::Rtf => Some(RTF),
+		ContentFormat::Html => Some(HTML),
+		ContentFormat::Image => Some(PNG),
+		ContentFormat::Files => Some(FILE_LIST),
+		ContentFormat::Markdown => Some(MARKDOWN),
+		ContentFormat::Url => Some(URL),
+		ContentFormat::Metafile => None,
+		ContentFormat::Other(format) => Some(format),
+	}
+}
+
+impl Clipboard for MockClipboard {
+	fn available_formats(&self) -> Result<Vec<String>> {
+		let store = self.store.lock().unwrap();
+		Ok(store.iter().map(|(name, _)| name.clone()).collect())
+	}
+
+	fn has(&self, format: ContentFormat) -> bool {
+		format_name(&format).map_or(false, |name| self.read(name).is_ok())
+	}
+
+	fn clear(&self) -> Result<()> {
+		self.store.lock().unwrap().clear();
+		Ok(())
+	}
+
+	fn get_buffer(&self, format: &str) -> Result<Vec<u8>> {
+		self.read(format)
+	}
+
+	fn get_text(&self) -> Result<String> {
+		self.read_string(TEXT)
+	}
+
+	fn get_rich_text(&self) -> Result<String> {
+		self.read_string(RTF)
+	}
+
+	fn get_html(&self) -> Result<String> {
+		self.read_string(HTML)
+	}
+
+	fn get_image(&self) -> Result<RustImageData> {
+		RustImageData::from_bytes(&self.read(PNG)?)
+	}
+
+	fn get_files(&self) -> Result<Vec<String>> {
+		Ok(self
+			.read_string(FILE_LIST)?
+			.lines()
+			.map(str::to_owned)
+			.collect())
+	}
+
+	fn get(&self, formats: &[ContentFormat]) -> Result<Vec<ClipboardContent>> {
+		let mut contents = Vec::new();
+		for format in formats {
+			let content = match format {
+				ContentFormat::Text => self.get_text().map(ClipboardContent::Text),
+				ContentFormat::Rtf => self.get_rich_text().map(ClipboardContent::Rtf),
+				ContentFormat::Html => self.get_html().map(ClipboardContent::Html),
+				ContentFormat::Image => self.get_image().map(ClipboardContent::Image),
+				ContentFormat::Files => self.get_files().map(ClipboardContent::Files),
+				ContentFormat::Markdown => {
+					self.read_string(MARKDOWN).map(ClipboardContent::Markdown)
+				}
+				ContentFormat::Url => self.read_string(URL).map(ClipboardContent::Url),
+				ContentFormat::Metafile => continue,
+				ContentFormat::Other(name) => self
+					.get_buffer(name)
+					.map(|buffer| ClipboardContent::Other(name.clone(), buffer)),
+			};
+			if let Ok(content) = content {
+				contents.push(content);
+			}
+		}
+		Ok(contents)
+	}
+
+	fn set_buffer(&self, format: &str, buffer: Vec<u8>) -> Result<()> {
+		self.set(vec![ClipboardContent::Other(format.to_owned(), buffer)])
+	}
+
+	fn set_text(&self, text: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Text(text)])
+	}
+
+	fn set_rich_text(&self, text: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Rtf(text)])
+	}
+
+	fn set_html(&self, html: String) -> Result<()> {
+		self.set(vec![ClipboardContent::Html(html)])
+	}
+
+	fn set_image(&self, image: RustImageData) -> Result<()> {
+		self.set(vec![ClipboardContent::Image(image)])
+	}
+
+	fn set_files(&self, files: Vec<String>) -> Result<()> {
+		self.set(vec![ClipboardContent::Files(files)])
+	}
+
+	fn set(&self, contents: Vec<ClipboardContent>) -> Result<()> {
+		let mut data = Vec::new();
+		for content in contents {
+			data.push(match content {
+				ClipboardContent::Text(text) => (TEXT.to_owned(), text.into_bytes()),
+				ClipboardContent::Rtf(rtf) => (RTF.to_owned(), rtf.into_bytes()),
+				ClipboardContent::Html(html) => (HTML.to_owned(), html.into_bytes()),
+				ClipboardContent::Image(image) => {
+					(PNG.to_owned(), image.to_png()?.get_bytes().to_vec())
+				}
+				ClipboardContent::Files(files) | ClipboardContent::FilesWithOperation(files, _) => {
+					(FILE_LIST.to_owned(), files.join("\n").into_bytes())
+				}
+				ClipboardContent::Markdown(markdown) => {
+					(MARKDOWN.to_owned(), markdown.into_bytes())
+				}
+				ClipboardContent::Url(url) => (URL.to_owned(), url.into_bytes()),
+				ClipboardContent::Other(format, buffer) => (format, buffer),
+			});
+		}
+		*self.store.lock().unwrap() = data;
+		Ok(())
+	}
+}
+
+/// Start the watcher on its own thread, the join handle gives the watcher back once it stops
+pub fn spawn_watcher<T: ClipboardHandler + 'static>(
+	mut watcher: ClipboardWatcherContext<T>,
+) -> (WatcherShutdown, JoinHandle<ClipboardWatcherContext<T>>) {
+	let shutdown = watcher.get_shutdown_channel();
+	let handle = thread::spawn(move || {
+		watcher.start_watch();
+		watcher
+	});
+	(shutdown, handle)
+}
+
+/// Run `test` while the watchers are running, then stop them and wait for their threads
+pub fn while_watching<T: ClipboardHandler + 'static>(
+	watchers: Vec<ClipboardWatcherContext<T>>,
+	test: impl FnOnce(),
+) {
+	let running: Vec<_> = watchers.into_iter().map(spawn_watcher).collect();
+	// give the watchers time to start listening
+	thread::sleep(Duration::from_secs(1));
+	test();
+	for (shutdown, handle) in running {
+		shutdown.stop();
+		handle.join().unwrap();
+	}
+}
+
+/// Wait up to 3 seconds for the next event of a handler
+pub fn recv_event<T>(events: &Receiver<T>) -> T {
+	events
+		.recv_timeout(Duration::from_secs(3))
+		.expect("the handler was not called")
+}
+
+/// Collect `count` different events, other tests may change the clipboard at the same time so
+/// repeated events are skipped, stops early when no event arrives for 3 seconds
+pub fn recv_distinct<T: Ord>(events: &Receiver<T>, count: usize) -> Vec<T> {
+	let mut received = Vec::new();
+	while received.len() < count {
+		match events.recv_timeout(Duration::from_secs(3)) {
+			Ok(event) if !received.contains(&event) => received.push(event),
+			Ok(_) => continue,
+			Err(_) => break,
+		}
+	}
+	received.sort();
+	received
+}
@@ -0,0 +1,44 @@
+use clipboard_rs::{
+	ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext, ContentFormat, WatcherShutdown,
+};
+
+struct NoopHandler;
+
+impl ClipboardHandler for NoopHandler {}
+
+// a watcher of another crate only has to implement the required methods
+struct CountingWatcher {
+	inner: ClipboardWatcherContext<NoopHandler>,
+	added: usize,
+}
+
+impl ClipboardWatcher<NoopHandler> for CountingWatcher {
+	fn add_handler(&mut self, handler: NoopHandler) -> &mut Self {
+		self.added += 1;
+		self.inner.add_handler(handler);
+		self
+	}
+
+	fn start_watch(&mut self) {
+		self.inner.start_watch();
+	}
+
+	fn get_shutdown_channel(&self) -> WatcherShutdown {
+		self.inner.get_shutdown_channel()
+	}
+}
+
+#[test]
+fn test_custom_watcher_defaults() {
+	let mut watcher = CountingWatcher {
+		inner: ClipboardWatcherContext::new().unwrap(),
+		added: 0,
+	};
+
+	watcher.add_filtered_handler(NoopHandler, vec![ContentFormat::Text]);
+	watcher.add_named_handler("named", NoopHandler);
+	assert_eq!(watcher.added, 2);
+
+	assert!(watcher.remove_handler(0).is_none());
+	assert!(watcher.remove_named_handler("named").is_none());
+}
@@ -0,0 +1,45 @@
+use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext, FileOperation};
+use std::path::PathBuf;
+
+#[test]
+fn test_set_files_with_operation() {
+	let ctx = ClipboardContext::new().unwrap();
+	let files = vec!["/tmp/clipboard-rs-cut.txt".to_string()];
+
+	ctx.set_files_with_operation(files.clone(), FileOperation::Cut)
+		.unwrap();
+	assert_eq!(
+		ctx.get_files_as_paths().unwrap(),
+		vec![PathBuf::from(&files[0])]
+	);
+	// Finder has no cut of files
+	#[cfg(not(target_os = "macos"))]
+	assert_eq!(
+		ctx.get_files_with_operation().unwrap().0,
+		FileOperation::Cut
+	);
+
+	ctx.set(vec![ClipboardContent::FilesWithOperation(
+		files,
+		FileOperation::Copy,
+	)])
+	.unwrap();
+	assert_eq!(
+		ctx.get_files_with_operation().unwrap().0,
+		FileOperation::Copy
+	);
+}
+
+#[test]
+fn test_content_operation() {
+	let files = vec!["/tmp/a.txt".to_string()];
+	assert_eq!(
+		ClipboardContent::Files(files.clone()).operation(),
+		Some(FileOperation::Copy)
+	);
+	assert_eq!(
+		ClipboardContent::FilesWithOperation(files, FileOperation::Cut).operation(),
+		Some(FileOperation::Cut)
+	);
+	assert_eq!(ClipboardContent::Text("a".to_string()).operation(), None);
+}
@@ -0,0 +1,29 @@
+use clipboard_rs::{Clipboard, ClipboardContext};
+use std::path::PathBuf;
+
+#[cfg(target_os = "macos")]
+const TMP_PATH: &str = "/tmp/";
+#[cfg(target_os = "windows")]
+const TMP_PATH: &str = "C:\\Windows\\Temp\\";
+#[cfg(all(
+	unix,
+	not(any(
+		target_os = "macos",
+		target_os = "ios",
+		target_os = "android",
+		target_os = "emscripten"
+	))
+))]
+const TMP_PATH: &str = "/tmp/";
+
+#[test]
+fn test_get_files_as_paths() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let file = format!("{}clipboard_rs file with spaces.txt", TMP_PATH);
+	std::fs::write(&file, "hello world").unwrap();
+
+	ctx.set_files(vec![file.clone()]).unwrap();
+
+	assert_eq!(ctx.get_files_as_paths().unwrap(), vec![PathBuf::from(file)]);
+}
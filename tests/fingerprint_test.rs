@@ -0,0 +1,26 @@
+use clipboard_rs::common::RustImage;
+use clipboard_rs::{Clipboard, ClipboardContext, RustImageData};
+
+#[test]
+fn test_content_fingerprint() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	ctx.set_text("fingerprint".to_string()).unwrap();
+	let first = ctx.content_fingerprint().unwrap();
+	assert_eq!(ctx.content_fingerprint().unwrap(), first);
+
+	// writing the same content again gives the same fingerprint
+	ctx.set_text("fingerprint".to_string()).unwrap();
+	assert_eq!(ctx.content_fingerprint().unwrap(), first);
+
+	ctx.set_text("fingerprint2".to_string()).unwrap();
+	assert_ne!(ctx.content_fingerprint().unwrap(), first);
+
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	ctx.set_image(image).unwrap();
+	let image_fingerprint = ctx.content_fingerprint().unwrap();
+	assert_ne!(image_fingerprint, first);
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	ctx.set_image(image).unwrap();
+	assert_eq!(ctx.content_fingerprint().unwrap(), image_fingerprint);
+}
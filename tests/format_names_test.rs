@@ -0,0 +1,18 @@
+use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext};
+
+#[test]
+fn test_available_format_names() {
+	let ctx = ClipboardContext::new().unwrap();
+	ctx.set(vec![
+		ClipboardContent::Text("text".to_string()),
+		ClipboardContent::Html("<b>html</b>".to_string()),
+	])
+	.unwrap();
+
+	let names = ctx.available_format_names().unwrap();
+	assert!(!names.is_empty());
+	assert!(names.len() <= ctx.available_formats().unwrap().len());
+	for (i, name) in names.iter().enumerate() {
+		assert!(!names[i + 1..].contains(name), "{} is repeated", name);
+	}
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use clipboard_rs::{
+	Clipboard, ClipboardContext, ClipboardEvent, ClipboardHandler, ClipboardWatcher,
+	ClipboardWatcherContext,
+};
+use common::{recv_event, while_watching};
+use std::sync::mpsc::{self, Sender};
+
+struct TextReader {
+	texts: Sender<String>,
+}
+
+impl ClipboardHandler for TextReader {
+	fn on_clipboard_change_ctx(&mut self, clipboard: &dyn Clipboard, _event: &ClipboardEvent) {
+		if let Ok(text) = clipboard.get_text() {
+			let _ = self.texts.send(text);
+		}
+	}
+}
+
+#[test]
+fn test_handler_reads_shared_clipboard() {
+	let ctx = ClipboardContext::new().unwrap();
+	let (tx, rx) = mpsc::channel();
+
+	let mut watcher = ClipboardWatcherContext::new().unwrap();
+	watcher.add_handler(TextReader { texts: tx });
+
+	while_watching(vec![watcher], || {
+		let text = "read by the handler".to_string();
+		ctx.set_text(text.clone()).unwrap();
+		assert_eq!(recv_event(&rx), text);
+	});
+}
@@ -0,0 +1,17 @@
+use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext, ContentFormat};
+
+#[test]
+fn test_has_any_has_all() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	ctx.set(vec![
+		ClipboardContent::Text("hello".to_string()),
+		ClipboardContent::Html("<b>hello</b>".to_string()),
+	])
+	.unwrap();
+
+	assert!(ctx.has_any(&[ContentFormat::Image, ContentFormat::Text]));
+	assert!(!ctx.has_any(&[ContentFormat::Image, ContentFormat::Files]));
+	assert!(ctx.has_all(&[ContentFormat::Text, ContentFormat::Html]));
+	assert!(!ctx.has_all(&[ContentFormat::Text, ContentFormat::Image]));
+}
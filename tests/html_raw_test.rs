@@ -0,0 +1,13 @@
+use clipboard_rs::{Clipboard, ClipboardContext, ContentFormat};
+
+#[test]
+fn test_set_html_raw() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let raw_html = "<div><b>written without a header</b></div>";
+	ctx.set_html_raw(raw_html.to_string()).unwrap();
+	assert!(ctx.has(ContentFormat::Html));
+	// the data has no CF_HTML header on Windows, get_html returns it as a whole
+	assert_eq!(ctx.get_html().unwrap(), raw_html);
+	assert_eq!(ctx.get_html_full_document().unwrap(), raw_html);
+}
@@ -0,0 +1,19 @@
+use clipboard_rs::common::{RustImage, RustImageData};
+use clipboard_rs::{Clipboard, ClipboardContext, ImageFormat};
+
+#[test]
+fn test_image_dimensions() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let (width, height) = image.get_size();
+	ctx.set_image(image).unwrap();
+
+	assert_eq!(
+		ctx.image_dimensions().unwrap(),
+		(width, height, ImageFormat::Png)
+	);
+
+	ctx.set_text("not an image".to_string()).unwrap();
+	assert!(ctx.image_dimensions().is_err());
+}
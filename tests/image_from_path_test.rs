@@ -0,0 +1,24 @@
+use clipboard_rs::common::{RustImage, RustImageData};
+use clipboard_rs::{Clipboard, ClipboardContext, ContentFormat};
+
+#[test]
+fn test_set_image_from_path() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	ctx.set_image_from_path("tests/test.png").unwrap();
+	assert!(ctx.has(ContentFormat::Image));
+
+	let expected = RustImageData::from_path("tests/test.png").unwrap();
+	let image = ctx.get_image().unwrap();
+	assert_eq!(image.get_size(), expected.get_size());
+
+	// the PNG file is written without re-encoding
+	if std::env::var("CLIPBOARD_RS_BACKEND").as_deref() == Ok("memory") {
+		assert_eq!(
+			ctx.get_buffer("image/png").unwrap(),
+			std::fs::read("tests/test.png").unwrap()
+		);
+	}
+
+	assert!(ctx.set_image_from_path("tests/missing.png").is_err());
+}
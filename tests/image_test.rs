@@ -1,7 +1,11 @@
 use clipboard_rs::{
 	common::{RustImage, RustImageData},
-	Clipboard, ClipboardContext, ContentFormat,
+	Clipboard, ClipboardContent, ClipboardContext, ContentFormat, FilterType, ImageFormat,
 };
+use static_assertions::assert_impl_all;
+
+// images are decoded on one thread and handed to another, e.g. by watcher handlers
+assert_impl_all!(RustImageData: Send, Sync);
 
 #[test]
 fn test_image() {
@@ -9,9 +13,7 @@ fn test_image() {
 
 	let rust_img = RustImageData::from_path("tests/test.png").unwrap();
 
-	let binding = RustImageData::from_path("tests/test.png").unwrap();
-
-	let rust_img_bytes = binding.to_png().unwrap();
+	let expected = RustImageData::from_path("tests/test.png").unwrap();
 
 	ctx.set_image(rust_img).unwrap();
 
@@ -19,8 +21,211 @@ fn test_image() {
 
 	let clipboard_img = ctx.get_image().unwrap();
 
+	// transcoding through DIB may slightly change the color values
+	assert!(clipboard_img.compare_pixels(&expected, 2));
+
+	// writing an image together with other contents must not clobber them
+	ctx.set(vec![
+		ClipboardContent::Text("image caption".to_string()),
+		ClipboardContent::Image(RustImageData::from_path("tests/test.png").unwrap()),
+	])
+	.unwrap();
+
+	assert!(ctx.has(ContentFormat::Text));
+	assert!(ctx.has(ContentFormat::Image));
+	assert_eq!(ctx.get_text().unwrap(), "image caption");
+}
+
+#[test]
+fn test_base64_png() {
+	let rust_img = RustImageData::from_path("tests/test.png").unwrap();
+
+	let data_uri = rust_img.to_base64_png().unwrap();
+	assert!(data_uri.starts_with("data:image/png;base64,"));
+
+	let from_uri = RustImageData::from_base64_png(&data_uri).unwrap();
+	assert_eq!(from_uri.to_rgba8().unwrap(), rust_img.to_rgba8().unwrap());
+
+	assert!(from_uri.compare_pixels(&rust_img, 0));
+	assert!(!from_uri.compare_pixels(&RustImageData::empty(), 0));
+
+	let bare = data_uri.trim_start_matches("data:image/png;base64,");
+	let from_bare = RustImageData::from_base64_png(bare).unwrap();
+	assert_eq!(from_bare.to_rgba8().unwrap(), rust_img.to_rgba8().unwrap());
+}
+
+#[test]
+fn test_to_tiff() {
+	let rust_img = RustImageData::from_path("tests/test.png").unwrap();
+
+	let tiff = rust_img.to_tiff().unwrap();
 	assert_eq!(
-		clipboard_img.to_png().unwrap().get_bytes().len(),
-		rust_img_bytes.get_bytes().len()
+		RustImageData::guess_format(tiff.get_bytes()),
+		Some(ImageFormat::Tiff)
 	);
+	let decoded = RustImageData::from_bytes(tiff.get_bytes()).unwrap();
+	assert!(decoded.compare_pixels(&rust_img, 0));
+}
+
+#[cfg(feature = "webp")]
+#[test]
+fn test_to_webp() {
+	let rust_img = RustImageData::from_path("tests/test.png").unwrap();
+
+	let webp = rust_img.to_webp().unwrap();
+	let decoded = RustImageData::from_bytes(webp.get_bytes()).unwrap();
+
+	assert_eq!(decoded.get_size(), rust_img.get_size());
+}
+
+#[test]
+fn test_guess_format() {
+	let png = std::fs::read("tests/test.png").unwrap();
+	assert_eq!(RustImageData::guess_format(&png), Some(ImageFormat::Png));
+	assert_eq!(RustImageData::guess_format(b"not an image"), None);
+
+	let image = RustImageData::from_bytes_with_format(&png, ImageFormat::Png).unwrap();
+	let expected = RustImageData::from_path("tests/test.png").unwrap();
+	assert!(image.compare_pixels(&expected, 0));
+
+	let jpeg = expected.to_jpeg().unwrap();
+	assert_eq!(
+		RustImageData::guess_format(jpeg.get_bytes()),
+		Some(ImageFormat::Jpeg)
+	);
+	assert!(RustImageData::from_bytes_with_format(jpeg.get_bytes(), ImageFormat::Png).is_err());
+}
+
+#[test]
+fn test_rotate_and_flip() {
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let (width, height) = image.get_size();
+	// a square image would not show the swap
+	let image = image
+		.resize(width, height / 2 + 1, FilterType::Nearest)
+		.unwrap();
+	let (width, height) = image.get_size();
+	assert_ne!(width, height);
+
+	let rotated = image.rotate(90).unwrap();
+	assert_eq!(rotated.get_size(), (height, width));
+	assert!(rotated.rotate(-90).unwrap().compare_pixels(&image, 0));
+	assert_eq!(image.rotate(180).unwrap().get_size(), (width, height));
+	assert!(image.rotate(45).is_err());
+
+	let flipped = image.flip_horizontal().unwrap();
+	assert!(flipped.flip_horizontal().unwrap().compare_pixels(&image, 0));
+	let flipped = image.flip_vertical().unwrap();
+	assert!(flipped.flip_vertical().unwrap().compare_pixels(&image, 0));
+}
+
+#[test]
+fn test_image_buffer_format() {
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+
+	let png = image.to_png().unwrap();
+	assert_eq!(png.format(), ImageFormat::Png);
+	assert_eq!(png.mime_type(), "image/png");
+
+	let jpeg = image.to_jpeg().unwrap();
+	assert_eq!(jpeg.format(), ImageFormat::Jpeg);
+	assert_eq!(jpeg.mime_type(), "image/jpeg");
+}
+
+#[test]
+fn test_image_buffer_detect_format() {
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+
+	let png = image.to_png().unwrap();
+	assert_eq!(png.detect_format(), Some(ImageFormat::Png));
+	assert!(png.is_png());
+	assert!(!png.is_jpeg());
+
+	let jpeg = image.to_jpeg().unwrap();
+	assert_eq!(jpeg.detect_format(), Some(ImageFormat::Jpeg));
+	assert!(jpeg.is_jpeg());
+	assert!(!jpeg.is_png());
+
+	// the bmp encoder is only enabled on Windows
+	if let Ok(bitmap) = image.to_bitmap() {
+		assert_eq!(bitmap.detect_format(), Some(ImageFormat::Bmp));
+		assert!(!bitmap.is_png() && !bitmap.is_jpeg());
+	}
+}
+
+#[test]
+fn test_exif_orientation() {
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	assert!(image.get_exif_data().is_none());
+	assert!(image.get_image_orientation().is_none());
+
+	// a little endian TIFF header with a single IFD entry: Orientation (0x0112) = 6
+	let mut tiff = b"II*\0".to_vec();
+	tiff.extend_from_slice(&8u32.to_le_bytes());
+	tiff.extend_from_slice(&1u16.to_le_bytes());
+	tiff.extend_from_slice(&0x0112u16.to_le_bytes());
+	tiff.extend_from_slice(&3u16.to_le_bytes());
+	tiff.extend_from_slice(&1u32.to_le_bytes());
+	tiff.extend_from_slice(&6u16.to_le_bytes());
+	tiff.extend_from_slice(&0u16.to_le_bytes());
+	tiff.extend_from_slice(&0u32.to_le_bytes());
+
+	// insert an APP1 segment right after the SOI marker of the JPEG
+	let jpeg = image.to_jpeg().unwrap().get_bytes().to_vec();
+	let mut app1 = vec![0xFF, 0xE1];
+	app1.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+	app1.extend_from_slice(b"Exif\0\0");
+	app1.extend_from_slice(&tiff);
+	let mut bytes = jpeg[..2].to_vec();
+	bytes.extend_from_slice(&app1);
+	bytes.extend_from_slice(&jpeg[2..]);
+
+	let image = RustImageData::from_bytes(&bytes).unwrap();
+	assert_eq!(image.get_exif_data(), Some(tiff));
+	assert_eq!(image.get_image_orientation(), Some(6));
+}
+
+#[test]
+fn test_clone_and_debug() {
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let (width, height) = image.get_size();
+
+	let content = ClipboardContent::Image(image);
+	let cloned = content.clone();
+	match cloned {
+		ClipboardContent::Image(cloned) => assert_eq!(cloned.get_size(), (width, height)),
+		_ => panic!("the clone is not an image"),
+	}
+	assert_eq!(
+		format!("{:?}", content),
+		format!("Image(Image {{ width: {}, height: {} }})", width, height)
+	);
+	assert_eq!(
+		format!("{:?}", ClipboardContent::Text("text".to_string())),
+		"Text(\"text\")"
+	);
+}
+
+#[test]
+fn test_with_pixels() {
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let (width, height) = image.get_size();
+
+	let expected = image.to_rgba8().unwrap();
+	let opaque = image
+		.with_pixels(|pixels| {
+			assert_eq!(pixels.dimensions(), (width, height));
+			assert_eq!(pixels.as_raw(), expected.as_raw());
+			pixels.pixels().filter(|pixel| pixel[3] == 255).count()
+		})
+		.unwrap();
+	assert!(opaque <= (width * height) as usize);
+
+	// an image with another pixel format is converted for the call
+	let rgb =
+		RustImageData::from_dynamic_image(image.get_dynamic_image().unwrap().to_rgb8().into());
+	let size = rgb.with_pixels(|pixels| pixels.dimensions()).unwrap();
+	assert_eq!(size, (width, height));
+
+	assert!(RustImageData::empty().with_pixels(|_| ()).is_err());
 }
@@ -0,0 +1,28 @@
+#![cfg(target_os = "macos")]
+
+use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext};
+
+#[test]
+fn test_get_items() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let text = "clipboard-rs item text".to_string();
+	let html = "<html><body>clipboard-rs item html</body></html>".to_string();
+
+	ctx.set(vec![
+		ClipboardContent::Text(text.clone()),
+		ClipboardContent::Html(html.clone()),
+	])
+	.unwrap();
+
+	let items = ctx.get_items().unwrap();
+	assert_eq!(items.len(), 2);
+	assert!(items
+		.iter()
+		.flatten()
+		.any(|c| matches!(c, ClipboardContent::Text(t) if *t == text)));
+	assert!(items
+		.iter()
+		.flatten()
+		.any(|c| matches!(c, ClipboardContent::Html(h) if *h == html)));
+}
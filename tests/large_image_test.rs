@@ -0,0 +1,31 @@
+use clipboard_rs::{
+	common::{RustImage, RustImageData},
+	Clipboard, ClipboardContext, ContentFormat,
+};
+use image::{DynamicImage, RgbaImage};
+
+#[test]
+fn test_large_image() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	// noise does not compress, so the encoded png is about 2 MB
+	let (width, height) = (1024, 512);
+	let mut seed: u32 = 0x2545_f491;
+	let pixels = (0..width * height * 4)
+		.map(|_| {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			(seed >> 24) as u8
+		})
+		.collect();
+	let rgba = RgbaImage::from_raw(width, height, pixels).unwrap();
+	let rust_img = RustImageData::from_dynamic_image(DynamicImage::ImageRgba8(rgba.clone()));
+
+	ctx.set_image(rust_img).unwrap();
+	assert!(ctx.has(ContentFormat::Image));
+
+	let clipboard_img = ctx.get_image().unwrap();
+	assert_eq!(clipboard_img.get_size(), (width, height));
+	assert_eq!(clipboard_img.to_rgba8().unwrap(), rgba);
+}
@@ -0,0 +1,51 @@
+#![cfg(target_os = "macos")]
+
+use clipboard_rs::{
+	common::{RustImage, RustImageData},
+	Clipboard, ClipboardContext, ContentFormat,
+};
+
+#[test]
+fn test_set_image_writes_png_and_tiff() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	ctx.set_image(RustImageData::from_path("tests/test.png").unwrap())
+		.unwrap();
+
+	assert!(ctx.has(ContentFormat::Image));
+
+	let formats = ctx.available_formats().unwrap();
+	assert!(formats.contains(&"public.png".to_string()));
+	assert!(formats.contains(&"public.tiff".to_string()));
+
+	let tiff = ctx.get_buffer("public.tiff").unwrap();
+	let from_tiff = RustImageData::from_bytes(&tiff).unwrap();
+	assert!(from_tiff.compare_pixels(&image, 0));
+}
+
+#[test]
+fn test_owner_of_own_write() {
+	let ctx = ClipboardContext::new().unwrap();
+	ctx.set_text("written by the test".to_string()).unwrap();
+
+	let owner = ctx.get_owner().unwrap();
+	assert_eq!(owner.pid, Some(std::process::id()));
+	assert!(owner.app_name.is_some());
+	// the same change gives the same owner
+	assert_eq!(ctx.get_owner(), Some(owner));
+}
+
+#[test]
+fn test_get_image_from_tiff_only() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let tiff = image.to_tiff().unwrap();
+	ctx.set_buffer("public.tiff", tiff.get_bytes().to_vec())
+		.unwrap();
+
+	assert!(ctx.has(ContentFormat::Image));
+	let read = ctx.get_image().unwrap();
+	assert_eq!(read.get_size(), image.get_size());
+}
@@ -0,0 +1,20 @@
+use clipboard_rs::{Clipboard, ClipboardContext, ContentFormat};
+
+#[test]
+fn test_markdown() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let markdown = "# Title\n\nSome *emphasis*";
+	ctx.set_markdown(markdown.to_string()).unwrap();
+	assert!(ctx.has(ContentFormat::Markdown));
+	assert_eq!(ctx.get_markdown().unwrap(), markdown);
+	// targets without Markdown support still get the source as text
+	assert_eq!(ctx.get_text().unwrap(), markdown);
+	#[cfg(feature = "markdown")]
+	assert!(ctx.get_html().unwrap().contains("<em>emphasis</em>"));
+
+	// plain text is returned when there is no Markdown
+	ctx.set_text("just text".to_string()).unwrap();
+	assert!(!ctx.has(ContentFormat::Markdown));
+	assert_eq!(ctx.get_markdown().unwrap(), "just text");
+}
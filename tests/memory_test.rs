@@ -0,0 +1,20 @@
+use clipboard_rs::{Clipboard, ClipboardContent, ContentFormat, MemoryClipboardContext};
+
+#[test]
+fn test_memory_clipboard() {
+	let ctx = MemoryClipboardContext::new().unwrap();
+	ctx.set(vec![
+		ClipboardContent::Text("plain".to_string()),
+		ClipboardContent::Html("<b>html</b>".to_string()),
+	])
+	.unwrap();
+
+	// every instance shares the same content
+	let other = MemoryClipboardContext::new().unwrap();
+	assert!(other.has(ContentFormat::Html));
+	assert_eq!(other.get_text().unwrap(), "plain");
+	assert_eq!(other.get_html().unwrap(), "<b>html</b>");
+
+	other.clear().unwrap();
+	assert!(ctx.is_empty());
+}
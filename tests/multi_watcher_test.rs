@@ -0,0 +1,41 @@
+mod common;
+
+use clipboard_rs::{
+	Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
+};
+use common::{recv_distinct, while_watching};
+use std::sync::mpsc::{self, Sender};
+
+struct Notifier {
+	name: &'static str,
+	sender: Sender<&'static str>,
+}
+
+impl ClipboardHandler for Notifier {
+	fn on_clipboard_change(&mut self) {
+		let _ = self.sender.send(self.name);
+	}
+}
+
+#[test]
+fn test_multiple_watchers() {
+	let ctx = ClipboardContext::new().unwrap();
+	let (tx, rx) = mpsc::channel();
+
+	let watchers = ["first", "second"]
+		.into_iter()
+		.map(|name| {
+			let mut watcher = ClipboardWatcherContext::new().unwrap();
+			watcher.add_handler(Notifier {
+				name,
+				sender: tx.clone(),
+			});
+			watcher
+		})
+		.collect();
+
+	while_watching(watchers, || {
+		ctx.set_text("seen by both watchers".to_string()).unwrap();
+		assert_eq!(recv_distinct(&rx, 2), vec!["first", "second"]);
+	});
+}
@@ -0,0 +1,20 @@
+#![cfg(target_os = "linux")]
+
+// the environment is shared by the whole test binary, so this test lives in its own file
+use clipboard_rs::{ClipboardContext, ClipboardError};
+
+#[test]
+fn test_no_display() {
+	std::env::remove_var("CLIPBOARD_RS_BACKEND");
+	std::env::set_var("DISPLAY", "");
+	std::env::remove_var("WAYLAND_DISPLAY");
+
+	let err = ClipboardContext::new().err().expect("expected an error");
+	match err.downcast_ref::<ClipboardError>() {
+		Some(ClipboardError::PlatformError { code, message }) => {
+			assert_eq!(*code, 0);
+			assert!(message.contains("DISPLAY"));
+		}
+		other => panic!("unexpected error: {:?}", other),
+	}
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "debug-utils")]
+
+mod common;
+
+use clipboard_rs::common::RustImage;
+use clipboard_rs::debug_utils::{ClipboardOperation, ClipboardRecorder};
+use clipboard_rs::{Clipboard, ClipboardContent, ContentFormat, RustImageData};
+use common::MockClipboard;
+
+#[test]
+fn test_recorder_log() {
+	let recorder = ClipboardRecorder::new(MockClipboard::new());
+
+	recorder.set_text("recorded".to_string()).unwrap();
+	assert!(recorder.has(ContentFormat::Text));
+	assert_eq!(recorder.get_text().unwrap(), "recorded");
+	assert!(recorder.get_html().is_err());
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let size = image.get_size();
+	recorder.set_image(image).unwrap();
+	recorder
+		.set(vec![
+			ClipboardContent::Text("a".to_string()),
+			ClipboardContent::Html("<b>a</b>".to_string()),
+		])
+		.unwrap();
+
+	let log = recorder.drain_log();
+	assert_eq!(log.len(), 6);
+	assert_eq!(log[0], ClipboardOperation::SetText("recorded".to_string()));
+	assert_eq!(
+		log[1],
+		ClipboardOperation::Has {
+			format: ContentFormat::Text,
+			result: true
+		}
+	);
+	assert_eq!(
+		log[2],
+		ClipboardOperation::GetText(Ok("recorded".to_string()))
+	);
+	assert!(matches!(log[3], ClipboardOperation::GetHtml(Err(_))));
+	assert_eq!(log[4], ClipboardOperation::SetImage { size });
+	assert_eq!(
+		log[5],
+		ClipboardOperation::Set(vec![ContentFormat::Text, ContentFormat::Html])
+	);
+
+	assert!(recorder.drain_log().is_empty());
+}
+
+#[test]
+fn test_recorder_forwards_provided_methods() {
+	let recorder = ClipboardRecorder::new(MockClipboard::new());
+
+	recorder.set_html_raw("<b>raw</b>".to_string()).unwrap();
+	assert!(recorder.has_any(&[ContentFormat::Rtf, ContentFormat::Html]));
+	recorder.append_text("first").unwrap();
+	assert_eq!(recorder.get_text_bytes().unwrap(), b"first");
+	let snapshot = recorder.snapshot().unwrap();
+	recorder.restore(snapshot).unwrap();
+
+	let log = recorder.drain_log();
+	assert_eq!(log.len(), 6);
+	assert_eq!(
+		log[0],
+		ClipboardOperation::SetHtmlRaw("<b>raw</b>".to_string())
+	);
+	assert_eq!(
+		log[1],
+		ClipboardOperation::HasAny {
+			formats: vec![ContentFormat::Rtf, ContentFormat::Html],
+			result: true
+		}
+	);
+	assert_eq!(
+		log[3],
+		ClipboardOperation::GetTextBytes(Ok(b"first".to_vec()))
+	);
+	assert!(matches!(&log[4], ClipboardOperation::Snapshot(Ok(formats)) if !formats.is_empty()));
+	assert!(matches!(&log[5], ClipboardOperation::Restore(formats) if !formats.is_empty()));
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use clipboard_rs::{
+	Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
+};
+use common::{recv_event, while_watching};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+struct Notifier {
+	name: &'static str,
+	sender: Sender<&'static str>,
+}
+
+impl ClipboardHandler for Notifier {
+	fn on_clipboard_change(&mut self) {
+		let _ = self.sender.send(self.name);
+	}
+}
+
+#[test]
+fn test_remove_handler() {
+	let ctx = ClipboardContext::new().unwrap();
+	let (tx, rx) = mpsc::channel();
+	let notifier = |name| Notifier {
+		name,
+		sender: tx.clone(),
+	};
+
+	let mut watcher = ClipboardWatcherContext::new().unwrap();
+	watcher
+		.add_handler(notifier("first"))
+		.add_named_handler("document", notifier("document"))
+		.add_handler(notifier("last"));
+	// a handler with the same name replaces the previous one
+	watcher.add_named_handler("document", notifier("replaced"));
+
+	assert!(watcher.remove_handler(3).is_none());
+	assert_eq!(watcher.remove_handler(0).unwrap().name, "first");
+	assert_eq!(
+		watcher.remove_named_handler("document").unwrap().name,
+		"replaced"
+	);
+	assert!(watcher.remove_named_handler("document").is_none());
+
+	while_watching(vec![watcher], || {
+		ctx.set_text("only the last handler remains".to_string())
+			.unwrap();
+		assert_eq!(recv_event(&rx), "last");
+		assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+	});
+}
@@ -0,0 +1,21 @@
+use clipboard_rs::{wrap_plain_as_rtf, Clipboard, ClipboardContext};
+
+#[test]
+fn test_set_rich_text_checked() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	assert!(ctx
+		.set_rich_text_checked("no rtf header".to_string())
+		.is_err());
+	assert!(ctx
+		.set_rich_text_checked("{\\rtf1\\ansi {unbalanced}".to_string())
+		.is_err());
+
+	let rtf = wrap_plain_as_rtf("braces {} and \\ backslash\nsecond line é");
+	assert_eq!(
+		rtf,
+		"{\\rtf1\\ansi\\deff0 braces \\{\\} and \\\\ backslash\\par\nsecond line \\u233?}"
+	);
+	ctx.set_rich_text_checked(rtf.clone()).unwrap();
+	assert_eq!(ctx.get_rich_text().unwrap(), rtf);
+}
@@ -0,0 +1,19 @@
+use clipboard_rs::{Clipboard, ClipboardContext};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_has_changed_since() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let last = ctx.get_sequence_number().unwrap();
+	assert!(!ctx.has_changed_since(last).unwrap());
+
+	ctx.set_text("sequence".to_string()).unwrap();
+	// on X11 the counter follows the selection events, which arrive asynchronously
+	let deadline = Instant::now() + Duration::from_secs(1);
+	while !ctx.has_changed_since(last).unwrap() && Instant::now() < deadline {
+		thread::sleep(Duration::from_millis(10));
+	}
+	assert!(ctx.has_changed_since(last).unwrap());
+}
@@ -0,0 +1,16 @@
+mod common;
+
+use clipboard_rs::{Clipboard, ClipboardError};
+use common::MockClipboard;
+
+#[test]
+fn test_sequence_number_unsupported() {
+	let clipboard = MockClipboard::new();
+
+	let error = clipboard.get_sequence_number().unwrap_err();
+	assert!(matches!(
+		error.downcast_ref::<ClipboardError>(),
+		Some(ClipboardError::PlatformError { code: 0, .. })
+	));
+	assert!(clipboard.has_changed_since(0).is_err());
+}
@@ -0,0 +1,50 @@
+use clipboard_rs::{
+	common::{ContentData, RustImage},
+	Clipboard, ClipboardContent, ClipboardContext, ClipboardError, RustImageData,
+};
+
+#[test]
+fn test_size_in_bytes() {
+	assert_eq!(
+		ClipboardContent::Text("héllo".to_string()).size_in_bytes(),
+		6
+	);
+	assert_eq!(
+		ClipboardContent::Html("<b>a</b>".to_string()).size_in_bytes(),
+		8
+	);
+	assert_eq!(
+		ClipboardContent::Files(vec!["/tmp/a".to_string(), "/tmp/bc".to_string()]).size_in_bytes(),
+		13
+	);
+	assert_eq!(
+		ClipboardContent::Other("custom".to_string(), vec![0; 42]).size_in_bytes(),
+		42
+	);
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let (width, height) = image.get_size();
+	assert_eq!(
+		ClipboardContent::Image(image).size_in_bytes(),
+		(width * height * 4) as usize
+	);
+}
+
+#[test]
+fn test_set_with_size_limit() {
+	let ctx = ClipboardContext::new().unwrap();
+	ctx.set_text("before".to_string()).unwrap();
+
+	let contents = vec![
+		ClipboardContent::Text("12345".to_string()),
+		ClipboardContent::Html("<b>12345</b>".to_string()),
+	];
+	let err = ctx.set_with_size_limit(contents.clone(), 16).unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<ClipboardError>(),
+		Some(&ClipboardError::Other("payload too large".to_string()))
+	);
+	assert_eq!(ctx.get_text().unwrap(), "before");
+
+	ctx.set_with_size_limit(contents, 17).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "12345");
+}
@@ -0,0 +1,24 @@
+use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext};
+
+#[test]
+fn test_snapshot_and_restore() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let format = "application/x-clipboard-rs-snapshot";
+	ctx.set(vec![
+		ClipboardContent::Text("snapshot text".to_string()),
+		ClipboardContent::Other(format.to_string(), vec![0, 1, 2, 255]),
+	])
+	.unwrap();
+
+	let snapshot = ctx.snapshot().unwrap();
+	assert!(!snapshot.is_empty());
+	assert_eq!(snapshot.get(format), Some(&[0u8, 1, 2, 255][..]));
+
+	ctx.set_text("temporary".to_string()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "temporary");
+
+	ctx.restore(snapshot).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "snapshot text");
+	assert_eq!(ctx.get_buffer(format).unwrap(), vec![0, 1, 2, 255]);
+}
@@ -36,6 +36,10 @@ fn test_string() {
 	assert_eq!(ctx.get_text().unwrap(), test_plain_txt);
 	assert_eq!(ctx.get_rich_text().unwrap(), test_rich_txt);
 	assert_eq!(ctx.get_html().unwrap(), test_html);
+	assert_eq!(ctx.get_text_lossless().unwrap(), test_plain_txt);
+	assert_eq!(ctx.get_text_bytes().unwrap(), test_plain_txt.as_bytes());
+	assert_eq!(ctx.get_rich_text_lossless().unwrap(), test_rich_txt);
+	assert_eq!(ctx.get_html_lossless().unwrap(), test_html);
 
 	let content_arr = ctx
 		.get(&[ContentFormat::Text, ContentFormat::Rtf, ContentFormat::Html])
@@ -51,4 +55,7 @@ fn test_string() {
 			_ => panic!("unexpected format"),
 		}
 	}
+
+	ctx.clear().unwrap();
+	assert!(ctx.is_empty());
 }
@@ -0,0 +1,23 @@
+use clipboard_rs::common::{RustImage, RustImageData};
+use clipboard_rs::{Clipboard, ClipboardContext};
+
+#[test]
+fn test_try_get() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	ctx.set_text("some text".to_string()).unwrap();
+	assert_eq!(ctx.try_get_text().unwrap(), Some("some text".to_string()));
+	assert!(ctx.try_get_image().unwrap().is_none());
+	assert_eq!(ctx.try_get_files().unwrap(), None);
+
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let size = image.get_size();
+	ctx.set_image(image).unwrap();
+	assert_eq!(ctx.try_get_text().unwrap(), None);
+	assert_eq!(ctx.try_get_image().unwrap().unwrap().get_size(), size);
+
+	ctx.clear().unwrap();
+	assert_eq!(ctx.try_get_text().unwrap(), None);
+	assert!(ctx.try_get_image().unwrap().is_none());
+	assert_eq!(ctx.try_get_files().unwrap(), None);
+}
@@ -0,0 +1,30 @@
+use clipboard_rs::common::RustImage;
+use clipboard_rs::{Clipboard, ClipboardContext, ClipboardSnapshot, RustImageData};
+use std::path::PathBuf;
+
+#[test]
+fn test_typed_read_write() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	ctx.write("typed text").unwrap();
+	let text: String = ctx.read().unwrap();
+	assert_eq!(text, "typed text");
+
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let size = image.get_size();
+	ctx.write(image).unwrap();
+	let image: RustImageData = ctx.read().unwrap();
+	assert_eq!(image.get_size(), size);
+
+	let files = vec![
+		PathBuf::from("/tmp/typed a.txt"),
+		PathBuf::from("/tmp/b.txt"),
+	];
+	ctx.write(files.clone()).unwrap();
+	assert_eq!(ctx.read::<Vec<PathBuf>>().unwrap(), files);
+
+	let snapshot: ClipboardSnapshot = ctx.read().unwrap();
+	ctx.write("overwritten".to_string()).unwrap();
+	ctx.write(snapshot).unwrap();
+	assert_eq!(ctx.read::<Vec<PathBuf>>().unwrap(), files);
+}
@@ -0,0 +1,18 @@
+use clipboard_rs::{Clipboard, ClipboardContent, ClipboardContext, ContentFormat};
+
+#[test]
+fn test_url() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let url = "https://github.com/ChurchTao/clipboard-rs";
+	ctx.set_url(url.to_string()).unwrap();
+	assert!(ctx.has(ContentFormat::Url));
+	assert_eq!(ctx.get_url().unwrap(), url);
+	// programs without URL support get it as text
+	assert_eq!(ctx.get_text().unwrap(), url);
+
+	ctx.set(vec![ClipboardContent::Text("not a url".to_string())])
+		.unwrap();
+	assert!(!ctx.has(ContentFormat::Url));
+	assert!(ctx.get_url().is_err());
+}
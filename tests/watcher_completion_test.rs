@@ -0,0 +1,35 @@
+mod common;
+
+use clipboard_rs::{ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext};
+use common::spawn_watcher;
+use std::thread;
+use std::time::Duration;
+
+struct Manager;
+
+impl ClipboardHandler for Manager {
+	fn on_clipboard_change(&mut self) {}
+}
+
+#[test]
+fn test_wait_for_completion() {
+	let mut watcher = ClipboardWatcherContext::new().unwrap();
+	watcher.add_handler(Manager);
+	let (shutdown, handle) = spawn_watcher(watcher);
+
+	thread::sleep(Duration::from_millis(500));
+	assert!(shutdown.wait_for_completion().is_ok());
+	let watcher = handle.join().unwrap();
+
+	// a watcher that is not running never completes
+	let shutdown = watcher.get_shutdown_channel();
+	assert!(shutdown
+		.wait_for_completion_timeout(Duration::from_millis(200))
+		.is_err());
+
+	// the watcher can be restarted and waited for again
+	let (shutdown, handle) = spawn_watcher(watcher);
+	thread::sleep(Duration::from_millis(500));
+	assert!(shutdown.wait_for_completion().is_ok());
+	handle.join().unwrap();
+}
@@ -0,0 +1,140 @@
+use clipboard_rs::{
+	common::{RustImage, RustImageData},
+	Clipboard, ClipboardContext, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
+	ContentFormat,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+struct Counter {
+	count: Arc<AtomicUsize>,
+}
+
+impl ClipboardHandler for Counter {
+	fn on_clipboard_change(&mut self) {
+		self.count.fetch_add(1, Ordering::SeqCst);
+	}
+}
+
+#[test]
+fn test_restart_watch() {
+	let ctx = ClipboardContext::new().unwrap();
+	let count = Arc::new(AtomicUsize::new(0));
+
+	let mut watcher = ClipboardWatcherContext::new().unwrap();
+	watcher.add_handler(Counter {
+		count: count.clone(),
+	});
+	let first_shutdown = watcher.get_shutdown_channel();
+	let second_shutdown = watcher.get_shutdown_channel();
+
+	let (session_end_tx, session_end_rx) = mpsc::channel();
+	let handle = thread::spawn(move || {
+		watcher.start_watch();
+		session_end_tx.send(()).unwrap();
+		watcher.start_watch();
+	});
+
+	// first session
+	thread::sleep(Duration::from_secs(1));
+	ctx.set_text("first session".to_string()).unwrap();
+	thread::sleep(Duration::from_millis(1500));
+	assert!(count.load(Ordering::SeqCst) >= 1);
+	first_shutdown.stop();
+	session_end_rx
+		.recv_timeout(Duration::from_secs(5))
+		.expect("the first session did not stop");
+
+	// second session must still receive events
+	let first_session_count = count.load(Ordering::SeqCst);
+	thread::sleep(Duration::from_secs(1));
+	ctx.set_text("second session".to_string()).unwrap();
+	thread::sleep(Duration::from_millis(1500));
+	assert!(count.load(Ordering::SeqCst) > first_session_count);
+	second_shutdown.stop();
+	handle.join().unwrap();
+}
+
+#[test]
+fn test_subscribe() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let mut watcher = ClipboardWatcherContext::<Counter>::new().unwrap();
+	let first = watcher.subscribe();
+	let second = watcher.subscribe();
+	let shutdown = watcher.get_shutdown_channel();
+
+	let handle = thread::spawn(move || {
+		watcher.start_watch();
+	});
+
+	thread::sleep(Duration::from_secs(1));
+	ctx.set_text("subscribe".to_string()).unwrap();
+
+	assert!(first.recv_timeout(Duration::from_secs(3)).is_ok());
+	assert!(second.recv_timeout(Duration::from_secs(3)).is_ok());
+
+	shutdown.stop();
+	handle.join().unwrap();
+}
+
+#[test]
+fn test_dropped_shutdown_keeps_watching() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let mut watcher = ClipboardWatcherContext::<Counter>::new().unwrap();
+	let events = watcher.subscribe();
+	drop(watcher.get_shutdown_channel());
+	let shutdown = watcher.get_shutdown_channel();
+	let shutdown_clone = shutdown.clone();
+
+	let handle = thread::spawn(move || {
+		watcher.start_watch();
+	});
+
+	thread::sleep(Duration::from_secs(1));
+	drop(shutdown);
+	ctx.set_text("dropped shutdown".to_string()).unwrap();
+	assert!(events.recv_timeout(Duration::from_secs(3)).is_ok());
+
+	shutdown_clone.stop();
+	handle.join().unwrap();
+}
+
+#[test]
+fn test_filtered_handler() {
+	let ctx = ClipboardContext::new().unwrap();
+	let image_count = Arc::new(AtomicUsize::new(0));
+
+	let mut watcher = ClipboardWatcherContext::new().unwrap();
+	watcher.add_filtered_handler(
+		Counter {
+			count: image_count.clone(),
+		},
+		vec![ContentFormat::Image],
+	);
+	let events = watcher.subscribe();
+	let shutdown = watcher.get_shutdown_channel();
+
+	let handle = thread::spawn(move || {
+		watcher.start_watch();
+	});
+
+	thread::sleep(Duration::from_secs(1));
+	ctx.set_image(RustImageData::from_path("tests/test.png").unwrap())
+		.unwrap();
+	// other tests may change the clipboard at the same time, wait for the image event
+	while image_count.load(Ordering::SeqCst) == 0 {
+		events.recv_timeout(Duration::from_secs(3)).unwrap();
+	}
+	let count = image_count.load(Ordering::SeqCst);
+
+	ctx.set_text("not an image".to_string()).unwrap();
+	events.recv_timeout(Duration::from_secs(3)).unwrap();
+	assert_eq!(image_count.load(Ordering::SeqCst), count);
+
+	shutdown.stop();
+	handle.join().unwrap();
+}
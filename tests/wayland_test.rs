@@ -0,0 +1,90 @@
+#![cfg(all(target_os = "linux", feature = "wayland"))]
+
+use clipboard_rs::{
+	common::{RustImage, RustImageData},
+	Clipboard, ClipboardContent, ClipboardHandler, ClipboardWatcher, ContentFormat, FileOperation,
+	WaylandClipboardContext, WaylandClipboardWatcherContext,
+};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+struct NoopHandler;
+
+impl ClipboardHandler for NoopHandler {}
+
+#[test]
+fn test_wayland_text() {
+	let ctx = WaylandClipboardContext::new().unwrap();
+	ctx.set_text("hello wayland".to_string()).unwrap();
+
+	assert!(ctx.has(ContentFormat::Text));
+	assert_eq!(ctx.get_text().unwrap(), "hello wayland");
+
+	ctx.clear().unwrap();
+	assert!(ctx.is_empty());
+}
+
+#[test]
+fn test_wayland_html_and_text() {
+	let ctx = WaylandClipboardContext::new().unwrap();
+	ctx.set(vec![
+		ClipboardContent::Text("hello".to_string()),
+		ClipboardContent::Html("<b>hello</b>".to_string()),
+	])
+	.unwrap();
+
+	assert!(ctx.has_all(&[ContentFormat::Text, ContentFormat::Html]));
+	assert_eq!(ctx.get_text().unwrap(), "hello");
+	assert_eq!(ctx.get_html().unwrap(), "<b>hello</b>");
+}
+
+#[test]
+fn test_wayland_image() {
+	let ctx = WaylandClipboardContext::new().unwrap();
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let size = image.get_size();
+	ctx.set_image(image).unwrap();
+
+	assert!(ctx.has(ContentFormat::Image));
+	assert!(ctx
+		.available_formats()
+		.unwrap()
+		.contains(&"image/png".to_string()));
+	assert_eq!(ctx.get_image().unwrap().get_size(), size);
+}
+
+#[test]
+fn test_wayland_files() {
+	let ctx = WaylandClipboardContext::new().unwrap();
+	ctx.set_files_with_operation(vec!["/tmp/a.txt".to_string()], FileOperation::Cut)
+		.unwrap();
+
+	assert!(ctx.has(ContentFormat::Files));
+	let (operation, files) = ctx.get_files_with_operation().unwrap();
+	assert_eq!(operation, FileOperation::Cut);
+	assert_eq!(files, vec!["file:///tmp/a.txt".to_string()]);
+}
+
+#[test]
+fn test_wayland_watcher() {
+	let ctx = WaylandClipboardContext::new().unwrap();
+
+	let mut watcher = WaylandClipboardWatcherContext::<NoopHandler>::new().unwrap();
+	let events = watcher.subscribe();
+	let shutdown = watcher.get_shutdown_channel();
+	let (done_tx, done_rx) = mpsc::channel();
+	thread::spawn(move || {
+		watcher.start_watch();
+		done_tx.send(()).unwrap();
+	});
+
+	thread::sleep(Duration::from_secs(1));
+	ctx.set_text("watched".to_string()).unwrap();
+	assert!(events.recv_timeout(Duration::from_secs(3)).is_ok());
+
+	shutdown.stop();
+	done_rx
+		.recv_timeout(Duration::from_secs(3))
+		.expect("the watcher did not stop");
+}
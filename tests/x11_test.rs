@@ -0,0 +1,790 @@
+#![cfg(target_os = "linux")]
+
+use clipboard_rs::{
+	common::{RustImage, RustImageData},
+	Clipboard, ClipboardContent, ClipboardContext, ClipboardContextBuilder,
+	ClipboardContextX11Options, ClipboardHandler, ClipboardWatcher, ClipboardWatcherContext,
+	ContentFormat, FileOperation, XSelection,
+};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+	AtomEnum, ChangeWindowAttributesAux, ConnectionExt, CreateWindowAux, EventMask, PropMode,
+	Property, SelectionNotifyEvent, WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::COPY_DEPTH_FROM_PARENT;
+
+#[test]
+fn test_ownership_lost_callback() {
+	let owner = ClipboardContext::new().unwrap();
+	let other = ClipboardContext::new().unwrap();
+
+	let (tx, rx) = mpsc::channel();
+	owner
+		.set_ownership_lost_callback(move || {
+			let _ = tx.send(());
+		})
+		.unwrap();
+
+	owner
+		.set_text("owned by the first context".to_string())
+		.unwrap();
+	other
+		.set_text("owned by the second context".to_string())
+		.unwrap();
+
+	assert!(rx.recv_timeout(Duration::from_secs(3)).is_ok());
+}
+
+#[test]
+fn test_get_text_lossless() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let invalid = vec![b'a', 0xff, b'b'];
+	ctx.set_buffer("UTF8_STRING", invalid.clone()).unwrap();
+
+	assert_eq!(ctx.get_text_bytes().unwrap(), invalid);
+	assert!(ctx.get_text_lossless().is_err());
+	assert_eq!(ctx.get_text().unwrap(), "a\u{fffd}b");
+}
+
+// owns CLIPBOARD and serves `data` through INCR, waiting `delay` before every chunk
+fn spawn_slow_owner(data: Vec<u8>, chunk_size: usize, delay: Duration) {
+	let (ready_tx, ready_rx) = mpsc::channel();
+	thread::spawn(move || {
+		let (conn, screen) = x11rb::connect(None).unwrap();
+		let root = conn.setup().roots[screen].root;
+		let win = conn.generate_id().unwrap();
+		conn.create_window(
+			COPY_DEPTH_FROM_PARENT,
+			win,
+			root,
+			0,
+			0,
+			1,
+			1,
+			0,
+			WindowClass::INPUT_OUTPUT,
+			0,
+			&CreateWindowAux::new(),
+		)
+		.unwrap();
+		let intern = |name: &str| {
+			conn.intern_atom(false, name.as_bytes())
+				.unwrap()
+				.reply()
+				.unwrap()
+				.atom
+		};
+		let clipboard = intern("CLIPBOARD");
+		let utf8 = intern("UTF8_STRING");
+		let incr = intern("INCR");
+		conn.set_selection_owner(win, clipboard, x11rb::CURRENT_TIME)
+			.unwrap();
+		conn.flush().unwrap();
+		ready_tx.send(()).unwrap();
+
+		let mut transfer = None;
+		let mut offset = 0;
+		loop {
+			match conn.wait_for_event().unwrap() {
+				Event::SelectionRequest(event) => {
+					let property = if event.target == utf8 {
+						conn.change_window_attributes(
+							event.requestor,
+							&ChangeWindowAttributesAux::new()
+								.event_mask(EventMask::PROPERTY_CHANGE),
+						)
+						.unwrap();
+						conn.change_property32(
+							PropMode::REPLACE,
+							event.requestor,
+							event.property,
+							incr,
+							&[data.len() as u32],
+						)
+						.unwrap();
+						transfer = Some((event.requestor, event.property));
+						event.property
+					} else {
+						AtomEnum::NONE.into()
+					};
+					let notify = SelectionNotifyEvent {
+						response_type: SELECTION_NOTIFY_EVENT,
+						sequence: 0,
+						time: event.time,
+						requestor: event.requestor,
+						selection: event.selection,
+						target: event.target,
+						property,
+					};
+					conn.send_event(false, event.requestor, EventMask::NO_EVENT, notify)
+						.unwrap();
+					conn.flush().unwrap();
+				}
+				Event::PropertyNotify(event) if event.state == Property::DELETE => {
+					let Some((requestor, property)) = transfer else {
+						continue;
+					};
+					if event.window != requestor || event.atom != property {
+						continue;
+					}
+					thread::sleep(delay);
+					let end = (offset + chunk_size).min(data.len());
+					conn.change_property8(
+						PropMode::REPLACE,
+						requestor,
+						property,
+						utf8,
+						&data[offset..end],
+					)
+					.unwrap();
+					conn.flush().unwrap();
+					if offset == end {
+						// the zero-length chunk ends the transfer
+						return;
+					}
+					offset = end;
+				}
+				_ => (),
+			}
+		}
+	});
+	ready_rx.recv().unwrap();
+}
+
+#[test]
+fn test_read_timeout_is_idle_timeout() {
+	let text = "slow owner ".repeat(100);
+	let progress = Arc::new(Mutex::new(Vec::new()));
+	let progress_clone = progress.clone();
+	let mut options = ClipboardContextX11Options::default();
+	options.read_timeout = Some(Duration::from_millis(300));
+	options.progress_callback = Some(Arc::new(move |received, total| {
+		progress_clone.lock().unwrap().push((received, total));
+	}));
+	let ctx = ClipboardContext::new_with_options(options).unwrap();
+
+	// the whole transfer takes longer than the timeout, but every chunk arrives in time
+	spawn_slow_owner(text.clone().into_bytes(), 200, Duration::from_millis(150));
+
+	assert_eq!(ctx.get_text().unwrap(), text);
+
+	let progress = progress.lock().unwrap();
+	assert!(progress.len() > 1);
+	assert_eq!(progress.last(), Some(&(text.len(), Some(text.len()))));
+}
+
+struct NoopHandler;
+
+impl ClipboardHandler for NoopHandler {}
+
+#[test]
+fn test_watch_primary_selection() {
+	let primary = ClipboardContextBuilder::default()
+		.with_selection(XSelection::Primary)
+		.build()
+		.unwrap();
+
+	let mut watcher = ClipboardWatcherContext::<NoopHandler>::new().unwrap();
+	watcher.watch_selections(&[XSelection::Clipboard, XSelection::Primary]);
+	let events = watcher.subscribe();
+	let shutdown = watcher.get_shutdown_channel();
+
+	let handle = thread::spawn(move || {
+		watcher.start_watch();
+	});
+
+	thread::sleep(Duration::from_secs(1));
+	primary.set_text("middle-click buffer".to_string()).unwrap();
+
+	let event = events.recv_timeout(Duration::from_secs(3)).unwrap();
+	assert_eq!(event.selection, XSelection::Primary);
+
+	shutdown.stop();
+	handle.join().unwrap();
+}
+
+#[test]
+fn test_primary_selection_is_separate() {
+	let clipboard = ClipboardContext::new().unwrap();
+	let primary = ClipboardContext::new_for_selection(XSelection::Primary).unwrap();
+
+	clipboard.set_text("copied".to_string()).unwrap();
+	primary.set_text("selected".to_string()).unwrap();
+
+	assert_eq!(primary.get_text().unwrap(), "selected");
+	assert_eq!(clipboard.get_text().unwrap(), "copied");
+}
+
+#[test]
+fn test_large_buffer_round_trip() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	// larger than both the maximum request length and a single INCR chunk
+	let data: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+	ctx.set_buffer("application/x-clipboard-rs-large", data.clone())
+		.unwrap();
+
+	let read = ctx.get_buffer("application/x-clipboard-rs-large").unwrap();
+	assert_eq!(read.len(), data.len());
+	assert!(read == data);
+
+	let mut streamed = Vec::new();
+	ctx.read_streaming("application/x-clipboard-rs-large", &mut streamed)
+		.unwrap();
+	assert!(streamed == data);
+}
+
+#[test]
+fn test_large_buffer_between_contexts() {
+	let writer = ClipboardContext::new().unwrap();
+	let reader = ClipboardContext::new().unwrap();
+
+	// the reader is a different X client, so the data has to be streamed through INCR
+	let data: Vec<u8> = (0..5 * 1024 * 1024).map(|i| (i % 253) as u8).collect();
+	writer
+		.set_buffer("application/x-clipboard-rs-large", data.clone())
+		.unwrap();
+
+	let read = reader
+		.get_buffer("application/x-clipboard-rs-large")
+		.unwrap();
+	assert_eq!(read.len(), data.len());
+	assert!(read == data);
+}
+
+#[test]
+fn test_get_text_latin1_fallback() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	// like xterm, only offer STRING, which is Latin-1
+	ctx.set_buffer("STRING", vec![b'c', b'a', b'f', 0xe9])
+		.unwrap();
+
+	assert_eq!(ctx.get_text().unwrap(), "café");
+}
+
+#[test]
+fn test_get_text_bytes_latin1_fallback() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	// like xterm, only offer STRING, the bytes are transcoded to UTF-8
+	ctx.set_buffer("STRING", vec![b'c', b'a', b'f', 0xe9])
+		.unwrap();
+
+	assert_eq!(ctx.get_text_bytes().unwrap(), "café".as_bytes());
+	assert_eq!(ctx.get_text_lossless().unwrap(), "café");
+}
+
+#[test]
+fn test_get_text_falls_through_refused_targets() {
+	let (ready_tx, ready_rx) = mpsc::channel();
+	thread::spawn(move || {
+		let (conn, screen) = x11rb::connect(None).unwrap();
+		let root = conn.setup().roots[screen].root;
+		let win = conn.generate_id().unwrap();
+		conn.create_window(
+			COPY_DEPTH_FROM_PARENT,
+			win,
+			root,
+			0,
+			0,
+			1,
+			1,
+			0,
+			WindowClass::INPUT_OUTPUT,
+			0,
+			&CreateWindowAux::new(),
+		)
+		.unwrap();
+		let intern = |name: &[u8]| conn.intern_atom(false, name).unwrap().reply().unwrap().atom;
+		let clipboard = intern(b"CLIPBOARD");
+		let targets = intern(b"TARGETS");
+		let utf8 = intern(b"UTF8_STRING");
+		let string: u32 = AtomEnum::STRING.into();
+		conn.set_selection_owner(win, clipboard, x11rb::CURRENT_TIME)
+			.unwrap();
+		conn.flush().unwrap();
+		ready_tx.send(()).unwrap();
+
+		// offers UTF8_STRING but only converts to STRING
+		loop {
+			if let Event::SelectionRequest(event) = conn.wait_for_event().unwrap() {
+				let property = if event.target == targets {
+					conn.change_property32(
+						PropMode::REPLACE,
+						event.requestor,
+						event.property,
+						AtomEnum::ATOM,
+						&[targets, utf8, string],
+					)
+					.unwrap();
+					event.property
+				} else if event.target == string {
+					conn.change_property8(
+						PropMode::REPLACE,
+						event.requestor,
+						event.property,
+						AtomEnum::STRING,
+						b"fallback",
+					)
+					.unwrap();
+					event.property
+				} else {
+					AtomEnum::NONE.into()
+				};
+				let notify = SelectionNotifyEvent {
+					response_type: SELECTION_NOTIFY_EVENT,
+					sequence: 0,
+					time: event.time,
+					requestor: event.requestor,
+					selection: event.selection,
+					target: event.target,
+					property,
+				};
+				conn.send_event(false, event.requestor, EventMask::NO_EVENT, notify)
+					.unwrap();
+				conn.flush().unwrap();
+			}
+		}
+	});
+	ready_rx.recv().unwrap();
+
+	let ctx = ClipboardContext::new().unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "fallback");
+}
+
+#[test]
+fn test_get_image_without_png() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	let image = RustImageData::from_path("tests/test.png").unwrap();
+	let jpeg = image.to_jpeg().unwrap();
+	ctx.set_buffer("image/jpeg", jpeg.get_bytes().to_vec())
+		.unwrap();
+
+	let read = ctx.get_image().unwrap();
+	assert_eq!(read.get_size(), image.get_size());
+
+	ctx.set_text("no image here".to_string()).unwrap();
+	let err = ctx.get_image().expect_err("expected an error");
+	assert!(err.to_string().contains("UTF8_STRING"));
+}
+
+#[test]
+fn test_get_rich_text_and_html_aliases() {
+	let ctx = ClipboardContext::new().unwrap();
+
+	ctx.set_buffer("text/richtext", b"{\\rtf1 alias}".to_vec())
+		.unwrap();
+	assert!(ctx.has(ContentFormat::Rtf));
+	assert_eq!(ctx.get_rich_text().unwrap(), "{\\rtf1 alias}");
+
+	ctx.set_buffer("text/html;charset=utf-8", b"<b>alias</b>".to_vec())
+		.unwrap();
+	assert!(ctx.has(ContentFormat::Html));
+	assert_eq!(ctx.get_html().unwrap(), "<b>alias</b>");
+}
+
+#[test]
+fn test_targets_cache_follows_writes() {
+	let ctx = ClipboardContext::new().unwrap();
+	let mut options = ClipboardContextX11Options::default();
+	options.disable_targets_cache = true;
+	let uncached = ClipboardContext::new_with_options(options).unwrap();
+
+	ctx.set_text("cached targets".to_string()).unwrap();
+	assert!(ctx.has(ContentFormat::Text));
+	assert!(!ctx.has(ContentFormat::Image));
+
+	// writing invalidates the cache right away
+	ctx.set_image(RustImageData::from_path("tests/test.png").unwrap())
+		.unwrap();
+	assert!(ctx.has(ContentFormat::Image));
+	assert!(!ctx.has(ContentFormat::Text));
+	assert!(uncached.has(ContentFormat::Image));
+}
+
+#[test]
+fn test_connect_to_display() {
+	let mut options = ClipboardContextX11Options::default();
+	options.display = Some(":1".to_string());
+	// only meaningful when a second X server runs on :1
+	let ctx = match ClipboardContext::new_with_options(options) {
+		Ok(ctx) => ctx,
+		Err(e) => {
+			println!("skipping, display :1 is not available: {}", e);
+			return;
+		}
+	};
+	ctx.set_text("hello display :1".to_string()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "hello display :1");
+}
+
+#[test]
+fn test_ownership_retries() {
+	let (ready_tx, ready_rx) = mpsc::channel();
+	thread::spawn(move || {
+		let (conn, screen) = x11rb::connect(None).unwrap();
+		let root = conn.setup().roots[screen].root;
+		let win = conn.generate_id().unwrap();
+		conn.create_window(
+			COPY_DEPTH_FROM_PARENT,
+			win,
+			root,
+			0,
+			0,
+			1,
+			1,
+			0,
+			WindowClass::INPUT_OUTPUT,
+			0,
+			&CreateWindowAux::new(),
+		)
+		.unwrap();
+		let clipboard = conn
+			.intern_atom(false, b"CLIPBOARD")
+			.unwrap()
+			.reply()
+			.unwrap()
+			.atom;
+		conn.set_selection_owner(win, clipboard, x11rb::CURRENT_TIME)
+			.unwrap();
+		conn.flush().unwrap();
+		ready_tx.send(()).unwrap();
+
+		// like an eager clipboard manager, grab the selection back once
+		loop {
+			if let Event::SelectionClear(_) = conn.wait_for_event().unwrap() {
+				conn.set_selection_owner(win, clipboard, x11rb::CURRENT_TIME)
+					.unwrap();
+				conn.flush().unwrap();
+				break;
+			}
+		}
+	});
+	ready_rx.recv().unwrap();
+
+	let mut options = ClipboardContextX11Options::default();
+	options.ownership_retries = 3;
+	let ctx = ClipboardContext::new_with_options(options).unwrap();
+	ctx.set_text("kept after retrying".to_string()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "kept after retrying");
+}
+
+#[test]
+fn test_write_without_ownership_check() {
+	let mut options = ClipboardContextX11Options::default();
+	options.ownership_check = false;
+	let ctx = ClipboardContext::new_with_options(options).unwrap();
+	ctx.set_text("written without confirmation".to_string())
+		.unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "written without confirmation");
+}
+
+#[test]
+fn test_write_timeout_and_poll_interval() {
+	let mut options = ClipboardContextX11Options::default();
+	options.write_timeout = None;
+	options.poll_interval = Duration::from_millis(5);
+	let ctx = ClipboardContext::new_with_options(options).unwrap();
+	ctx.set_text("no write timeout".to_string()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "no write timeout");
+
+	let mut options = ClipboardContextX11Options::default();
+	options.write_timeout = Some(Duration::from_millis(100));
+	let ctx = ClipboardContext::new_with_options(options).unwrap();
+	ctx.set_text("short write timeout".to_string()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "short write timeout");
+}
+
+#[test]
+fn test_drop_releases_connections() {
+	let open_fds = || std::fs::read_dir("/proc/self/fd").unwrap().count();
+	// the first context may open files that stay open for the whole process
+	drop(ClipboardContext::new().unwrap());
+
+	let before = open_fds();
+	for _ in 0..100 {
+		drop(ClipboardContext::new().unwrap());
+	}
+	// other tests running at the same time may hold a few connections
+	assert!(open_fds() <= before + 10);
+}
+
+#[test]
+fn test_drop_does_not_wait_for_clipboard_manager() {
+	let ctx = ClipboardContext::new().unwrap();
+	ctx.set_text("not handed over".to_string()).unwrap();
+	let start = Instant::now();
+	drop(ctx);
+	assert!(start.elapsed() < Duration::from_secs(1));
+
+	// the handover on drop is bounded by the option
+	let mut options = ClipboardContextX11Options::default();
+	options.persist_on_drop_timeout = Some(Duration::from_millis(100));
+	let ctx = ClipboardContext::new_with_options(options).unwrap();
+	ctx.set_text("handed over".to_string()).unwrap();
+	let start = Instant::now();
+	drop(ctx);
+	assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_clear_gives_up_selection() {
+	let writer = ClipboardContext::new().unwrap();
+	writer.set_text("to be cleared".to_string()).unwrap();
+	writer.clear().unwrap();
+
+	let (conn, _) = x11rb::connect(None).unwrap();
+	let clipboard = conn
+		.intern_atom(false, b"CLIPBOARD")
+		.unwrap()
+		.reply()
+		.unwrap()
+		.atom;
+	let owner = conn
+		.get_selection_owner(clipboard)
+		.unwrap()
+		.reply()
+		.unwrap()
+		.owner;
+	assert_eq!(owner, x11rb::NONE);
+
+	let reader = ClipboardContext::new().unwrap();
+	assert!(reader.available_formats().unwrap().is_empty());
+	assert!(!reader.has(ContentFormat::Text));
+	assert!(reader.is_empty());
+}
+
+#[test]
+fn test_set_text_targets() {
+	let writer = ClipboardContext::new().unwrap();
+	writer
+		.set(vec![ClipboardContent::Text("plain text".to_string())])
+		.unwrap();
+
+	// a second connection sees the same text targets as from a GTK application
+	let reader = ClipboardContext::new().unwrap();
+	let formats = reader.available_formats().unwrap();
+	for target in [
+		"UTF8_STRING",
+		"text/plain;charset=utf-8",
+		"text/plain;charset=UTF-8",
+		"STRING",
+		"TEXT",
+	] {
+		assert!(formats.contains(&target.to_string()), "missing {}", target);
+	}
+	assert_eq!(reader.get_buffer("STRING").unwrap(), b"plain text");
+	assert_eq!(
+		reader.get_buffer("text/plain;charset=utf-8").unwrap(),
+		b"plain text"
+	);
+}
+
+#[test]
+fn test_serve_text_aliases() {
+	let writer = ClipboardContext::new().unwrap();
+	writer.set_text("café ✓".to_string()).unwrap();
+
+	let reader = ClipboardContext::new().unwrap();
+	let formats = reader.available_formats().unwrap();
+	assert!(formats.contains(&"STRING".to_string()));
+	assert!(formats.contains(&"TEXT".to_string()));
+	assert_eq!(reader.get_buffer("STRING").unwrap(), b"caf\xe9 ?");
+	assert_eq!(reader.get_buffer("TEXT").unwrap(), "café ✓".as_bytes());
+	assert_eq!(
+		reader.get_buffer("text/plain").unwrap(),
+		"café ✓".as_bytes()
+	);
+
+	writer.set_html("<b>café</b>".to_string()).unwrap();
+	assert_eq!(
+		reader.get_buffer("text/html;charset=utf-8").unwrap(),
+		"<b>café</b>".as_bytes()
+	);
+	// the text aliases go away with the text
+	assert!(reader.get_buffer("STRING").is_err());
+}
+
+#[test]
+fn test_timestamp_target() {
+	let ctx = ClipboardContext::new().unwrap();
+	ctx.set_text("timestamped".to_string()).unwrap();
+
+	let (conn, screen) = x11rb::connect(None).unwrap();
+	let root = conn.setup().roots[screen].root;
+	let win = conn.generate_id().unwrap();
+	conn.create_window(
+		COPY_DEPTH_FROM_PARENT,
+		win,
+		root,
+		0,
+		0,
+		1,
+		1,
+		0,
+		WindowClass::INPUT_OUTPUT,
+		0,
+		&CreateWindowAux::new(),
+	)
+	.unwrap();
+	let intern = |name: &[u8]| conn.intern_atom(false, name).unwrap().reply().unwrap().atom;
+	let clipboard = intern(b"CLIPBOARD");
+	let timestamp = intern(b"TIMESTAMP");
+	let property = intern(b"CLIPBOARD_RS_TEST");
+	let query_timestamp = || {
+		conn.convert_selection(win, clipboard, timestamp, property, x11rb::CURRENT_TIME)
+			.unwrap();
+		conn.flush().unwrap();
+		loop {
+			if let Event::SelectionNotify(event) = conn.wait_for_event().unwrap() {
+				assert_eq!(event.property, property);
+				break;
+			}
+		}
+		let reply = conn
+			.get_property(true, win, property, AtomEnum::INTEGER, 0, 1)
+			.unwrap()
+			.reply()
+			.unwrap();
+		let time = reply.value32().unwrap().next().unwrap();
+		time
+	};
+
+	let first = query_timestamp();
+	assert_ne!(first, x11rb::CURRENT_TIME);
+
+	// every write acquires the selection again with a newer server time
+	thread::sleep(Duration::from_millis(10));
+	ctx.set_text("timestamped again".to_string()).unwrap();
+	let second = query_timestamp();
+	assert!(second > first);
+}
+
+#[test]
+fn test_content_survives_drop() {
+	let (conn, _) = x11rb::connect(None).unwrap();
+	let manager = conn
+		.intern_atom(false, b"CLIPBOARD_MANAGER")
+		.unwrap()
+		.reply()
+		.unwrap()
+		.atom;
+	// only meaningful when a clipboard manager is running
+	if conn
+		.get_selection_owner(manager)
+		.unwrap()
+		.reply()
+		.unwrap()
+		.owner == x11rb::NONE
+	{
+		println!("skipping, no clipboard manager is running");
+		return;
+	}
+
+	{
+		let ctx = ClipboardContext::new().unwrap();
+		ctx.set_text("outlives its context".to_string()).unwrap();
+	}
+
+	let reader = ClipboardContext::new().unwrap();
+	assert_eq!(reader.get_text().unwrap(), "outlives its context");
+}
+
+#[test]
+fn test_getters_fail_without_owner() {
+	// nothing owns the SECONDARY selection in the test session
+	let ctx = ClipboardContextBuilder::default()
+		.with_selection(XSelection::Secondary)
+		.build()
+		.unwrap();
+	assert!(ctx.get_text().is_err());
+	assert!(ctx.get_rich_text().is_err());
+	assert!(ctx.get_html().is_err());
+	assert!(ctx.get_files().is_err());
+
+	// empty data from an owner is not an error
+	ctx.set_text(String::new()).unwrap();
+	assert_eq!(ctx.get_text().unwrap(), "");
+}
+
+#[test]
+fn test_get_files_with_cut_operation() {
+	let ctx = ClipboardContext::new().unwrap();
+	let files = vec!["file:///tmp/clipboard-rs-cut.txt".to_string()];
+
+	// what Nautilus writes when files are cut
+	ctx.set_buffers(vec![
+		(
+			"x-special/gnome-copied-files".to_string(),
+			format!("cut\n{}", files[0]).into_bytes(),
+		),
+		(
+			"text/uri-list".to_string(),
+			format!("# cut files\r\n{}\r\n", files[0]).into_bytes(),
+		),
+	])
+	.unwrap();
+	assert_eq!(
+		ctx.get_files_with_operation().unwrap(),
+		(FileOperation::Cut, files.clone())
+	);
+	assert_eq!(ctx.get_files().unwrap(), files);
+
+	// what Dolphin writes
+	ctx.set_buffers(vec![
+		("text/uri-list".to_string(), files[0].clone().into_bytes()),
+		("application/x-kde-cutselection".to_string(), b"1".to_vec()),
+	])
+	.unwrap();
+	assert_eq!(
+		ctx.get_files_with_operation().unwrap(),
+		(FileOperation::Cut, files.clone())
+	);
+
+	ctx.set_files(files.clone()).unwrap();
+	assert_eq!(
+		ctx.get_files_with_operation().unwrap(),
+		(FileOperation::Copy, files)
+	);
+}
+
+#[test]
+fn test_set_files_with_cut_operation() {
+	let ctx = ClipboardContext::new().unwrap();
+	let files = vec!["file:///tmp/clipboard-rs-cut.txt".to_string()];
+
+	ctx.set_files_with_operation(files.clone(), FileOperation::Cut)
+		.unwrap();
+	for format in [
+		"x-special/gnome-copied-files",
+		"x-special/nautilus-clipboard",
+	] {
+		let data = ctx.get_buffer(format).unwrap();
+		assert!(
+			data.starts_with(b"cut\n"),
+			"{} does not start with cut",
+			format
+		);
+	}
+	assert_eq!(
+		ctx.get_buffer("application/x-kde-cutselection").unwrap(),
+		b"1"
+	);
+	assert_eq!(
+		ctx.get_files_with_operation().unwrap(),
+		(FileOperation::Cut, files.clone())
+	);
+
+	ctx.set_files_with_operation(files.clone(), FileOperation::Copy)
+		.unwrap();
+	let data = ctx.get_buffer("x-special/gnome-copied-files").unwrap();
+	assert!(data.starts_with(b"copy\n"));
+}
@@ -0,0 +1,23 @@
+#![cfg(target_os = "linux")]
+
+// counts the threads of the whole process, so this test lives in its own file
+use clipboard_rs::{Clipboard, ClipboardContext};
+
+fn thread_count() -> usize {
+	std::fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+#[test]
+fn test_contexts_do_not_leak_threads() {
+	let before = thread_count();
+	for i in 0..100 {
+		let ctx = ClipboardContext::new().unwrap();
+		ctx.set_text(format!("context {}", i)).unwrap();
+		if i % 2 == 0 {
+			ctx.close().unwrap();
+		} else {
+			drop(ctx);
+		}
+	}
+	assert_eq!(thread_count(), before);
+}